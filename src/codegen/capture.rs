@@ -0,0 +1,39 @@
+use crate::parser::ast::*;
+
+// referenced_names lists the names a block refers to, in the order they
+// first appear. A closure captures the ones that are variables of the
+// enclosing scope. Names the block declares itself may be listed too; they
+// are captured needlessly but shadowed before use.
+pub fn referenced_names(program: &BlockStmt) -> Vec<String> {
+    let mut names = Names(vec![]);
+    names.visit_program(program);
+    names.0
+}
+
+struct Names(Vec<String>);
+
+impl Names {
+    fn add(&mut self, ident: &Ident) {
+        if !self.0.contains(&ident.0) {
+            self.0.push(ident.0.clone());
+        }
+    }
+}
+
+// A nested closure is walked like any other expression: it captures from
+// this one, so this one has to capture what the nested one uses.
+impl Visitor for Names {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        if let StmtKind::Assignment(ident, _) = &stmt.kind {
+            self.add(ident);
+        }
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        if let ExprKind::Ident(ident) = &expr.kind {
+            self.add(ident);
+        }
+        walk_expr(self, expr);
+    }
+}
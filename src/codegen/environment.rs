@@ -1,12 +1,12 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use crate::codegen::object::*;
-use crate::parser::*;
-use crate::parser::ast::*;
 
 #[derive(Debug, Clone)]
 pub struct Environment {
     pub store: HashMap<String, Object>,
+    // The names bound to a closure's copies of the variables it captures.
+    captured: HashSet<String>,
 }
 
 impl Environment {
@@ -16,8 +16,10 @@ impl Environment {
         store.insert("void".to_string(), Object::Null);
         store.insert("printf".to_string(), Object::BuildIn(BuildIn::Printf));
         store.insert("length".to_string(), Object::BuildIn(BuildIn::Length));
+        store.insert("contains".to_string(), Object::BuildIn(BuildIn::Contains));
+        store.insert("remove".to_string(), Object::BuildIn(BuildIn::Remove));
 
-        Environment { store: store }
+        Environment { store, captured: HashSet::new() }
     }
 
     pub fn get(&self, name: &str) -> Object {
@@ -28,7 +30,31 @@ impl Environment {
     }
 
     pub fn set(&mut self, name: String, value: Object) -> Object {
+        self.captured.remove(&name);
         self.store.insert(name, value.clone());
         value
     }
+
+    // capture binds `name` to the copy of a variable a closure holds.
+    pub fn capture(&mut self, name: String, value: Object) {
+        self.store.insert(name.clone(), value);
+        self.captured.insert(name);
+    }
+
+    pub fn is_captured(&self, name: &str) -> bool {
+        self.captured.contains(name)
+    }
+
+    // function_scope returns the environment a function body starts with:
+    // the builtins and functions, but none of the caller's variables.
+    pub fn function_scope(&self) -> Self {
+        let store = self
+            .store
+            .iter()
+            .filter(|(_, object)| !object.is_variable())
+            .map(|(name, object)| (name.clone(), object.clone()))
+            .collect();
+
+        Environment { store, captured: HashSet::new() }
+    }
 }
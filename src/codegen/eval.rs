@@ -1,12 +1,18 @@
-use crate::parser::*;
-use crate::lexer::*;
+use std::collections::HashMap;
+
 use llvm_sys::*;
 
-use crate::codegen::stack::*;
+use crate::codegen::capture::*;
+use crate::codegen::environment::*;
+use crate::codegen::generics::*;
+use crate::codegen::namespace::*;
 use crate::codegen::object::*;
+use crate::codegen::stack::*;
 
+use crate::lexer::{IntKind, Span};
 use crate::parser::ast::*;
 
+use crate::ir::arithmetic::*;
 use crate::ir::block::*;
 use crate::ir::condition::*;
 use crate::ir::const_value::*;
@@ -15,15 +21,32 @@ use crate::ir::creator::*;
 use crate::ir::function::*;
 use crate::ir::llvm_type::*;
 use crate::ir::operate::*;
-use crate::ir::test_util::*;
-use crate::ir::validate::*;
+use crate::ir::runtime::*;
+
+use crate::loader::Module;
 
+use crate::llvm_bool;
 
 pub struct Eval {
-    pub stack_arg: Vec<Vec<Expr>>,
     pub lc: LLVMCreator,
     pub main_block: *mut LLVMBasicBlock,
     pub function_stack: FunctionStack,
+    pub loop_stack: LoopStack,
+    pub structs: HashMap<String, StructType>,
+    pub enums: HashMap<String, EnumType>,
+    // The wrappers through which named functions are called as closures.
+    pub function_values: HashMap<*mut LLVMValue, *mut LLVMValue>,
+    pub generics: Vec<GenericFunction>,
+    // How many instantiations are being compiled inside each other. A
+    // generic function that calls itself with ever larger types would
+    // otherwise never stop.
+    instantiation_depth: usize,
+}
+
+impl Default for Eval {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[allow(dead_code)]
@@ -34,31 +57,308 @@ impl Eval {
         let (main_block, main_function) = Eval::setup_main(&mut lc);
 
         Eval {
-            stack_arg: Vec::new(),
-            lc: lc,
-            main_block: main_block,
+            lc,
+            main_block,
             function_stack: FunctionStack::new(main_function),
+            loop_stack: LoopStack::new(),
+            structs: HashMap::new(),
+            enums: HashMap::new(),
+            function_values: HashMap::new(),
+            generics: vec![],
+            instantiation_depth: 0,
         }
     }
 
+    fn setup_main(lc: &mut LLVMCreator) -> (*mut LLVMBasicBlock, *mut LLVMValue) {
+        let fn_type = function_type(int32_type(), &mut []);
+        let main_function = add_function(lc.module, fn_type, "main");
+        let main_block = append_basic_block_in_context(lc.context, main_function, "entry");
+        build_position_at_end(lc.builder, main_block);
+        (main_block, main_function)
+    }
+
+    // entry_eval_program compiles a whole program. Types and functions are
+    // declared up front so they can be used before their definition, and
+    // every other top-level statement becomes part of `main`.
     pub fn entry_eval_program(&mut self, program: Program, env: &mut Environment) -> Object {
-        for statement in program.into_iter() {
-            if let Some(mut obj) = self.eval_statement(statement, env) {
-                let llvm_value = unwrap_object(&mut obj);
-                build_ret(self.lc.builder, llvm_value);
-                return obj;
+        let (types, program): (Program, Program) = program
+            .into_iter()
+            .partition(|statement| matches!(statement.kind, StmtKind::Struct { .. } | StmtKind::Enum { .. }));
+        let (functions, statements): (Program, Program) = program
+            .into_iter()
+            .partition(|statement| matches!(statement.kind, StmtKind::Func { .. }));
+
+        if let Object::Error(err) = self.declare_types(types) {
+            return Object::Error(err);
+        }
+        if let Object::Error(err) = self.eval_functions(functions, None, env) {
+            return Object::Error(err);
+        }
+        self.eval_main(statements, env)
+    }
+
+    // entry_eval_modules compiles a program loaded from several files into
+    // one module. The modules come in the order the loader returns them,
+    // each after the ones it imports and the main file last. The functions
+    // and types of an imported file are reached through its name, as in
+    // `shapes.area()` and `shapes.Rect`, and get it as a prefix in the module.
+    pub fn entry_eval_modules(&mut self, modules: Vec<Module>) -> Object {
+        let main = modules.len() - 1;
+        let declared: Vec<Vec<String>> = modules
+            .iter()
+            .map(|module| {
+                module
+                    .program
+                    .iter()
+                    .filter_map(|statement| match &statement.kind {
+                        StmtKind::Struct { name, .. } | StmtKind::Enum { name, .. } => Some(name.0.clone()),
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .collect();
+        let module_names: Vec<String> = modules.iter().map(|module| module.name.clone()).collect();
+
+        let mut files = vec![];
+        let mut types = vec![];
+        for (index, mut module) in modules.into_iter().enumerate() {
+            let mut names = HashMap::new();
+            for name in declared[index].iter() {
+                let qualified = match index == main {
+                    true => name.clone(),
+                    false => format!("{}.{}", module.name, name),
+                };
+                names.insert(name.clone(), qualified);
+            }
+            for (alias, imported) in module.imports.iter() {
+                for name in declared[*imported].iter() {
+                    names.insert(format!("{}.{}", alias, name), format!("{}.{}", module_names[*imported], name));
+                }
+            }
+            if let Err(err) = qualify_types(&mut module.program, &names, index != main) {
+                return Object::Error(err);
+            }
+
+            let (module_types, program): (Program, Program) = std::mem::take(&mut module.program)
+                .into_iter()
+                .partition(|statement| matches!(statement.kind, StmtKind::Struct { .. } | StmtKind::Enum { .. }));
+            types.extend(module_types);
+            files.push((module, program));
+        }
+        if let Object::Error(err) = self.declare_types(types) {
+            return Object::Error(err);
+        }
+
+        let mut namespaces: Vec<Object> = vec![];
+        for (index, (module, program)) in files.into_iter().enumerate() {
+            let mut env = Environment::new();
+            for (name, imported) in module.imports.iter() {
+                env.set(name.clone(), namespaces[*imported].clone());
+            }
+            let (functions, statements): (Program, Program) = program
+                .into_iter()
+                .filter(|statement| !matches!(statement.kind, StmtKind::Import { .. } | StmtKind::Blank))
+                .partition(|statement| matches!(statement.kind, StmtKind::Func { .. }));
+
+            if index == main {
+                if let Object::Error(err) = self.eval_functions(functions, None, &mut env) {
+                    return Object::Error(err);
+                }
+                return self.eval_main(statements, &mut env);
+            }
+
+            if let Some(statement) = statements.first() {
+                return Object::Error(format!(
+                    "{}: only declarations can appear at the top level of an imported file", statement.span
+                ));
+            }
+            let public: Vec<String> = functions
+                .iter()
+                .filter_map(|statement| match &statement.kind {
+                    StmtKind::Func { name, public: true, .. } => Some(name.clone()),
+                    _ => None,
+                })
+                .collect();
+            if let Object::Error(err) = self.eval_functions(functions, Some(&module.name), &mut env) {
+                return Object::Error(err);
+            }
+            let members = public
+                .into_iter()
+                .map(|name| {
+                    let function = env.get(&name);
+                    (name, function)
+                })
+                .collect();
+            namespaces.push(Object::Module(module.name, members));
+        }
+        Object::Null
+    }
+
+    // eval_functions declares the functions of one file before compiling
+    // any of them, so that they can call each other in any order. `prefix`
+    // is the name of an imported file. Generic functions are only compiled
+    // when they are called.
+    fn eval_functions(&mut self, functions: Program, prefix: Option<&str>, env: &mut Environment) -> Object {
+        let (generics, functions): (Program, Program) = functions
+            .into_iter()
+            .partition(|statement| matches!(&statement.kind, StmtKind::Func { type_params, .. } if !type_params.is_empty()));
+        let first_generic = self.generics.len();
+        for statement in generics.into_iter() {
+            if let Object::Error(err) = self.declare_generic(statement, prefix, env) {
+                return Object::Error(err);
+            }
+        }
+        for statement in functions.iter() {
+            if let StmtKind::Func { name, param_types, return_type, .. } = &statement.kind {
+                let llvm_name = match prefix {
+                    Some(prefix) => format!("{}.{}", prefix, name),
+                    None => name.clone(),
+                };
+                if let Object::Error(err) = self.declare_function(name, &llvm_name, param_types, return_type, &statement.span, env) {
+                    return Object::Error(err);
+                }
+            }
+        }
+        // The instances see every function of the file.
+        for generic in self.generics[first_generic..].iter_mut() {
+            generic.env = env.function_scope();
+        }
+
+        for statement in functions.into_iter() {
+            if let Object::Error(err) = self.eval_statement(statement, env) {
+                return Object::Error(err);
+            }
+        }
+        Object::Null
+    }
+
+    fn declare_generic(&mut self, statement: Stmt, prefix: Option<&str>, env: &mut Environment) -> Object {
+        let (name, type_params, params, param_types, return_type, body) = match statement.kind {
+            StmtKind::Func { name, type_params, params, param_types, return_type, body, .. } => {
+                (name, type_params, params, param_types, return_type, body)
+            }
+            _ => return Object::Null,
+        };
+        for (index, type_param) in type_params.iter().enumerate() {
+            if type_params[..index].contains(type_param) {
+                return Object::Error(format!(
+                    "{}: type parameter `{}` of `{}` is declared twice", statement.span, type_param.0, name
+                ));
+            }
+        }
+
+        let llvm_name = match prefix {
+            Some(prefix) => format!("{}.{}", prefix, name),
+            None => name.clone(),
+        };
+        self.generics.push(GenericFunction {
+            name: name.clone(),
+            llvm_name,
+            type_params,
+            params,
+            param_types,
+            return_type,
+            body,
+            span: statement.span,
+            env: Environment::new(),
+            instances: HashMap::new(),
+        });
+        env.set(name.clone(), Object::Generic(name, self.generics.len() - 1))
+    }
+
+    // instantiate returns the instance of a generic function for the types
+    // of the arguments it is called with, and compiles it unless an earlier
+    // call already has. The type arguments are inferred from the argument
+    // types; errors in the instance are reported along with the call.
+    fn instantiate(&mut self, index: usize, arg_types: &[LLVMExpressionType], span: &Span) -> Object {
+        let generic = self.generics[index].clone();
+        if generic.param_types.len() != arg_types.len() {
+            return Object::Error(format!(
+                "{}: expected {} arguments, found {}", span, generic.param_types.len(), arg_types.len()
+            ));
+        }
+        let mut bindings = HashMap::new();
+        for (param_type, arg_type) in generic.param_types.iter().zip(arg_types.iter()) {
+            infer(param_type, arg_type, &generic.type_params, &mut bindings);
+        }
+        let mut type_args = vec![];
+        for type_param in generic.type_params.iter() {
+            match bindings.get(&type_param.0) {
+                Some(type_arg) => type_args.push(type_arg.to_string()),
+                None => {
+                    return Object::Error(format!(
+                        "{}: cannot infer type parameter `{}` of `{}` from the arguments", span, type_param.0, generic.name
+                    ))
+                }
             }
         }
-        build_ret(self.lc.builder, const_int(int32_type(), 0));
+        let name = format!("{}<{}>", generic.name, type_args.join(", "));
+        if let Some(function) = generic.instances.get(&name) {
+            return Object::Function(function.clone());
+        }
+        if self.instantiation_depth >= 64 {
+            return Object::Error(format!("{}: `{}` instantiates generic functions too deeply", span, name));
+        }
+
+        let param_types: Vec<LLVMExpressionType> =
+            generic.param_types.iter().map(|param_type| substitute(param_type, &bindings)).collect();
+        let return_type = substitute(&generic.return_type, &bindings);
+        let mut body = generic.body;
+        substitute_block(&mut body, &bindings);
+
+        let llvm_name = format!("{}<{}>", generic.llvm_name, type_args.join(", "));
+        let mut env = generic.env;
+        let function = match self.declare_function(&name, &llvm_name, &param_types, &return_type, &generic.span, &mut env) {
+            Object::Function(function) => function,
+            Object::Error(err) => return Object::Error(format!("{}, in `{}` called at {}", err, name, span)),
+            object => return object,
+        };
+        // Registered before the body is compiled, so that it can call itself.
+        self.generics[index].instances.insert(name.clone(), function.clone());
+
+        let kind = StmtKind::Func {
+            name: name.clone(),
+            doc: None,
+            distributed: false,
+            public: false,
+            type_params: vec![],
+            params: generic.params,
+            param_types,
+            return_type,
+            body,
+        };
+        let loops = std::mem::take(&mut self.loop_stack);
+        self.instantiation_depth += 1;
+        let result = self.eval_function_statement(kind, &generic.span, &mut env);
+        self.instantiation_depth -= 1;
+        self.loop_stack = loops;
+        if let Object::Error(err) = result {
+            return Object::Error(format!("{}, in `{}` called at {}", err, name, span));
+        }
+        Object::Function(function)
+    }
+
+    // eval_main compiles the top-level statements of the main file into
+    // `main`.
+    fn eval_main(&mut self, statements: Program, env: &mut Environment) -> Object {
+        if let Object::Error(err) = self.eval_program(statements, env) {
+            return Object::Error(err);
+        }
+        if !self.is_terminated() {
+            build_ret(self.lc.builder, const_int(int32_type(), 0));
+        }
         Object::Null
     }
 
+    // eval_program compiles a block. Statements after a `return` are dead and
+    // are skipped, since the current basic block is already terminated.
     pub fn eval_program(&mut self, program: Program, env: &mut Environment) -> Object {
         for statement in program.into_iter() {
-            if let Some(mut obj) = self.eval_statement(statement, env) {
-                let llvm_Value = unwrap_object(&mut obj);
-                build_ret(self.lc.builder, llvm_value);
-                return obj;
+            if let Object::Error(err) = self.eval_statement(statement, env) {
+                return Object::Error(err);
+            }
+            if self.is_terminated() {
+                break;
             }
         }
         Object::Null
@@ -68,144 +368,3021 @@ impl Eval {
         &mut self,
         statement: Stmt,
         env: &mut Environment,
-    ) -> Option<Object> {
-        match statement {
-            Stmt::Assignment(ident, expr) => {
-                let obj = self.eval_assignment_statement(ident, expr, llvm_type, env);
-                None
+    ) -> Object {
+        let span = statement.span;
+        match statement.kind {
+            StmtKind::Assignment(ident, expr) => self.eval_assignment_statement(ident, expr, &span, env),
+            StmtKind::IndexAssignment { array, index, op, value } => self.eval_index_assignment(array, index, op, value, &span, env),
+            StmtKind::FieldAssignment { object, field, op, value } => self.eval_field_assignment(object, field, op, value, &span, env),
+            StmtKind::Let(ident, expr, llvm_type) => self.eval_let_statement(ident, llvm_type, expr, &span, env),
+            StmtKind::LetTuple(names, expr, llvm_type) => self.eval_let_tuple_statement(names, llvm_type, expr, &span, env),
+            StmtKind::Return(expr) => self.eval_return_statement(expr, &span, env),
+            StmtKind::Expr(expr) | StmtKind::Tail(expr) => self.eval_expression_statement(expr, env),
+            kind @ StmtKind::Func { .. } => self.eval_function_statement(kind, &span, env),
+            StmtKind::While { label, cond, body } => self.eval_while_statement(label, cond, body, env),
+            StmtKind::For { label, init, cond, step, body } => self.eval_for_statement(label, init, cond, step, body, env),
+            StmtKind::Break(label) => self.eval_jump_statement(label, true, &span),
+            StmtKind::Continue(label) => self.eval_jump_statement(label, false, &span),
+            // Declared by entry_eval_program.
+            StmtKind::Struct { .. } | StmtKind::Enum { .. } => Object::Null,
+            // Resolved by the loader, which entry_eval_modules takes them from.
+            StmtKind::Import { name, .. } => Object::Error(format!("{}: module `{}` is not loaded", span, name.0)),
+            StmtKind::Blank => Object::Null,
+        }
+    }
+
+    // declare_types creates the named types first, so that fields can refer
+    // to types declared later, and then fills in their fields.
+    fn declare_types(&mut self, types: Program) -> Object {
+        for statement in types.iter() {
+            let (name, kind) = match &statement.kind {
+                StmtKind::Struct { name, .. } => (name, "struct"),
+                StmtKind::Enum { name, .. } => (name, "enum"),
+                _ => continue,
+            };
+            if self.structs.contains_key(&name.0) || self.enums.contains_key(&name.0) {
+                return Object::Error(format!("{}: {} `{}` is already declared", statement.span, kind, name.0));
             }
-            Stmt::Let(ident, expr, llvm_type) => {
-                let obj = self.eval_let_statement(ident, expr, llvm_type, env);
-                None
+            let llvm_type = named_struct_type(&name.0);
+            if kind == "struct" {
+                self.structs.insert(name.0.clone(), StructType { llvm_type, fields: vec![] });
+            } else {
+                self.enums.insert(name.0.clone(), EnumType { llvm_type, variants: vec![], payload_words: 0 });
             }
-            Stmt::Return(expr) => self.eval_return_statement(expr, env),
-            Stmt::Expr(expr) => self.eval_expression_statement(expr, env),
         }
-    }
 
+        for statement in types.iter() {
+            let result = match &statement.kind {
+                StmtKind::Struct { name, fields } => self.declare_struct_fields(name, fields, &statement.span),
+                StmtKind::Enum { name, variants } => self.declare_enum_variants(name, variants, &statement.span),
+                _ => Object::Null,
+            };
+            if let Object::Error(err) = result {
+                return Object::Error(err);
+            }
+        }
 
-    pub fn eval_assignment_statement(&mut self, ident: Ident, expr: Expr, llvm_type: LLVMExpressionType, env: &mut Environment){
-        let identify_object = env.get(&ident.0);
-        let llvm_value_ref = match identify_object {
-            Object::Integer(reference) => reference,
-            Object::Boolean(reference) => reference,
-            _ => 0 as *mut LLVMValue,
-        };
+        for statement in types.iter() {
+            let (name, kind) = match &statement.kind {
+                StmtKind::Struct { name, .. } => (name, "struct"),
+                StmtKind::Enum { name, .. } => (name, "enum"),
+                _ => continue,
+            };
+            if self.part_types(&name.0).iter().any(|part_type| self.contains_type(part_type, &name.0, &mut vec![])) {
+                return Object::Error(format!("{}: {} `{}` contains itself", statement.span, kind, name.0));
+            }
+        }
+
+        let mut defined = vec![];
+        for statement in types.iter() {
+            if let StmtKind::Struct { name, .. } | StmtKind::Enum { name, .. } = &statement.kind {
+                self.define_type(&name.0, &mut defined);
+            }
+        }
+        Object::Null
+    }
 
-        let mut object = self.eval_expression(expr, &mut env.clone());
-        let llvm_value = unwrap_object(&mut object);
-        build_store(self.lc.builder, llvm_value, llvm_value_ref);
+    fn declare_struct_fields(&mut self, name: &Ident, fields: &[(Ident, LLVMExpressionType)], span: &Span) -> Object {
+        let mut declared: Vec<(String, LLVMExpressionType)> = vec![];
+        for (field, field_type) in fields.iter() {
+            if declared.iter().any(|(declared, _)| *declared == field.0) {
+                return Object::Error(format!("{}: field `{}` is declared twice in `{}`", span, field.0, name.0));
+            }
+            if let LLVMExpressionType::Null | LLVMExpressionType::Call = field_type {
+                return Object::Error(format!("{}: field `{}` cannot be {}", span, field.0, field_type));
+            }
+            if let Object::Error(err) = self.check_type(field_type, span) {
+                return Object::Error(err);
+            }
+            declared.push((field.0.clone(), field_type.clone()));
+        }
+        self.structs.get_mut(&name.0).unwrap().fields = declared;
+        Object::Null
+    }
 
+    fn declare_enum_variants(&mut self, name: &Ident, variants: &[(Ident, Vec<LLVMExpressionType>)], span: &Span) -> Object {
+        let mut declared: Vec<EnumVariant> = vec![];
+        for (variant, fields) in variants.iter() {
+            if declared.iter().any(|declared| declared.name == variant.0) {
+                return Object::Error(format!("{}: variant `{}` is declared twice in `{}`", span, variant.0, name.0));
+            }
+            for field_type in fields.iter() {
+                if let LLVMExpressionType::Null | LLVMExpressionType::Call = field_type {
+                    return Object::Error(format!("{}: variant `{}` cannot hold {}", span, variant.0, field_type));
+                }
+                if let Object::Error(err) = self.check_type(field_type, span) {
+                    return Object::Error(err);
+                }
+            }
+            declared.push(EnumVariant {
+                name: variant.0.clone(),
+                fields: fields.clone(),
+                llvm_type: named_struct_type(&format!("{}.{}", name.0, variant.0)),
+            });
+        }
+        self.enums.get_mut(&name.0).unwrap().variants = declared;
         Object::Null
     }
 
-    pub fn eval_let_statement(&mut self, ident: ident, expr_type: LLVMExpressionType, expr: Expr, env: &mut Environment) -> Object {
-        let mut object = self.eval_expression(expr, env);
+    // part_types lists the types a struct or an enum holds inline.
+    fn part_types(&self, name: &str) -> Vec<LLVMExpressionType> {
+        match self.structs.get(name) {
+            Some(definition) => definition.fields.iter().map(|(_, field_type)| field_type.clone()).collect(),
+            None => self.enums[name].variants.iter().flat_map(|variant| variant.fields.clone()).collect(),
+        }
+    }
 
-        match expr_type {
-            LLVMExpressionType::Call => match object {
-                Object::Integer(value) | Object::Boolean(value) => {
-                    self.set_value_to_identify(value, object, &ident.0, env);
-                },
-                _ => env.set(ident.0, object),
+    // contains_type reports whether a value of `value_type` holds a `name`
+    // inline. Maps only hold a pointer to their entries.
+    fn contains_type(&self, value_type: &LLVMExpressionType, name: &str, seen: &mut Vec<String>) -> bool {
+        match value_type {
+            LLVMExpressionType::Array(element_type, _) => self.contains_type(element_type, name, seen),
+            LLVMExpressionType::Tuple(elements) => elements.iter().any(|element| self.contains_type(element, name, seen)),
+            LLVMExpressionType::Named(inner) if inner == name => true,
+            LLVMExpressionType::Named(inner) => {
+                if seen.contains(inner) {
+                    return false;
+                }
+                seen.push(inner.clone());
+                self.part_types(inner).iter().any(|part_type| self.contains_type(part_type, name, seen))
             }
+            _ => false,
         }
-        _ => self.set_value_to_identify(llvm_value, object, &ident.0, env),
     }
 
-    pub fn eval_expression_statement(&mut self, expr: Expr, env: &mut Environment) -> Option<Object> {
-        match expr {
-            Expression::If {
-                cond,
-                consequence,
-                alternative
-            } => {
-                let object = self.eval_if(cond, consequence, alternative);
-                None
+    // define_type sets the body of a named type once the types it holds
+    // have theirs, since the size of an enum depends on its payloads.
+    fn define_type(&mut self, name: &str, defined: &mut Vec<String>) {
+        if defined.iter().any(|done| done == name) {
+            return;
+        }
+        defined.push(name.to_owned());
+        for part_type in self.part_types(name) {
+            self.define_part_types(&part_type, defined);
+        }
+
+        if let Some(definition) = self.structs.get(name) {
+            let mut field_types: Vec<*mut LLVMType> = definition.fields.iter().map(|(_, field_type)| self.storage_type(field_type)).collect();
+            set_struct_body(definition.llvm_type, &mut field_types);
+            return;
+        }
+
+        let definition = &self.enums[name];
+        let mut largest = 0;
+        for variant in definition.variants.iter() {
+            let mut field_types = vec![int64_type()];
+            field_types.extend(variant.fields.iter().map(|field_type| self.storage_type(field_type)));
+            set_struct_body(variant.llvm_type, &mut field_types);
+            largest = largest.max(abi_size_of(self.lc.module, variant.llvm_type));
+        }
+        // The tag takes a whole word, so the payload starts at offset 8 in
+        // the enum and in every variant, whatever the data layout aligns an
+        // i64 to.
+        let payload_words = largest.saturating_sub(8).div_ceil(8);
+        set_struct_body(definition.llvm_type, &mut [int64_type(), array_type(int64_type(), payload_words as u32)]);
+        self.enums.get_mut(name).unwrap().payload_words = payload_words as u32;
+    }
+
+    // define_part_types defines the named types held inline by a value of
+    // `value_type`.
+    fn define_part_types(&mut self, value_type: &LLVMExpressionType, defined: &mut Vec<String>) {
+        match value_type {
+            LLVMExpressionType::Array(element_type, _) => self.define_part_types(element_type, defined),
+            LLVMExpressionType::Tuple(elements) => {
+                for element in elements.iter() {
+                    self.define_part_types(element, defined);
+                }
             }
-            _ => {
-                let obj = self.eval_expression(expr, env);
-                None
+            LLVMExpressionType::Named(name) => self.define_type(name, defined),
+            _ => {}
+        }
+    }
+
+    // check_type reports struct and enum names that have not been declared.
+    fn check_type(&self, value_type: &LLVMExpressionType, span: &Span) -> Object {
+        match value_type {
+            LLVMExpressionType::Array(element_type, _) => self.check_type(element_type, span),
+            LLVMExpressionType::Map(key_type, value_type) => match self.check_type(key_type, span) {
+                Object::Null => self.check_type(value_type, span),
+                error => error,
+            },
+            LLVMExpressionType::Named(name) if !self.structs.contains_key(name) && !self.enums.contains_key(name) => {
+                Object::Error(format!("{}: unknown type `{}`", span, name))
             }
+            LLVMExpressionType::Function(param_types, return_type) => param_types
+                .iter()
+                .chain(std::iter::once(return_type.as_ref()))
+                .map(|part_type| self.check_type(part_type, span))
+                .find(|object| matches!(object, Object::Error(_)))
+                .unwrap_or(Object::Null),
+            LLVMExpressionType::Tuple(elements) => elements
+                .iter()
+                .map(|element| self.check_type(element, span))
+                .find(|object| matches!(object, Object::Error(_)))
+                .unwrap_or(Object::Null),
+            _ => Object::Null,
+        }
+    }
 
+    // storage_type returns the type a value occupies in memory. Arrays,
+    // structs, enums and tuples hold their parts inline.
+    fn storage_type(&self, value_type: &LLVMExpressionType) -> *mut LLVMType {
+        match value_type {
+            LLVMExpressionType::Array(element_type, length) => array_type(self.storage_type(element_type), *length),
+            LLVMExpressionType::Named(name) => match self.structs.get(name) {
+                Some(definition) => definition.llvm_type,
+                None => self.enums[name].llvm_type,
+            },
+            LLVMExpressionType::Tuple(elements) => {
+                struct_type(&mut elements.iter().map(|element| self.storage_type(element)).collect::<Vec<_>>())
+            }
+            LLVMExpressionType::Function(_, _) => closure_type(),
+            value_type => convert_llvm_type(value_type.clone()),
         }
     }
 
-    pub fn parse_return_statement(
+    pub fn declare_function(
         &mut self,
-        expr: Expr,
-        env: &mut Environment
-    ){
-        self.eval_expression(expr, env);
+        name: &str,
+        llvm_name: &str,
+        param_types: &[LLVMExpressionType],
+        return_type: &LLVMExpressionType,
+        span: &Span,
+        env: &mut Environment,
+    ) -> Object {
+        if let Object::Error(err) = self.check_signature(&format!("function `{}`", name), param_types, return_type, span) {
+            return Object::Error(err);
+        }
+
+        let fn_type = function_signature(vec![], param_types, return_type);
+        let function = add_function(self.lc.module, fn_type, llvm_name);
+        let block = append_basic_block_in_context(self.lc.context, function, "entry");
+
+        env.set(name.to_string(), Object::Function(Function {
+            llvm_value: function,
+            llvm_block: block,
+            param_types: param_types.to_vec(),
+            return_type: return_type.clone(),
+        }))
     }
 
-    pub fn eval_if(
+    // check_signature checks the parameter and return types of `what`, a
+    // function or a closure.
+    fn check_signature(&self, what: &str, param_types: &[LLVMExpressionType], return_type: &LLVMExpressionType, span: &Span) -> Object {
+        for param_type in param_types.iter() {
+            if let LLVMExpressionType::Null | LLVMExpressionType::Call = param_type {
+                return Object::Error(format!("{}: invalid parameter type in {}", span, what));
+            }
+            if let Object::Error(err) = self.check_type(param_type, span) {
+                return Object::Error(err);
+            }
+        }
+        if let Object::Error(err) = self.check_type(return_type, span) {
+            return Object::Error(err);
+        }
+        // An array returned by pointer would point into the callee's frame.
+        if let LLVMExpressionType::Array(_, _) = return_type {
+            return Object::Error(format!("{}: {} cannot return an array", span, what));
+        }
+        Object::Null
+    }
+
+    pub fn eval_function_statement(&mut self, kind: StmtKind, span: &Span, env: &mut Environment) -> Object {
+        let (name, params, param_types, return_type, body) = match kind {
+            StmtKind::Func { name, params, param_types, return_type, body, .. } => {
+                (name, params, param_types, return_type, body)
+            }
+            _ => return Object::Null,
+        };
+
+        let function = match env.get(&name) {
+            Object::Function(function) => function,
+            _ => match self.declare_function(&name, &name, &param_types, &return_type, span, env) {
+                Object::Function(function) => function,
+                object => return object,
+            },
+        };
+
+        let caller_block = get_insert_block(self.lc.builder);
+        build_position_at_end(self.lc.builder, function.llvm_block);
+        self.function_stack.push(function.llvm_value, return_type.clone());
+
+        let mut function_env = env.function_scope();
+        let first_param = returns_in_slot(&return_type) as usize;
+        for (index, (param, param_type)) in params.iter().zip(param_types).enumerate() {
+            let llvm_value = get_param(function.llvm_value, (first_param + index) as u32);
+            let object = wrap_llvm_value(param_type, llvm_value);
+            self.set_value_to_identify(llvm_value, object, &param.0, &mut function_env);
+        }
+
+        let what = format!("function `{}`", name);
+        let result = self.eval_function_body(body, &return_type, function.llvm_block, &what, span, &mut function_env);
+
+        self.function_stack.pop();
+        build_position_at_end(self.lc.builder, caller_block);
+        result
+    }
+
+    // eval_function_body compiles the body of a function or a closure whose
+    // entry block is `entry`. Only a void function may fall off its end.
+    fn eval_function_body(
         &mut self,
-        cond: Box<Expr>,
-        consequence: Program,
-        alternative: Option<Program>,
+        body: BlockStmt,
+        return_type: &LLVMExpressionType,
+        entry: *mut LLVMBasicBlock,
+        what: &str,
+        span: &Span,
         env: &mut Environment,
-    ) -> Option<Object> {
-        let current_function = self.function_stack.last();
-        let mut return_obj = Object::Null;
+    ) -> Object {
+        if let Object::Error(err) = self.eval_program(body, env) {
+            return Object::Error(err);
+        }
+        if !self.is_terminated() {
+            let current_block = get_insert_block(self.lc.builder);
+            if *return_type == LLVMExpressionType::Null {
+                build_ret_void(self.lc.builder);
+            } else if current_block != entry && !block_has_predecessors(current_block) {
+                build_unreachable(self.lc.builder);
+            } else {
+                return Object::Error(format!("{}: {} is missing a return", span, what));
+            }
+        }
+        Object::Null
+    }
 
-        let boolean: *mut LLVMValue = unwrap_object(self.eval_expression(cond, &mut env.clone()));
+    // eval_closure_expression compiles the body of a closure as a function
+    // of its own, which takes the environment as a hidden parameter. The
+    // variables the body uses are copied into the environment when the
+    // closure is created, so the body cannot assign to them; see
+    // captured_place. The environment lives on the heap so that the closure
+    // can outlive the scope it was created in, and is never freed: each
+    // closure created, in a loop too, keeps its environment until the
+    // program exits.
+    pub fn eval_closure_expression(&mut self, kind: ExprKind, span: &Span, env: &mut Environment) -> Object {
+        let (params, param_types, return_type, body) = match kind {
+            ExprKind::Closure { params, param_types, return_type, body } => (params, param_types, return_type, body),
+            _ => return Object::Null,
+        };
+        if let Object::Error(err) = self.check_signature("closure", &param_types, &return_type, span) {
+            return Object::Error(err);
+        }
 
-        left_block = append_basic_block_in_context(
-            self.lc.context,
-            current_function,
-            "",
-        );
+        let captures: Vec<(String, Object)> = referenced_names(&body)
+            .into_iter()
+            .filter(|name| !params.iter().any(|param| param.0 == *name))
+            .map(|name| {
+                let object = env.get(&name);
+                (name, object)
+            })
+            .filter(|(_, object)| object.is_variable())
+            .collect();
+        let mut part_types: Vec<*mut LLVMType> = captures
+            .iter()
+            .map(|(_, object)| self.storage_type(&get_expression_type_from_object(object)))
+            .collect();
+        let environment_type = struct_type(&mut part_types);
+        let environment = if captures.is_empty() {
+            const_null(pointer_type())
+        } else {
+            let malloc = self.lc.built_ins["malloc"];
+            call_function(self.lc.builder, get_function_type(malloc), malloc, vec![size_of(environment_type)], "")
+        };
+        for (index, (name, object)) in captures.iter().enumerate() {
+            let value = self.load_variable(object.clone(), name);
+            let target = self.build_field_pointer(environment_type, environment, index);
+            self.store_value(&value, target);
+        }
 
-        let right_block = append_basic_block_in_context(
-            self.lc.context,
-            current_function,
-            "",
-        );
+        let fn_type = function_signature(vec![pointer_type()], &param_types, &return_type);
+        let function = add_function(self.lc.module, fn_type, "closure");
+        let entry = append_basic_block_in_context(self.lc.context, function, "entry");
+        let caller_block = get_insert_block(self.lc.builder);
+        build_position_at_end(self.lc.builder, entry);
+        self.function_stack.push(function, return_type.clone());
+        // `break` and `continue` cannot jump out of the closure.
+        let loops = std::mem::take(&mut self.loop_stack);
 
-        let end_block = append_basic_block_in_context(self.lc.context, current_function, "");
+        let mut function_env = env.function_scope();
+        let first_param = returns_in_slot(&return_type) as usize;
+        let environment_param = get_param(function, first_param as u32);
+        for (index, (name, object)) in captures.into_iter().enumerate() {
+            let field = self.build_field_pointer(environment_type, environment_param, index);
+            let captured = self.wrap_element(get_expression_type_from_object(&object), field);
+            function_env.capture(name, captured);
+        }
+        for (index, (param, param_type)) in params.iter().zip(param_types.iter()).enumerate() {
+            let llvm_value = get_param(function, (first_param + 1 + index) as u32);
+            let object = wrap_llvm_value(param_type.clone(), llvm_value);
+            self.set_value_to_identify(llvm_value, object, &param.0, &mut function_env);
+        }
 
-        build_cond_br(self.lc.builder, boolean, left_block, right_block);
-        build_position_at_end(self.lc.builder, left_block);
-        return_obj = self.eval_program(consequence.clone(), env);
-        build_br(self.lc.builder, end_block);
+        let result = self.eval_function_body(body, &return_type, entry, "closure", span, &mut function_env);
 
-        build_position_at_end(self.lc.builder, right_block);
-        return_obj = self.eval_program(alternative.clone(), env);
+        self.loop_stack = loops;
+        self.function_stack.pop();
+        build_position_at_end(self.lc.builder, caller_block);
+        if let Object::Error(err) = result {
+            return Object::Error(err);
+        }
+        self.build_closure(function, environment, param_types, return_type)
+    }
 
-        build_br(self.lc.builder, end_block);
-        build_position_at_end(self.lc.builder, end_block);
+    // function_value turns a named function into a closure with no
+    // environment. It is called through a wrapper that takes the
+    // environment like any closure and drops it.
+    fn function_value(&mut self, function: Function) -> Object {
+        let wrapper = match self.function_values.get(&function.llvm_value) {
+            Some(wrapper) => *wrapper,
+            None => {
+                let wrapper = self.build_function_wrapper(&function);
+                self.function_values.insert(function.llvm_value, wrapper);
+                wrapper
+            }
+        };
+        self.build_closure(wrapper, const_null(pointer_type()), function.param_types, function.return_type)
+    }
+
+    fn build_function_wrapper(&mut self, function: &Function) -> *mut LLVMValue {
+        let fn_type = function_signature(vec![pointer_type()], &function.param_types, &function.return_type);
+        let wrapper_name = format!("{}.value", get_value_name(function.llvm_value));
+        let wrapper = add_function(self.lc.module, fn_type, &wrapper_name);
+        let caller_block = get_insert_block(self.lc.builder);
+        let entry = append_basic_block_in_context(self.lc.context, wrapper, "entry");
+        build_position_at_end(self.lc.builder, entry);
 
-        match return_obj {
-            Object::Null => None,
-            _ => Some(return_obj),
+        let environment_index = returns_in_slot(&function.return_type) as u32;
+        let args = (0..count_param_types(fn_type))
+            .filter(|index| *index != environment_index)
+            .map(|index| get_param(wrapper, index))
+            .collect();
+        let value = call_function(self.lc.builder, get_function_type(function.llvm_value), function.llvm_value, args, "");
+        if function.return_type == LLVMExpressionType::Null || returns_in_slot(&function.return_type) {
+            build_ret_void(self.lc.builder);
+        } else {
+            build_ret(self.lc.builder, value);
         }
+        build_position_at_end(self.lc.builder, caller_block);
+        wrapper
     }
 
-    pub fn eval_expression(&mut self, expr: Expr, env: &mut Environment) {
-        match expr {
-            Expr::Literal(literal) => {
-                match literal {
-                    Int(value) => Object::Integer(const_int(int32_type(), value)),
-                    Bool(value) => Object::Boolean(const_int(int1_type(), value)),
+    // build_closure stores a function pointer and an environment pointer in
+    // a new stack slot.
+    fn build_closure(
+        &mut self,
+        function: *mut LLVMValue,
+        environment: *mut LLVMValue,
+        param_types: Vec<LLVMExpressionType>,
+        return_type: LLVMExpressionType,
+    ) -> Object {
+        let pair = self.build_entry_alloca(closure_type(), "");
+        let target = self.build_field_pointer(closure_type(), pair, 0);
+        build_store(self.lc.builder, cast_type(self.lc.builder, function, pointer_type(), ""), target);
+        let target = self.build_field_pointer(closure_type(), pair, 1);
+        build_store(self.lc.builder, environment, target);
+        Object::Closure(param_types, return_type, cast_type(self.lc.builder, pair, pointer_type(), ""))
+    }
+
+    pub fn eval_assignment_statement(&mut self, ident: Ident, expr: Expr, span: &Span, env: &mut Environment) -> Object {
+        if env.is_captured(&ident.0) {
+            return Object::Error(format!("{}: cannot assign to captured variable `{}`", span, ident.0));
+        }
+        let identify_object = env.get(&ident.0);
+        if !identify_object.is_variable() {
+            return Object::Error(format!("{}: `{}` is not a variable", span, ident.0));
+        }
+
+        let expected = get_expression_type_from_object(&identify_object);
+        let object = self.eval_expression_with_hint(expr, int_kind_of(&expected), env);
+        if let Object::Error(err) = object {
+            return Object::Error(err);
+        }
+
+        let actual = get_expression_type_from_object(&object);
+        if expected != actual {
+            return Object::Error(format!(
+                "{}: cannot assign {} to `{}` of type {}", span, actual, ident.0, expected
+            ));
+        }
+
+        let llvm_value_ref = unwrap_object(&mut identify_object.clone());
+        self.store_value(&object, llvm_value_ref);
+        Object::Null
+    }
+
+    // The container and index are evaluated once, then the value. A compound
+    // assignment such as `a[i] += y` reads the element only after that.
+    pub fn eval_index_assignment(&mut self, array: Expr, index: Expr, op: Option<Infix>, value: Expr, span: &Span, env: &mut Environment) -> Object {
+        if let Some((name, container_type)) = self.captured_place(&array, env) {
+            if !matches!(container_type, LLVMExpressionType::Map(_, _)) {
+                return Object::Error(format!("{}: cannot assign to captured variable `{}`", span, name));
+            }
+        }
+        let container = self.eval_expression(array, env);
+        if let Object::Map(key_type, value_type, map) = container {
+            // The value is evaluated before the entry is looked up or added,
+            // since adding an entry may move the others.
+            let key = match self.eval_map_key(&key_type, index, env) {
+                Object::Integer(_, key) => key,
+                object => return object,
+            };
+            let object = self.eval_expression_with_hint(value, int_kind_of(&value_type), env);
+            if let Object::Error(err) = object {
+                return Object::Error(err);
+            }
+            let element = match op {
+                Some(_) => self.build_map_find(value_type.clone(), map, key, span),
+                None => {
+                    let value = self.call_map_runtime("flash_map_insert", vec![map, key]);
+                    self.wrap_element(value_type.clone(), value)
                 }
+            };
+            return self.store_assigned_element(&value_type, element, op, object, span);
+        }
+
+        let element = self.eval_element(container, index, span, env);
+        if let Object::Error(err) = element {
+            return Object::Error(err);
+        }
+
+        let expected = get_expression_type_from_object(&element);
+        let object = self.eval_expression_with_hint(value, int_kind_of(&expected), env);
+        if let Object::Error(err) = object {
+            return Object::Error(err);
+        }
+        self.store_assigned_element(&expected, element, op, object, span)
+    }
+
+    fn store_assigned_element(&mut self, expected: &LLVMExpressionType, element: Object, op: Option<Infix>, object: Object, span: &Span) -> Object {
+        let object = self.eval_compound_value(&element, op, object, span);
+        if let Object::Error(err) = object {
+            return Object::Error(err);
+        }
+
+        let actual = get_expression_type_from_object(&object);
+        if *expected != actual {
+            return Object::Error(format!(
+                "{}: cannot assign {} to an element of type {}", span, actual, expected
+            ));
+        }
+        self.store_value(&object, unwrap_object(&mut element.clone()));
+        Object::Null
+    }
+
+    // eval_compound_value combines the value `target` points to with `object`
+    // when the assignment has an operator, and is `object` otherwise.
+    fn eval_compound_value(&mut self, target: &Object, op: Option<Infix>, object: Object, span: &Span) -> Object {
+        match op {
+            Some(op) => {
+                let current = self.load_variable(target.clone(), "");
+                self.apply_infix(op, current, object, span)
             }
+            None => object,
         }
     }
 
-    pub fn set_value_to_identify(
+    pub fn eval_field_assignment(&mut self, object: Expr, field: Ident, op: Option<Infix>, value: Expr, span: &Span, env: &mut Environment) -> Object {
+        if let Some((name, _)) = self.captured_place(&object, env) {
+            return Object::Error(format!("{}: cannot assign to captured variable `{}`", span, name));
+        }
+        let target = self.eval_field(object, &field, span, env);
+        if let Object::Error(err) = target {
+            return Object::Error(err);
+        }
+
+        let expected = get_expression_type_from_object(&target);
+        let object = self.eval_expression_with_hint(value, int_kind_of(&expected), env);
+        if let Object::Error(err) = object {
+            return Object::Error(err);
+        }
+        let object = self.eval_compound_value(&target, op, object, span);
+        if let Object::Error(err) = object {
+            return Object::Error(err);
+        }
+        let actual = get_expression_type_from_object(&object);
+        if expected != actual {
+            return Object::Error(format!(
+                "{}: cannot assign {} to field `{}` of type {}", span, actual, field.0, expected
+            ));
+        }
+
+        let llvm_value_ref = unwrap_object(&mut target.clone());
+        self.store_value(&object, llvm_value_ref);
+        Object::Null
+    }
+
+    // captured_place returns the captured variable `place` is part of, and
+    // the type of `place`, when it lies in the closure's copy of the variable.
+    // An element of a map is not: a map is shared by every copy of it.
+    fn captured_place(&self, place: &Expr, env: &Environment) -> Option<(String, LLVMExpressionType)> {
+        match &place.kind {
+            ExprKind::Ident(ident) if env.is_captured(&ident.0) => {
+                Some((ident.0.clone(), get_expression_type_from_object(&env.get(&ident.0))))
+            }
+            ExprKind::Index { array, .. } => match self.captured_place(array, env)? {
+                (name, LLVMExpressionType::Array(element_type, _)) => Some((name, *element_type)),
+                _ => None,
+            },
+            ExprKind::Field { object, field } => {
+                let (name, object_type) = self.captured_place(object, env)?;
+                let field_type = match object_type {
+                    LLVMExpressionType::Named(struct_name) => {
+                        let fields = &self.structs.get(&struct_name)?.fields;
+                        fields.iter().find(|(declared, _)| *declared == field.0)?.1.clone()
+                    }
+                    LLVMExpressionType::Tuple(elements) => elements.get(field.0.parse::<usize>().ok()?)?.clone(),
+                    _ => return None,
+                };
+                Some((name, field_type))
+            }
+            _ => None,
+        }
+    }
+
+    // eval_let_statement binds a new variable. Without a type annotation the
+    // variable takes the type of its value; unsuffixed integer literals are
+    // `int` then.
+    pub fn eval_let_statement(&mut self, ident: Ident, expr_type: Option<LLVMExpressionType>, expr: Expr, span: &Span, env: &mut Environment) -> Object {
+        if let Some(Object::Error(err)) = expr_type.as_ref().map(|expr_type| self.check_type(expr_type, span)) {
+            return Object::Error(err);
+        }
+        let object = self.eval_expression_with_hint(expr, expr_type.as_ref().and_then(int_kind_of), env);
+        match object {
+            Object::Error(err) => return Object::Error(err),
+            Object::Null if expr_type.is_none() => {
+                return Object::Error(format!(
+                    "{}: cannot infer the type of `{}`: its initializer has no value", span, ident.0
+                ));
+            }
+            ref object if !object.is_variable() => {
+                return Object::Error(format!("{}: `{}` cannot hold a value of this kind", span, ident.0));
+            }
+            _ => {}
+        }
+
+        let actual = get_expression_type_from_object(&object);
+        match expr_type {
+            Some(ref expected) if *expected != actual => {
+                return Object::Error(format!(
+                    "{}: `{}` is declared as {} but initialised with {}", span, ident.0, expected, actual
+                ));
+            }
+            _ => {}
+        }
+
+        let llvm_value = unwrap_object(&mut object.clone());
+        self.set_value_to_identify(llvm_value, object, &ident.0, env);
+        Object::Null
+    }
+
+    // eval_let_tuple_statement binds each element of a tuple to its own
+    // variable, copying it out of the tuple.
+    pub fn eval_let_tuple_statement(
         &mut self,
-        llvm_value: &mut LLVMValue,
-        mut object: Object,
-        name: &str,
+        names: Vec<Ident>,
+        expr_type: Option<LLVMExpressionType>,
+        expr: Expr,
+        span: &Span,
         env: &mut Environment,
-    ) -> {
-        let llvm_type = get_llvm_type_from_object(&mut object);
-        let llvm_value_ref = build_alloc(self.lc.builder, llvm_type, name);
-        build_store(self.lc.builder, llvm_value, llvm_value_ref);
-        let rewraped_object = rewrap_llvm_value_ref(object, llvm_value_ref);
-        env.set(name.to_string(), rewraped_object);
+    ) -> Object {
+        if let Some(Object::Error(err)) = expr_type.as_ref().map(|expr_type| self.check_type(expr_type, span)) {
+            return Object::Error(err);
+        }
+        let (elements, pointer) = match self.eval_expression(expr, env) {
+            Object::Tuple(elements, pointer) => (elements, pointer),
+            Object::Error(err) => return Object::Error(err),
+            object => return Object::Error(format!("{}: cannot destructure {}, which is not a tuple", span, object)),
+        };
+        let actual = LLVMExpressionType::Tuple(elements.clone());
+        match expr_type {
+            Some(ref expected) if *expected != actual => {
+                return Object::Error(format!("{}: the tuple is declared as {} but initialised with {}", span, expected, actual));
+            }
+            _ => {}
+        }
+        if names.len() != elements.len() {
+            return Object::Error(format!(
+                "{}: cannot destructure {} into {} names", span, actual, names.len()
+            ));
+        }
+
+        let storage_type = self.storage_type(&actual);
+        for (index, (name, element_type)) in names.iter().zip(elements).enumerate() {
+            if name.0 == "_" {
+                continue;
+            }
+            let element = self.build_field_pointer(storage_type, pointer, index);
+            let object = self.wrap_element(element_type, element);
+            let object = self.load_variable(object, &name.0);
+            let llvm_value = unwrap_object(&mut object.clone());
+            self.set_value_to_identify(llvm_value, object, &name.0, env);
+        }
+        Object::Null
     }
-}
 
+    pub fn eval_return_statement(&mut self, expr: Expr, span: &Span, env: &mut Environment) -> Object {
+        let expected = self.function_stack.last_return_type();
+        let object = self.eval_expression_with_hint(expr, int_kind_of(&expected), env);
+        if let Object::Error(err) = object {
+            return Object::Error(err);
+        }
 
+        match object {
+            Object::Null if expected == LLVMExpressionType::Null => {
+                build_ret_void(self.lc.builder);
+            }
+            ref object @ (Object::Named(_, _) | Object::Tuple(_, _) | Object::Closure(_, _, _)) if get_expression_type_from_object(object) == expected => {
+                let slot = get_param(self.function_stack.last(), 0);
+                self.store_value(object, slot);
+                build_ret_void(self.lc.builder);
+            }
+            ref object if object.is_variable() && get_expression_type_from_object(object) == expected => {
+                build_ret(self.lc.builder, unwrap_object(&mut object.clone()));
+            }
+            object => return Object::Error(format!(
+                "{}: cannot return {} from a function returning {}", span, object, expected
+            )),
+        }
+        Object::Null
+    }
+
+    pub fn eval_expression_statement(&mut self, expr: Expr, env: &mut Environment) -> Object {
+        match self.eval_expression(expr, env) {
+            Object::Error(err) => Object::Error(err),
+            _ => Object::Null,
+        }
+    }
+
+    pub fn eval_while_statement(&mut self, label: Option<Ident>, cond: Expr, body: BlockStmt, env: &mut Environment) -> Object {
+        let current_function = self.function_stack.last();
+        let cond_block = append_basic_block_in_context(self.lc.context, current_function, "");
+        let body_block = append_basic_block_in_context(self.lc.context, current_function, "");
+        let end_block = append_basic_block_in_context(self.lc.context, current_function, "");
+
+        build_br(self.lc.builder, cond_block);
+        build_position_at_end(self.lc.builder, cond_block);
+        let boolean = match self.eval_condition(cond, env) {
+            Object::Boolean(value) => value,
+            object => return object,
+        };
+        build_cond_br(self.lc.builder, boolean, body_block, end_block);
+
+        build_position_at_end(self.lc.builder, body_block);
+        if let Object::Error(err) = self.eval_loop_body(label, body, cond_block, end_block, env) {
+            return Object::Error(err);
+        }
+
+        build_position_at_end(self.lc.builder, end_block);
+        Object::Null
+    }
+
+    // eval_for_statement lowers `for init; cond; step { body }`. Variables
+    // declared in `init` are only visible inside the loop, and `continue`
+    // jumps to `step`.
+    pub fn eval_for_statement(
+        &mut self,
+        label: Option<Ident>,
+        init: Option<Box<Stmt>>,
+        cond: Option<Expr>,
+        step: Option<Box<Stmt>>,
+        body: BlockStmt,
+        env: &mut Environment,
+    ) -> Object {
+        let mut loop_env = env.clone();
+        if let Some(init) = init {
+            if let Object::Error(err) = self.eval_statement(*init, &mut loop_env) {
+                return Object::Error(err);
+            }
+        }
+
+        let current_function = self.function_stack.last();
+        let cond_block = append_basic_block_in_context(self.lc.context, current_function, "");
+        let body_block = append_basic_block_in_context(self.lc.context, current_function, "");
+        let step_block = append_basic_block_in_context(self.lc.context, current_function, "");
+        let end_block = append_basic_block_in_context(self.lc.context, current_function, "");
+
+        build_br(self.lc.builder, cond_block);
+        build_position_at_end(self.lc.builder, cond_block);
+        match cond {
+            Some(cond) => match self.eval_condition(cond, &mut loop_env) {
+                Object::Boolean(value) => build_cond_br(self.lc.builder, value, body_block, end_block),
+                object => return object,
+            },
+            None => build_br(self.lc.builder, body_block),
+        }
+
+        build_position_at_end(self.lc.builder, body_block);
+        if let Object::Error(err) = self.eval_loop_body(label, body, step_block, end_block, &mut loop_env) {
+            return Object::Error(err);
+        }
+
+        build_position_at_end(self.lc.builder, step_block);
+        if let Some(step) = step {
+            if let Object::Error(err) = self.eval_statement(*step, &mut loop_env) {
+                return Object::Error(err);
+            }
+        }
+        build_br(self.lc.builder, cond_block);
+
+        build_position_at_end(self.lc.builder, end_block);
+        Object::Null
+    }
+
+    fn eval_loop_body(
+        &mut self,
+        label: Option<Ident>,
+        body: BlockStmt,
+        continue_block: *mut LLVMBasicBlock,
+        break_block: *mut LLVMBasicBlock,
+        env: &mut Environment,
+    ) -> Object {
+        self.loop_stack.push(label.map(|label| label.0), continue_block, break_block);
+        let result = self.eval_program(body, &mut env.clone());
+        self.loop_stack.pop();
+        if let Object::Error(err) = result {
+            return Object::Error(err);
+        }
+
+        if !self.is_terminated() {
+            build_br(self.lc.builder, continue_block);
+        }
+        Object::Null
+    }
+
+    // eval_jump_statement lowers `break` (when `is_break` is set) and
+    // `continue`.
+    pub fn eval_jump_statement(&mut self, label: Option<Ident>, is_break: bool, span: &Span) -> Object {
+        let keyword = if is_break { "break" } else { "continue" };
+        let target = match self.loop_stack.find(label.as_ref().map(|label| label.0.as_str())) {
+            Some(target) if is_break => target.break_block,
+            Some(target) => target.continue_block,
+            None => return match label {
+                Some(label) => Object::Error(format!("{}: no loop labelled `{}`", span, label.0)),
+                None => Object::Error(format!("{}: `{}` outside of a loop", span, keyword)),
+            },
+        };
+
+        build_br(self.lc.builder, target);
+        Object::Null
+    }
+
+    fn eval_condition(&mut self, cond: Expr, env: &mut Environment) -> Object {
+        let cond_span = cond.span.clone();
+        match self.eval_expression(cond, env) {
+            object @ Object::Boolean(_) => object,
+            Object::Error(err) => Object::Error(err),
+            _ => Object::Error(format!("{}: condition must be a bool", cond_span)),
+        }
+    }
+
+    // eval_if yields a value when both arms end in a tail expression of the
+    // same type; the values are merged with a PHI node. An arm that returns
+    // or breaks does not reach the end and has no say in the result.
+    pub fn eval_if(
+        &mut self,
+        cond: Expr,
+        consequence: Program,
+        alternative: Option<Program>,
+        hint: Option<IntKind>,
+        span: &Span,
+        env: &mut Environment,
+    ) -> Object {
+        let current_function = self.function_stack.last();
+
+        let boolean = match self.eval_condition(cond, env) {
+            Object::Boolean(value) => value,
+            object => return object,
+        };
+
+        let left_block = append_basic_block_in_context(self.lc.context, current_function, "");
+        let right_block = append_basic_block_in_context(self.lc.context, current_function, "");
+        let end_block = append_basic_block_in_context(self.lc.context, current_function, "");
+        let has_alternative = alternative.is_some();
+
+        build_cond_br(self.lc.builder, boolean, left_block, right_block);
+        // Without a hint from the context, an unsuffixed literal in the `else`
+        // arm takes the integer kind of the first arm.
+        let mut hint = hint;
+        let mut arms = vec![];
+        for (block, program) in [(left_block, consequence), (right_block, alternative.unwrap_or_default())] {
+            build_position_at_end(self.lc.builder, block);
+            let value = self.eval_branch(program, hint, &mut env.clone());
+            if let Object::Error(err) = value {
+                return Object::Error(err);
+            }
+            hint = hint.or(int_kind_of_object(&value));
+            if !self.is_terminated() {
+                arms.push((value, get_insert_block(self.lc.builder)));
+                build_br(self.lc.builder, end_block);
+            }
+        }
+
+        build_position_at_end(self.lc.builder, end_block);
+        if !has_alternative {
+            return match arms.first() {
+                Some((Object::Null, _)) | None => Object::Null,
+                Some(_) => Object::Error(format!("{}: `if` without `else` has no value", span)),
+            };
+        }
+        self.merge_arms(arms, "`if` arms", span)
+    }
+
+    // merge_arms joins the values the arms of an `if` or a `match` end with.
+    // Either every arm that falls through has a value of the same type, or
+    // none has one.
+    fn merge_arms(&mut self, arms: Vec<(Object, *mut LLVMBasicBlock)>, what: &str, span: &Span) -> Object {
+        if arms.iter().all(|(value, _)| matches!(value, Object::Null)) {
+            return Object::Null;
+        }
+
+        let (first, _) = arms[0].clone();
+        for (value, _) in arms.iter().skip(1) {
+            if get_expression_type_from_object(value) != get_expression_type_from_object(&first) {
+                return Object::Error(format!(
+                    "{}: {} have different types: {} and {}", span, what, first, value
+                ));
+            }
+        }
+        if !first.is_variable() {
+            return Object::Null;
+        }
+
+        let (mut values, mut blocks): (Vec<_>, Vec<_>) = arms
+            .into_iter()
+            .map(|(mut value, block)| (unwrap_object(&mut value), block))
+            .unzip();
+        let llvm_type = get_llvm_type_from_object(&mut first.clone());
+        let phi = build_phi(self.lc.builder, llvm_type, &mut values, &mut blocks, "");
+        rewrap_llvm_value_ref(first, phi)
+    }
+
+    // eval_branch compiles one arm of an `if` or a `match` and returns the
+    // value of its tail expression, or Null if it has none.
+    fn eval_branch(&mut self, mut program: Program, hint: Option<IntKind>, env: &mut Environment) -> Object {
+        let tail = match program.last() {
+            Some(Stmt { kind: StmtKind::Tail(_), .. }) => program.pop(),
+            _ => None,
+        };
+
+        if let Object::Error(err) = self.eval_program(program, env) {
+            return Object::Error(err);
+        }
+        match tail {
+            Some(Stmt { kind: StmtKind::Tail(expr), .. }) if !self.is_terminated() => {
+                self.eval_expression_with_hint(expr, hint, env)
+            }
+            _ => Object::Null,
+        }
+    }
+
+    pub fn eval_expression(&mut self, expr: Expr, env: &mut Environment) -> Object {
+        self.eval_expression_with_hint(expr, None, env)
+    }
+
+    // eval_expression_with_hint compiles an expression whose context expects
+    // an integer of kind `hint`. Integer literals without a suffix take that
+    // kind; everywhere else they are `int`.
+    pub fn eval_expression_with_hint(&mut self, expr: Expr, hint: Option<IntKind>, env: &mut Environment) -> Object {
+        let span = expr.span;
+        match expr.kind {
+            ExprKind::Literal(literal) => self.eval_literal(literal, false, hint, &span),
+            ExprKind::Ident(ident) => self.eval_identifier(ident, &span, env),
+            ExprKind::Prefix(prefix, right) => self.eval_prefix_expression(prefix, *right, hint, &span, env),
+            ExprKind::Infix(infix, left, right) => self.eval_infix_expression(infix, *left, *right, hint, &span, env),
+            ExprKind::If { cond, consequence, alternative } => self.eval_if(*cond, consequence, alternative, hint, &span, env),
+            ExprKind::Call { func, args } => self.eval_call_expression(*func, args, &span, env),
+            ExprKind::Cast(value, target) => self.eval_cast_expression(*value, target, &span, env),
+            ExprKind::Tuple(elements) => self.eval_tuple_expression(elements, env),
+            ExprKind::Array(elements) => self.eval_array_expression(elements, hint, &span, env),
+            ExprKind::Hash(map_type, entries) => self.eval_hash_expression(map_type, entries, &span, env),
+            ExprKind::Index { array, index } => self.eval_index_expression(*array, *index, &span, env),
+            ExprKind::Struct { name, fields } => self.eval_struct_expression(name, fields, &span, env),
+            ExprKind::Field { object, field } => match self.module_member(&object, &field, &span, env) {
+                Some(Object::Function(function)) => self.function_value(function),
+                Some(Object::Generic(name, _)) => {
+                    Object::Error(format!("{}: generic function `{}` can only be called", span, name))
+                }
+                Some(member) => member,
+                None => match self.eval_field(*object, &field, &span, env) {
+                    Object::Error(err) => Object::Error(err),
+                    field => self.load_variable(field, ""),
+                },
+            },
+            ExprKind::Variant { name, variant, args } => self.eval_variant_expression(name, variant, args, &span, env),
+            ExprKind::Match { value, arms } => self.eval_match_expression(*value, arms, hint, &span, env),
+            kind @ ExprKind::Closure { .. } => self.eval_closure_expression(kind, &span, env),
+        }
+    }
+
+    // eval_literal reports integer literals that do not fit their type instead
+    // of letting them wrap. `negate` is set for literals directly under a
+    // unary minus so that `-128i8` is in range.
+    pub fn eval_literal(&mut self, literal: Literal, negate: bool, hint: Option<IntKind>, span: &Span) -> Object {
+        match literal {
+            Literal::Int(value, suffix) => {
+                let kind = suffix.or(hint).unwrap_or(IntKind::I32);
+                let value = if negate { -(value as i128) } else { value as i128 };
+                if !kind.contains(value) {
+                    return Object::Error(format!(
+                        "{}: literal `{}` is out of range for {}", span, value, LLVMExpressionType::Integer(kind)
+                    ));
+                }
+                Object::Integer(kind, const_int(integer_type(kind), value as u64))
+            }
+            Literal::Float(value) => Object::Float(const_float(double_type(), value)),
+            Literal::Bool(value) => Object::Boolean(llvm_bool!(value)),
+            Literal::Char(value) => Object::Char(const_int(int32_type(), value as u64)),
+            Literal::String(value) => Object::String(const_string(&mut self.lc, &value)),
+        }
+    }
+
+    pub fn eval_identifier(&mut self, ident: Ident, span: &Span, env: &mut Environment) -> Object {
+        match env.get(&ident.0) {
+            Object::Error(_) => Object::Error(format!("{}: `{}` is not found", span, ident.0)),
+            Object::Function(function) => self.function_value(function),
+            Object::Generic(name, _) => Object::Error(format!("{}: generic function `{}` can only be called", span, name)),
+            object => self.load_variable(object, &ident.0),
+        }
+    }
+
+    // load_variable reads the value out of a stack slot. Arrays, structs and
+    // closures are used in place, through the pointer to their storage.
+    fn load_variable(&mut self, object: Object, name: &str) -> Object {
+        match object {
+            Object::Array(_, _, _) | Object::Named(_, _) | Object::Tuple(_, _) | Object::Closure(_, _, _) => object,
+            mut object if object.is_variable() => {
+                let llvm_type = get_llvm_type_from_object(&mut object);
+                let llvm_value_ref = unwrap_object(&mut object);
+                let llvm_value = build_load(self.lc.builder, llvm_type, llvm_value_ref, name);
+                rewrap_llvm_value_ref(object, llvm_value)
+            }
+            object => object,
+        }
+    }
+
+    // eval_array_expression stores the elements in a new stack slot. An
+    // unsuffixed integer literal takes the kind of the elements before it.
+    pub fn eval_array_expression(&mut self, elements: Vec<Expr>, hint: Option<IntKind>, span: &Span, env: &mut Environment) -> Object {
+        if elements.is_empty() {
+            return Object::Error(format!("{}: cannot infer the element type of an empty array", span));
+        }
+
+        let mut hint = hint;
+        let mut objects = vec![];
+        for element in elements.into_iter() {
+            let element_span = element.span.clone();
+            let object = self.eval_expression_with_hint(element, hint, env);
+            if let Object::Error(err) = object {
+                return Object::Error(err);
+            }
+            if !object.is_variable() {
+                return Object::Error(format!("{}: {} cannot be stored in an array", element_span, object));
+            }
+            hint = hint.or(int_kind_of_object(&object));
+            objects.push((object, element_span));
+        }
+
+        let element_type = get_expression_type_from_object(&objects[0].0);
+        for (object, element_span) in objects.iter().skip(1) {
+            let actual = get_expression_type_from_object(object);
+            if actual != element_type {
+                return Object::Error(format!(
+                    "{}: array elements have different types: {} and {}", element_span, element_type, actual
+                ));
+            }
+        }
+
+        let length = objects.len() as u32;
+        let storage_type = self.storage_type(&LLVMExpressionType::Array(Box::new(element_type.clone()), length));
+        let array = self.build_entry_alloca(storage_type, "");
+        for (index, (object, _)) in objects.iter().enumerate() {
+            let mut indices = [const_int(int64_type(), 0), const_int(int64_type(), index as u64)];
+            let element = build_in_bounds_gep(self.lc.builder, storage_type, array, &mut indices, "");
+            self.store_value(object, element);
+        }
+        let array = cast_type(self.lc.builder, array, pointer_type(), "");
+        Object::Array(element_type, array, length)
+    }
+
+    // eval_tuple_expression stores the elements in a new stack slot.
+    pub fn eval_tuple_expression(&mut self, elements: Vec<Expr>, env: &mut Environment) -> Object {
+        let mut objects = vec![];
+        for element in elements.into_iter() {
+            let element_span = element.span.clone();
+            match self.eval_expression(element, env) {
+                Object::Error(err) => return Object::Error(err),
+                object if object.is_variable() => objects.push(object),
+                object => return Object::Error(format!("{}: {} cannot be stored in a tuple", element_span, object)),
+            }
+        }
+
+        let element_types: Vec<LLVMExpressionType> = objects.iter().map(get_expression_type_from_object).collect();
+        let storage_type = self.storage_type(&LLVMExpressionType::Tuple(element_types.clone()));
+        let pointer = self.build_entry_alloca(storage_type, "");
+        let pointer = cast_type(self.lc.builder, pointer, pointer_type(), "");
+        for (index, object) in objects.iter().enumerate() {
+            let element = self.build_field_pointer(storage_type, pointer, index);
+            self.store_value(object, element);
+        }
+        Object::Tuple(element_types, pointer)
+    }
+
+    pub fn eval_index_expression(&mut self, array: Expr, index: Expr, span: &Span, env: &mut Environment) -> Object {
+        let container = self.eval_expression(array, env);
+        match self.eval_element(container, index, span, env) {
+            Object::Error(err) => Object::Error(err),
+            element => self.load_variable(element, ""),
+        }
+    }
+
+    // eval_element returns the element `container[index]` in the same form as
+    // a variable in the environment: an object holding a pointer to it.
+    fn eval_element(&mut self, container: Object, index: Expr, span: &Span, env: &mut Environment) -> Object {
+        match container {
+            Object::Array(element_type, pointer, length) => {
+                self.eval_array_element(element_type, pointer, length, index, span, env)
+            }
+            Object::Map(key_type, value_type, map) => self.eval_map_value(key_type, value_type, map, index, span, env),
+            Object::Error(err) => Object::Error(err),
+            object => Object::Error(format!("{}: cannot index into {}", span, object)),
+        }
+    }
+
+    // The index is checked against the length at run time.
+    //
+    // Array objects carry an i8 pointer, the type array parameters and PHI
+    // nodes use; it is cast back to the array type before indexing.
+    fn eval_array_element(
+        &mut self,
+        element_type: LLVMExpressionType,
+        pointer: *mut LLVMValue,
+        length: u32,
+        index: Expr,
+        span: &Span,
+        env: &mut Environment,
+    ) -> Object {
+        let index_span = index.span.clone();
+        if let ExprKind::Literal(Literal::Int(value, _)) = index.kind {
+            if value >= length as u64 {
+                return Object::Error(format!(
+                    "{}: index {} is out of bounds for [{}; {}]", index_span, value, element_type, length
+                ));
+            }
+        }
+        let index = match self.eval_expression(index, env) {
+            Object::Integer(kind, value) => self.resize_int(value, kind.bits(), kind.is_signed(), 64),
+            Object::Error(err) => return Object::Error(err),
+            object => return Object::Error(format!("{}: array index must be an integer, not {}", index_span, object)),
+        };
+        self.build_bounds_check(index, length, span);
+
+        let storage_type = self.storage_type(&LLVMExpressionType::Array(Box::new(element_type.clone()), length));
+        let pointer = cast_type(self.lc.builder, pointer, pointer_to(storage_type), "");
+        let mut indices = [const_int(int64_type(), 0), index];
+        let element = build_in_bounds_gep(self.lc.builder, storage_type, pointer, &mut indices, "");
+        self.wrap_element(element_type, element)
+    }
+
+    // wrap_element wraps a pointer to a value of `element_type` stored in
+    // memory, casting it to the pointer type a variable of that type has.
+    fn wrap_element(&mut self, element_type: LLVMExpressionType, element: *mut LLVMValue) -> Object {
+        let pointer_type = match element_type {
+            LLVMExpressionType::Array(_, _)
+            | LLVMExpressionType::Named(_)
+            | LLVMExpressionType::Tuple(_)
+            | LLVMExpressionType::Function(_, _) => pointer_type(),
+            ref element_type => pointer_to(convert_llvm_type(element_type.clone())),
+        };
+        wrap_llvm_value(element_type, cast_type(self.lc.builder, element, pointer_type, ""))
+    }
+
+    // build_bounds_check aborts unless `index` (an i64) is below `length`.
+    // Negative indexes wrap to large unsigned ones.
+    fn build_bounds_check(&mut self, index: *mut LLVMValue, length: u32, span: &Span) {
+        let in_bounds = build_int_ult(self.lc.builder, index, const_int(int64_type(), length as u64), "");
+        let location = span.to_string().replace('%', "%%");
+        let message = format!("{}: index out of bounds: the length is {} but the index is %lld\n", location, length);
+        self.build_check(in_bounds, &message, vec![index]);
+    }
+
+    // build_check prints `message`, a printf format for `args`, and aborts
+    // unless `ok` holds.
+    fn build_check(&mut self, ok: *mut LLVMValue, message: &str, mut args: Vec<*mut LLVMValue>) {
+        let current_function = self.function_stack.last();
+        let fail_block = append_basic_block_in_context(self.lc.context, current_function, "");
+        let ok_block = append_basic_block_in_context(self.lc.context, current_function, "");
+        build_cond_br(self.lc.builder, ok, ok_block, fail_block);
+
+        build_position_at_end(self.lc.builder, fail_block);
+        args.insert(0, const_string(&mut self.lc, message));
+        let printf = self.lc.built_ins["printf"];
+        call_function(self.lc.builder, get_function_type(printf), printf, args, "");
+        let abort = self.lc.built_ins["abort"];
+        call_function(self.lc.builder, get_function_type(abort), abort, vec![], "");
+        build_unreachable(self.lc.builder);
+
+        build_position_at_end(self.lc.builder, ok_block);
+    }
+
+    // eval_hash_expression builds a new table and inserts the entries in
+    // order, so a repeated key keeps its last value.
+    pub fn eval_hash_expression(
+        &mut self,
+        map_type: LLVMExpressionType,
+        entries: Vec<(Expr, Expr)>,
+        span: &Span,
+        env: &mut Environment,
+    ) -> Object {
+        let (key_type, value_type) = match map_type {
+            LLVMExpressionType::Map(key_type, value_type) => (*key_type, *value_type),
+            map_type => return Object::Error(format!("{}: {} is not a map type", span, map_type)),
+        };
+        match key_type {
+            LLVMExpressionType::Integer(_) | LLVMExpressionType::Char | LLVMExpressionType::Boolean | LLVMExpressionType::String => {}
+            ref key_type => return Object::Error(format!("{}: {} cannot be used as a map key", span, key_type)),
+        }
+        if let LLVMExpressionType::Null | LLVMExpressionType::Call = value_type {
+            return Object::Error(format!("{}: {} cannot be stored in a map", span, value_type));
+        }
+        if let Object::Error(err) = self.check_type(&value_type, span) {
+            return Object::Error(err);
+        }
+
+        let key_is_string = matches!(key_type, LLVMExpressionType::String) as u64;
+        let value_size = size_of(self.storage_type(&value_type));
+        let map = self.call_map_runtime("flash_map_new", vec![const_int(int64_type(), key_is_string), value_size]);
+
+        for (key, value) in entries.into_iter() {
+            let key = match self.eval_map_key(&key_type, key, env) {
+                Object::Integer(_, key) => key,
+                object => return object,
+            };
+            let value_span = value.span.clone();
+            let object = self.eval_expression_with_hint(value, int_kind_of(&value_type), env);
+            if let Object::Error(err) = object {
+                return Object::Error(err);
+            }
+            let actual = get_expression_type_from_object(&object);
+            if actual != value_type {
+                return Object::Error(format!("{}: map value should be {}, found {}", value_span, value_type, actual));
+            }
+            self.build_map_insert(map, key, &object);
+        }
+        Object::Map(key_type, value_type, map)
+    }
+
+    // eval_map_key evaluates a key and widens it to the i64 the runtime
+    // takes. Strings are passed by address and compared by content.
+    fn eval_map_key(&mut self, key_type: &LLVMExpressionType, key: Expr, env: &mut Environment) -> Object {
+        let key_span = key.span.clone();
+        let object = self.eval_expression_with_hint(key, int_kind_of(key_type), env);
+        if let Object::Error(err) = object {
+            return Object::Error(err);
+        }
+        let actual = get_expression_type_from_object(&object);
+        if actual != *key_type {
+            return Object::Error(format!("{}: map key should be {}, found {}", key_span, key_type, actual));
+        }
+
+        let key = match object {
+            Object::Integer(kind, value) => self.resize_int(value, kind.bits(), kind.is_signed(), 64),
+            Object::Char(value) | Object::Boolean(value) => build_zext(self.lc.builder, value, int64_type(), ""),
+            Object::String(value) => build_ptr_to_int(self.lc.builder, value, int64_type(), ""),
+            object => return Object::Error(format!("{}: {} cannot be used as a map key", key_span, object)),
+        };
+        Object::Integer(IntKind::I64, key)
+    }
+
+    // A missing key aborts at run time.
+    fn eval_map_value(
+        &mut self,
+        key_type: LLVMExpressionType,
+        value_type: LLVMExpressionType,
+        map: *mut LLVMValue,
+        index: Expr,
+        span: &Span,
+        env: &mut Environment,
+    ) -> Object {
+        let key = match self.eval_map_key(&key_type, index, env) {
+            Object::Integer(_, key) => key,
+            object => return object,
+        };
+        self.build_map_find(value_type, map, key, span)
+    }
+
+    fn build_map_find(&mut self, value_type: LLVMExpressionType, map: *mut LLVMValue, key: *mut LLVMValue, span: &Span) -> Object {
+        let value = self.call_map_runtime("flash_map_find", vec![map, key]);
+        let found = build_is_not_null(self.lc.builder, value, "");
+        let location = span.to_string().replace('%', "%%");
+        self.build_check(found, &format!("{}: key not found in map\n", location), vec![]);
+        self.wrap_element(value_type, value)
+    }
+
+    fn build_map_insert(&mut self, map: *mut LLVMValue, key: *mut LLVMValue, object: &Object) {
+        let value = self.call_map_runtime("flash_map_insert", vec![map, key]);
+        let target = self.wrap_element(get_expression_type_from_object(object), value);
+        self.store_value(object, unwrap_object(&mut target.clone()));
+    }
+
+    // call_map_runtime links the map runtime into the module on first use.
+    fn call_map_runtime(&mut self, name: &str, args: Vec<*mut LLVMValue>) -> *mut LLVMValue {
+        link_map_runtime(&mut self.lc);
+        let function = self.lc.built_ins[name];
+        call_function(self.lc.builder, get_function_type(function), function, args, "")
+    }
+
+    // eval_struct_expression stores the fields in a new stack slot. Every
+    // field must be given exactly once.
+    pub fn eval_struct_expression(&mut self, name: Ident, fields: Vec<(Ident, Expr)>, span: &Span, env: &mut Environment) -> Object {
+        let definition = match self.structs.get(&name.0) {
+            Some(definition) => definition.clone(),
+            None => return Object::Error(format!("{}: unknown struct `{}`", span, name.0)),
+        };
+
+        let mut values: Vec<Option<Object>> = vec![None; definition.fields.len()];
+        for (field, value) in fields.into_iter() {
+            let value_span = value.span.clone();
+            let index = match definition.fields.iter().position(|(declared, _)| *declared == field.0) {
+                Some(index) => index,
+                None => return Object::Error(format!("{}: `{}` has no field `{}`", value_span, name.0, field.0)),
+            };
+            if values[index].is_some() {
+                return Object::Error(format!("{}: field `{}` is given twice", value_span, field.0));
+            }
+
+            let field_type = &definition.fields[index].1;
+            let object = self.eval_expression_with_hint(value, int_kind_of(field_type), env);
+            if let Object::Error(err) = object {
+                return Object::Error(err);
+            }
+            let actual = get_expression_type_from_object(&object);
+            if actual != *field_type {
+                return Object::Error(format!(
+                    "{}: field `{}` of `{}` should be {}, found {}", value_span, field.0, name.0, field_type, actual
+                ));
+            }
+            values[index] = Some(object);
+        }
+        if let Some(index) = values.iter().position(|value| value.is_none()) {
+            return Object::Error(format!("{}: missing field `{}` in `{}`", span, definition.fields[index].0, name.0));
+        }
+
+        let pointer = self.build_entry_alloca(definition.llvm_type, "");
+        let pointer = cast_type(self.lc.builder, pointer, pointer_type(), "");
+        for (index, value) in values.into_iter().enumerate() {
+            let field = self.build_field_pointer(definition.llvm_type, pointer, index);
+            self.store_value(&value.unwrap(), field);
+        }
+        Object::Named(name.0, pointer)
+    }
+
+    // eval_field returns the field `object.field` in the same form as a
+    // variable: an object holding a pointer to it.
+    fn eval_field(&mut self, object: Expr, field: &Ident, span: &Span, env: &mut Environment) -> Object {
+        let (name, pointer) = match self.eval_expression(object, env) {
+            Object::Named(name, pointer) if self.structs.contains_key(&name) => (name, pointer),
+            Object::Tuple(elements, pointer) => return self.eval_tuple_element(elements, pointer, field, span),
+            Object::Error(err) => return Object::Error(err),
+            object => return Object::Error(format!("{}: {} has no fields", span, object)),
+        };
+        let definition = &self.structs[&name];
+        let (index, field_type) = match definition.fields.iter().position(|(declared, _)| *declared == field.0) {
+            Some(index) => (index, definition.fields[index].1.clone()),
+            None => return Object::Error(format!("{}: `{}` has no field `{}`", span, name, field.0)),
+        };
+
+        let field = self.build_field_pointer(definition.llvm_type, pointer, index);
+        self.wrap_element(field_type, field)
+    }
+
+    // eval_tuple_element returns `t.0`, the element a field index names.
+    fn eval_tuple_element(&mut self, elements: Vec<LLVMExpressionType>, pointer: *mut LLVMValue, field: &Ident, span: &Span) -> Object {
+        let tuple_type = LLVMExpressionType::Tuple(elements.clone());
+        let index = match field.0.parse::<usize>() {
+            Ok(index) if index < elements.len() => index,
+            _ => return Object::Error(format!("{}: {} has no element `{}`", span, tuple_type, field.0)),
+        };
+        let element = self.build_field_pointer(self.storage_type(&tuple_type), pointer, index);
+        self.wrap_element(elements[index].clone(), element)
+    }
+
+    // Struct and enum objects carry an i8 pointer, like arrays; it is cast
+    // back to `struct_type` before indexing.
+    fn build_field_pointer(&mut self, struct_type: *mut LLVMType, pointer: *mut LLVMValue, index: usize) -> *mut LLVMValue {
+        let pointer = cast_type(self.lc.builder, pointer, pointer_to(struct_type), "");
+        let mut indices = [const_int(int32_type(), 0), const_int(int32_type(), index as u64)];
+        build_in_bounds_gep(self.lc.builder, struct_type, pointer, &mut indices, "")
+    }
+
+    // eval_variant_expression stores the tag and the payload of a variant in
+    // a new stack slot.
+    pub fn eval_variant_expression(&mut self, name: Ident, variant: Ident, args: Vec<Expr>, span: &Span, env: &mut Environment) -> Object {
+        let (tag, definition) = match self.find_variant(&name, &variant, span) {
+            Ok(found) => found,
+            Err(err) => return err,
+        };
+        if args.len() != definition.fields.len() {
+            return Object::Error(format!(
+                "{}: `{}::{}` takes {} values, found {}", span, name.0, variant.0, definition.fields.len(), args.len()
+            ));
+        }
+
+        let mut values = vec![];
+        for (index, (arg, field_type)) in args.into_iter().zip(definition.fields.iter()).enumerate() {
+            let arg_span = arg.span.clone();
+            let object = self.eval_expression_with_hint(arg, int_kind_of(field_type), env);
+            if let Object::Error(err) = object {
+                return Object::Error(err);
+            }
+            let actual = get_expression_type_from_object(&object);
+            if actual != *field_type {
+                return Object::Error(format!(
+                    "{}: value {} of `{}::{}` should be {}, found {}", arg_span, index + 1, name.0, variant.0, field_type, actual
+                ));
+            }
+            values.push(object);
+        }
+
+        let pointer = self.build_entry_alloca(self.enums[&name.0].llvm_type, "");
+        let pointer = cast_type(self.lc.builder, pointer, pointer_type(), "");
+        let tag_pointer = self.build_field_pointer(definition.llvm_type, pointer, 0);
+        build_store(self.lc.builder, const_int(int64_type(), tag as u64), tag_pointer);
+        for (index, value) in values.iter().enumerate() {
+            let field = self.build_field_pointer(definition.llvm_type, pointer, index + 1);
+            self.store_value(value, field);
+        }
+        Object::Named(name.0, pointer)
+    }
+
+    fn find_variant(&self, name: &Ident, variant: &Ident, span: &Span) -> Result<(usize, EnumVariant), Object> {
+        let definition = match self.enums.get(&name.0) {
+            Some(definition) => definition,
+            None => return Err(Object::Error(format!("{}: unknown enum `{}`", span, name.0))),
+        };
+        match definition.variants.iter().position(|declared| declared.name == variant.0) {
+            Some(index) => Ok((index, definition.variants[index].clone())),
+            None => Err(Object::Error(format!("{}: `{}` has no variant `{}`", span, name.0, variant.0))),
+        }
+    }
+
+    // eval_match_expression switches on the tag of an enum, or on an integer,
+    // char or bool. Each case tries the arms that can match it in order, and
+    // the values of the arms are merged as for `if`.
+    pub fn eval_match_expression(
+        &mut self,
+        value: Expr,
+        arms: Vec<MatchArm>,
+        hint: Option<IntKind>,
+        span: &Span,
+        env: &mut Environment,
+    ) -> Object {
+        let value_span = value.span.clone();
+        let value = self.eval_expression(value, env);
+        match &value {
+            Object::Error(_) => return value,
+            Object::Integer(_, _) | Object::Char(_) | Object::Boolean(_) => {}
+            Object::Named(name, _) if self.enums.contains_key(name) => {}
+            object => return Object::Error(format!("{}: cannot match on {}", value_span, object)),
+        }
+        let value_type = get_expression_type_from_object(&value);
+
+        // Bindings get their stack slots up front, since an arm whose pattern
+        // matches any value is tried in several cases.
+        let mut arm_envs = vec![];
+        for arm in arms.iter() {
+            let mut arm_env = env.clone();
+            let mut bindings = vec![];
+            if let Object::Error(err) = self.check_pattern(&arm.pattern, &value_type, &mut bindings, span, &mut arm_env) {
+                return Object::Error(err);
+            }
+            for (name, binding_type) in bindings {
+                let slot = self.build_entry_alloca(self.storage_type(&binding_type), &name);
+                let variable = self.wrap_element(binding_type, slot);
+                arm_env.set(name, variable);
+            }
+            arm_envs.push(arm_env);
+        }
+
+        // A guard may fail, so guarded arms do not count towards covering.
+        let rows = arms.iter().filter(|arm| arm.guard.is_none()).map(|arm| vec![arm.pattern.clone()]).collect();
+        if let Some(missing) = self.missing_patterns(rows, std::slice::from_ref(&value_type)) {
+            return Object::Error(format!("{}: match is not exhaustive: `{}` is not covered", span, missing[0]));
+        }
+
+        // The constant the switch compares with for each arm, or None for
+        // an arm that matches any value.
+        let mut arm_cases = vec![];
+        for (arm, arm_env) in arms.iter().zip(arm_envs.iter_mut()) {
+            arm_cases.push(match &arm.pattern {
+                Pattern::Literal(literal) => Some(unwrap_object(&mut self.eval_pattern_literal(literal, &value_type, arm_env))),
+                Pattern::Variant { name, variant, .. } => {
+                    let (tag, _) = self.find_variant(name, variant, span).unwrap();
+                    Some(const_int(int64_type(), tag as u64))
+                }
+                _ => None,
+            });
+        }
+        let mut cases: Vec<*mut LLVMValue> = vec![];
+        for case in arm_cases.iter().flatten() {
+            if !cases.contains(case) {
+                cases.push(*case);
+            }
+        }
+
+        let discriminant = match value {
+            Object::Named(ref name, pointer) => {
+                let tag = self.build_field_pointer(self.enums[name].llvm_type, pointer, 0);
+                build_load(self.lc.builder, int64_type(), tag, "")
+            }
+            ref value => unwrap_object(&mut value.clone()),
+        };
+        let current_function = self.function_stack.last();
+        let body_blocks: Vec<_> = arms.iter().map(|_| append_basic_block_in_context(self.lc.context, current_function, "")).collect();
+        let case_blocks: Vec<_> = cases
+            .iter()
+            .map(|case| (*case, append_basic_block_in_context(self.lc.context, current_function, "")))
+            .collect();
+        let default_block = append_basic_block_in_context(self.lc.context, current_function, "");
+        build_switch(self.lc.builder, discriminant, default_block, &case_blocks);
+
+        let all_cases = case_blocks.iter().map(|(case, block)| (Some(*case), *block)).chain([(None, default_block)]);
+        for (case, block) in all_cases {
+            build_position_at_end(self.lc.builder, block);
+            let mut matched = false;
+            for (index, arm) in arms.iter().enumerate() {
+                if arm_cases[index].is_some() && arm_cases[index] != case {
+                    continue;
+                }
+                let condition = self.build_pattern_test(&arm.pattern, &value, true, &mut arm_envs[index]);
+                if condition.is_none() && arm.guard.is_none() {
+                    build_br(self.lc.builder, body_blocks[index]);
+                    matched = true;
+                    break;
+                }
+
+                let next_block = append_basic_block_in_context(self.lc.context, current_function, "");
+                if let Some(condition) = condition {
+                    let guard_block = append_basic_block_in_context(self.lc.context, current_function, "");
+                    build_cond_br(self.lc.builder, condition, guard_block, next_block);
+                    build_position_at_end(self.lc.builder, guard_block);
+                }
+                match &arm.guard {
+                    Some(guard) => {
+                        let guard = match self.eval_condition(guard.clone(), &mut arm_envs[index]) {
+                            Object::Boolean(guard) => guard,
+                            object => return object,
+                        };
+                        build_cond_br(self.lc.builder, guard, body_blocks[index], next_block);
+                    }
+                    None => build_br(self.lc.builder, body_blocks[index]),
+                }
+                build_position_at_end(self.lc.builder, next_block);
+            }
+            // Exhaustiveness rules this out, bar a default case no value reaches.
+            if !matched {
+                build_unreachable(self.lc.builder);
+            }
+        }
+
+        let end_block = append_basic_block_in_context(self.lc.context, current_function, "");
+        let mut hint = hint;
+        let mut results = vec![];
+        for (index, arm) in arms.into_iter().enumerate() {
+            build_position_at_end(self.lc.builder, body_blocks[index]);
+            let value = self.eval_branch(arm.body, hint, &mut arm_envs[index].clone());
+            if let Object::Error(err) = value {
+                return Object::Error(err);
+            }
+            hint = hint.or(int_kind_of_object(&value));
+            if !self.is_terminated() {
+                results.push((value, get_insert_block(self.lc.builder)));
+                build_br(self.lc.builder, end_block);
+            }
+        }
+
+        build_position_at_end(self.lc.builder, end_block);
+        self.merge_arms(results, "match arms", span)
+    }
+
+    // check_pattern checks that `pattern` fits a value of `value_type` and
+    // collects the names it binds, with their types.
+    fn check_pattern(
+        &mut self,
+        pattern: &Pattern,
+        value_type: &LLVMExpressionType,
+        bindings: &mut Vec<(String, LLVMExpressionType)>,
+        span: &Span,
+        env: &mut Environment,
+    ) -> Object {
+        match pattern {
+            Pattern::Wildcard => Object::Null,
+            Pattern::Binding(name) => {
+                if bindings.iter().any(|(bound, _)| *bound == name.0) {
+                    return Object::Error(format!("{}: `{}` is bound twice in the same pattern", span, name.0));
+                }
+                bindings.push((name.0.clone(), value_type.clone()));
+                Object::Null
+            }
+            Pattern::Literal(literal) => match self.eval_pattern_literal(literal, value_type, env) {
+                Object::Error(err) => Object::Error(err),
+                _ => Object::Null,
+            },
+            Pattern::Variant { name, variant, fields } => {
+                let (_, definition) = match self.find_variant(name, variant, span) {
+                    Ok(found) => found,
+                    Err(err) => return err,
+                };
+                if LLVMExpressionType::Named(name.0.clone()) != *value_type {
+                    return Object::Error(format!(
+                        "{}: expected a pattern for {}, found `{}::{}`", span, value_type, name.0, variant.0
+                    ));
+                }
+                if fields.len() != definition.fields.len() {
+                    return Object::Error(format!(
+                        "{}: `{}::{}` has {} values, found {}", span, name.0, variant.0, definition.fields.len(), fields.len()
+                    ));
+                }
+                for (field, field_type) in fields.iter().zip(definition.fields.iter()) {
+                    if let Object::Error(err) = self.check_pattern(field, field_type, bindings, span, env) {
+                        return Object::Error(err);
+                    }
+                }
+                Object::Null
+            }
+        }
+    }
+
+    // eval_pattern_literal evaluates a literal pattern as a constant of
+    // `value_type`.
+    fn eval_pattern_literal(&mut self, literal: &Expr, value_type: &LLVMExpressionType, env: &mut Environment) -> Object {
+        let object = self.eval_expression_with_hint(literal.clone(), int_kind_of(value_type), env);
+        if let Object::Error(err) = object {
+            return Object::Error(err);
+        }
+        let literal_type = get_expression_type_from_object(&object);
+        if literal_type != *value_type {
+            return Object::Error(format!(
+                "{}: expected a pattern for {}, found {}", literal.span, value_type, literal_type
+            ));
+        }
+        if let LLVMExpressionType::String = literal_type {
+            return Object::Error(format!("{}: strings cannot be matched against a literal", literal.span));
+        }
+        object
+    }
+
+    // build_pattern_test stores the values `pattern` binds into their slots
+    // and returns the condition under which it matches, or None if it
+    // matches any value. When `checked` is set the switch has already
+    // compared the tag or the literal.
+    fn build_pattern_test(&mut self, pattern: &Pattern, value: &Object, checked: bool, env: &mut Environment) -> Option<*mut LLVMValue> {
+        match pattern {
+            Pattern::Wildcard => None,
+            Pattern::Binding(name) => {
+                let slot = unwrap_object(&mut env.get(&name.0));
+                self.store_value(value, slot);
+                None
+            }
+            Pattern::Literal(_) if checked => None,
+            Pattern::Literal(literal) => {
+                let value_type = get_expression_type_from_object(value);
+                let expected = unwrap_object(&mut self.eval_pattern_literal(literal, &value_type, env));
+                let actual = unwrap_object(&mut value.clone());
+                Some(match value_type {
+                    LLVMExpressionType::Float => build_float_oeq(self.lc.builder, actual, expected, ""),
+                    _ => build_int_eq(self.lc.builder, actual, expected, ""),
+                })
+            }
+            Pattern::Variant { name, variant, fields } => {
+                let (tag, definition) = self.find_variant(name, variant, &Span::default()).ok()?;
+                let pointer = unwrap_object(&mut value.clone());
+                let mut condition = None;
+                if !checked {
+                    let tag_pointer = self.build_field_pointer(definition.llvm_type, pointer, 0);
+                    let actual = build_load(self.lc.builder, int64_type(), tag_pointer, "");
+                    condition = Some(build_int_eq(self.lc.builder, actual, const_int(int64_type(), tag as u64), ""));
+                }
+                for (index, (field, field_type)) in fields.iter().zip(definition.fields.iter()).enumerate() {
+                    if let Pattern::Wildcard = field {
+                        continue;
+                    }
+                    let element = self.build_field_pointer(definition.llvm_type, pointer, index + 1);
+                    let field_value = self.wrap_element(field_type.clone(), element);
+                    let field_value = self.load_variable(field_value, "");
+                    if let Some(test) = self.build_pattern_test(field, &field_value, false, env) {
+                        condition = Some(match condition {
+                            Some(condition) => and_variable(self.lc.builder, condition, test, ""),
+                            None => test,
+                        });
+                    }
+                }
+                condition
+            }
+        }
+    }
+
+    // missing_patterns returns values that no row of patterns matches,
+    // written as patterns, or None when the rows cover every value of
+    // `types`. Each row is specialised to every variant, or to true and
+    // false, in turn; other types are only covered by a wildcard.
+    fn missing_patterns(&self, rows: Vec<Vec<Pattern>>, types: &[LLVMExpressionType]) -> Option<Vec<String>> {
+        let (first_type, rest_types) = match types.split_first() {
+            Some(split) => split,
+            None => return if rows.is_empty() { Some(vec![]) } else { None },
+        };
+        let constructors: Vec<(String, String, Vec<LLVMExpressionType>)> = match first_type {
+            LLVMExpressionType::Named(name) if self.enums.contains_key(name) => self.enums[name]
+                .variants
+                .iter()
+                .map(|variant| (variant.name.clone(), format!("{}::{}", name, variant.name), variant.fields.clone()))
+                .collect(),
+            LLVMExpressionType::Boolean => ["true", "false"].iter().map(|value| (value.to_string(), value.to_string(), vec![])).collect(),
+            _ => {
+                let rows = rows
+                    .into_iter()
+                    .filter(|row| matches!(row[0], Pattern::Wildcard | Pattern::Binding(_)))
+                    .map(|row| row[1..].to_vec())
+                    .collect();
+                let mut missing = self.missing_patterns(rows, rest_types)?;
+                missing.insert(0, "_".to_owned());
+                return Some(missing);
+            }
+        };
+
+        for (constructor, written, field_types) in constructors.iter() {
+            let specialized = rows
+                .iter()
+                .filter_map(|row| {
+                    let fields = match &row[0] {
+                        Pattern::Wildcard | Pattern::Binding(_) => vec![Pattern::Wildcard; field_types.len()],
+                        Pattern::Variant { variant, fields, .. } if variant.0 == *constructor => fields.clone(),
+                        Pattern::Literal(Expr { kind: ExprKind::Literal(Literal::Bool(value)), .. }) if value.to_string() == *constructor => vec![],
+                        _ => return None,
+                    };
+                    Some(fields.into_iter().chain(row[1..].iter().cloned()).collect())
+                })
+                .collect();
+            let types: Vec<LLVMExpressionType> = field_types.iter().chain(rest_types).cloned().collect();
+            if let Some(missing) = self.missing_patterns(specialized, &types) {
+                let (fields, rest) = missing.split_at(field_types.len());
+                let head = match fields.is_empty() {
+                    true => written.clone(),
+                    false => format!("{}({})", written, fields.join(", ")),
+                };
+                return Some(std::iter::once(head).chain(rest.iter().cloned()).collect());
+            }
+        }
+        None
+    }
+
+    pub fn eval_prefix_expression(&mut self, prefix: Prefix, right: Expr, hint: Option<IntKind>, span: &Span, env: &mut Environment) -> Object {
+        if let (Prefix::Minus, ExprKind::Literal(Literal::Int(_, _))) = (&prefix, &right.kind) {
+            if let ExprKind::Literal(literal) = right.kind {
+                return self.eval_literal(literal, true, hint, span);
+            }
+        }
+
+        match (prefix, self.eval_expression_with_hint(right, hint, env)) {
+            (_, Object::Error(err)) => Object::Error(err),
+            (Prefix::Plus, Object::Integer(kind, value)) => Object::Integer(kind, value),
+            (Prefix::Plus, Object::Float(value)) => Object::Float(value),
+            (Prefix::Minus, Object::Float(value)) => Object::Float(float_neg_variable(self.lc.builder, value, "")),
+            (Prefix::Minus, Object::Integer(kind, value)) if kind.is_signed() => {
+                Object::Integer(kind, neg_variable(self.lc.builder, value, ""))
+            }
+            (Prefix::Not, Object::Boolean(value)) => Object::Boolean(not_variable(self.lc.builder, value, "")),
+            (prefix, object) => Object::Error(format!("{}: cannot apply {:?} to {}", span, prefix, object)),
+        }
+    }
+
+    pub fn eval_infix_expression(&mut self, infix: Infix, left: Expr, right: Expr, hint: Option<IntKind>, span: &Span, env: &mut Environment) -> Object {
+        if matches!(infix, Infix::And | Infix::Or) {
+            return self.eval_logical_expression(infix, left, right, env);
+        }
+
+        // Comparisons produce a bool, so the context says nothing about the
+        // kind of their operands.
+        let hint = match infix {
+            Infix::Plus | Infix::Minus | Infix::Multiply | Infix::Divide | Infix::Modulo
+            | Infix::BitAnd | Infix::BitOr | Infix::BitXor | Infix::ShiftLeft | Infix::ShiftRight => hint,
+            _ => None,
+        };
+
+        // An unsuffixed literal operand takes the kind of the other operand.
+        // Literals have no side effects, so compiling the other side first
+        // does not change the program's behaviour.
+        let (left, right) = if is_unsuffixed_int_literal(&left) && !is_unsuffixed_int_literal(&right) {
+            let right = self.eval_expression_with_hint(right, hint, env);
+            let left = self.eval_expression_with_hint(left, int_kind_of_object(&right).or(hint), env);
+            (left, right)
+        } else {
+            let left = self.eval_expression_with_hint(left, hint, env);
+            let right = self.eval_expression_with_hint(right, int_kind_of_object(&left).or(hint), env);
+            (left, right)
+        };
+        if let Object::Error(err) = left {
+            return Object::Error(err);
+        }
+        if let Object::Error(err) = right {
+            return Object::Error(err);
+        }
+        self.apply_infix(infix, left, right, span)
+    }
+
+    // apply_infix combines two evaluated operands of the same type.
+    fn apply_infix(&mut self, infix: Infix, left: Object, right: Object, span: &Span) -> Object {
+        match (left, right) {
+            (Object::Integer(lk, l), Object::Integer(rk, r)) if lk == rk => self.eval_integer_infix(infix, lk, l, r, span),
+            (Object::Float(l), Object::Float(r)) => self.eval_float_infix(infix, l, r, span),
+            (Object::Char(l), Object::Char(r)) => self.eval_char_infix(infix, l, r, span),
+            (Object::Boolean(l), Object::Boolean(r)) => self.eval_boolean_infix(infix, l, r, span),
+            (left, right) => Object::Error(format!(
+                "{}: unsupported operands for `{}`: {} and {}", span, infix, left, right
+            )),
+        }
+    }
+
+    // eval_logical_expression short-circuits `&&` and `||`: the right operand
+    // is only evaluated when the left one does not already decide the result.
+    fn eval_logical_expression(&mut self, infix: Infix, left: Expr, right: Expr, env: &mut Environment) -> Object {
+        let current_function = self.function_stack.last();
+        let is_and = infix == Infix::And;
+
+        let l = match self.eval_logical_operand(&infix, left, env) {
+            Object::Boolean(value) => value,
+            object => return object,
+        };
+        let left_block = get_insert_block(self.lc.builder);
+        let right_block = append_basic_block_in_context(self.lc.context, current_function, "");
+        let end_block = append_basic_block_in_context(self.lc.context, current_function, "");
+        if is_and {
+            build_cond_br(self.lc.builder, l, right_block, end_block);
+        } else {
+            build_cond_br(self.lc.builder, l, end_block, right_block);
+        }
+
+        build_position_at_end(self.lc.builder, right_block);
+        let r = match self.eval_logical_operand(&infix, right, env) {
+            Object::Boolean(value) => value,
+            object => return object,
+        };
+        let right_block = get_insert_block(self.lc.builder);
+        build_br(self.lc.builder, end_block);
+
+        build_position_at_end(self.lc.builder, end_block);
+        // Coming straight from the left operand, the result is what decided it.
+        let decided = const_int(int1_type(), if is_and { 0 } else { 1 });
+        Object::Boolean(build_phi(self.lc.builder, int1_type(), &mut [decided, r], &mut [left_block, right_block], ""))
+    }
+
+    fn eval_logical_operand(&mut self, infix: &Infix, operand: Expr, env: &mut Environment) -> Object {
+        let span = operand.span.clone();
+        match self.eval_expression(operand, env) {
+            object @ Object::Boolean(_) => object,
+            Object::Error(err) => Object::Error(err),
+            object => Object::Error(format!("{}: `{}` expects bool operands, not {}", span, infix, object)),
+        }
+    }
+
+    // Division, remainder, right shifts and ordering depend on whether the
+    // operands are signed; everything else is the same instruction for both.
+    fn eval_integer_infix(&mut self, infix: Infix, kind: IntKind, l: *mut LLVMValue, r: *mut LLVMValue, span: &Span) -> Object {
+        let builder = self.lc.builder;
+        let signed = kind.is_signed();
+        match infix {
+            Infix::Plus => Object::Integer(kind, add_variable(builder, l, r, "")),
+            Infix::Minus => Object::Integer(kind, sub_variable(builder, l, r, "")),
+            Infix::Multiply => Object::Integer(kind, multiple_variable(builder, l, r, "")),
+            Infix::Divide if signed => Object::Integer(kind, signed_divide_variable(builder, l, r, "")),
+            Infix::Divide => Object::Integer(kind, divide_variable(builder, l, r, "")),
+            Infix::Modulo if signed => Object::Integer(kind, signed_rem_variable(builder, l, r, "")),
+            Infix::Modulo => Object::Integer(kind, rem_variable(builder, l, r, "")),
+            Infix::BitAnd => Object::Integer(kind, and_variable(builder, l, r, "")),
+            Infix::BitOr => Object::Integer(kind, or_variable(builder, l, r, "")),
+            Infix::BitXor => Object::Integer(kind, xor_variable(builder, l, r, "")),
+            Infix::ShiftLeft => Object::Integer(kind, shift_left_variable(builder, l, r, "")),
+            Infix::ShiftRight if signed => Object::Integer(kind, arithmetic_shift_right_variable(builder, l, r, "")),
+            Infix::ShiftRight => Object::Integer(kind, logical_shift_right_variable(builder, l, r, "")),
+            Infix::Equal => Object::Boolean(build_int_eq(builder, l, r, "")),
+            Infix::NotEqual => Object::Boolean(build_int_ne(builder, l, r, "")),
+            Infix::LessThan if signed => Object::Boolean(build_int_slt(builder, l, r, "")),
+            Infix::LessThan => Object::Boolean(build_int_ult(builder, l, r, "")),
+            Infix::GreaterThan if signed => Object::Boolean(build_int_sgt(builder, l, r, "")),
+            Infix::GreaterThan => Object::Boolean(build_int_ugt(builder, l, r, "")),
+            Infix::LessEqual if signed => Object::Boolean(build_int_sle(builder, l, r, "")),
+            Infix::LessEqual => Object::Boolean(build_int_ule(builder, l, r, "")),
+            Infix::GreaterEqual if signed => Object::Boolean(build_int_sge(builder, l, r, "")),
+            Infix::GreaterEqual => Object::Boolean(build_int_uge(builder, l, r, "")),
+            infix => Object::Error(format!("{}: `{}` is not defined for {}", span, infix, LLVMExpressionType::Integer(kind))),
+        }
+    }
+
+    fn eval_float_infix(&mut self, infix: Infix, l: *mut LLVMValue, r: *mut LLVMValue, span: &Span) -> Object {
+        let builder = self.lc.builder;
+        match infix {
+            Infix::Plus => Object::Float(float_add_variable(builder, l, r, "")),
+            Infix::Minus => Object::Float(float_sub_variable(builder, l, r, "")),
+            Infix::Multiply => Object::Float(float_multiple_variable(builder, l, r, "")),
+            Infix::Divide => Object::Float(float_divide_variable(builder, l, r, "")),
+            Infix::Modulo => Object::Float(float_rem_variable(builder, l, r, "")),
+            Infix::Equal => Object::Boolean(build_float_oeq(builder, l, r, "")),
+            Infix::NotEqual => Object::Boolean(build_float_une(builder, l, r, "")),
+            Infix::LessThan => Object::Boolean(build_float_olt(builder, l, r, "")),
+            Infix::GreaterThan => Object::Boolean(build_float_ogt(builder, l, r, "")),
+            Infix::LessEqual => Object::Boolean(build_float_ole(builder, l, r, "")),
+            Infix::GreaterEqual => Object::Boolean(build_float_oge(builder, l, r, "")),
+            infix => Object::Error(format!("{}: `{}` is not defined for float", span, infix)),
+        }
+    }
+
+    // Chars compare by code point; there is no char arithmetic, so convert to
+    // an integer first.
+    fn eval_char_infix(&mut self, infix: Infix, l: *mut LLVMValue, r: *mut LLVMValue, span: &Span) -> Object {
+        let builder = self.lc.builder;
+        match infix {
+            Infix::Equal => Object::Boolean(build_int_eq(builder, l, r, "")),
+            Infix::NotEqual => Object::Boolean(build_int_ne(builder, l, r, "")),
+            Infix::LessThan => Object::Boolean(build_int_ult(builder, l, r, "")),
+            Infix::GreaterThan => Object::Boolean(build_int_ugt(builder, l, r, "")),
+            Infix::LessEqual => Object::Boolean(build_int_ule(builder, l, r, "")),
+            Infix::GreaterEqual => Object::Boolean(build_int_uge(builder, l, r, "")),
+            infix => Object::Error(format!("{}: `{}` is not defined for char", span, infix)),
+        }
+    }
+
+    // eval_cast_expression converts between integers, floats, chars and
+    // bools. Only a u8 becomes a char, since any byte is a valid code point;
+    // an unsuffixed literal being cast takes the target's integer kind.
+    pub fn eval_cast_expression(&mut self, value: Expr, target: LLVMExpressionType, span: &Span, env: &mut Environment) -> Object {
+        let hint = match target {
+            LLVMExpressionType::Char => Some(IntKind::U8),
+            ref target => int_kind_of(target),
+        };
+        let object = self.eval_expression_with_hint(value, hint, env);
+        let builder = self.lc.builder;
+        let llvm_type = convert_llvm_type(target.clone());
+        match (object, &target) {
+            (Object::Error(err), _) => Object::Error(err),
+            (Object::Integer(from, value), LLVMExpressionType::Integer(to)) => {
+                Object::Integer(*to, self.resize_int(value, from.bits(), from.is_signed(), to.bits()))
+            }
+            (Object::Integer(from, value), LLVMExpressionType::Float) if from.is_signed() => {
+                Object::Float(build_signed_int_to_float(builder, value, llvm_type, ""))
+            }
+            (Object::Integer(_, value), LLVMExpressionType::Float) => {
+                Object::Float(build_unsigned_int_to_float(builder, value, llvm_type, ""))
+            }
+            (Object::Integer(IntKind::U8, value), LLVMExpressionType::Char) => {
+                Object::Char(build_zext(builder, value, llvm_type, ""))
+            }
+            (Object::Float(value), LLVMExpressionType::Integer(to)) if to.is_signed() => {
+                Object::Integer(*to, build_float_to_signed_int(builder, value, llvm_type, ""))
+            }
+            (Object::Float(value), LLVMExpressionType::Integer(to)) => {
+                Object::Integer(*to, build_float_to_unsigned_int(builder, value, llvm_type, ""))
+            }
+            (Object::Char(value), LLVMExpressionType::Integer(to)) => {
+                Object::Integer(*to, self.resize_int(value, 32, false, to.bits()))
+            }
+            (Object::Boolean(value), LLVMExpressionType::Integer(to)) => {
+                Object::Integer(*to, self.resize_int(value, 1, false, to.bits()))
+            }
+            (object, target) if get_expression_type_from_object(&object) == *target => object,
+            (Object::Integer(kind, _), LLVMExpressionType::Char) => Object::Error(format!(
+                "{}: only u8 can be cast to char, not {}", span, LLVMExpressionType::Integer(kind)
+            )),
+            (object, target) => Object::Error(format!("{}: cannot cast {} to {}", span, object, target)),
+        }
+    }
+
+    fn resize_int(&mut self, value: *mut LLVMValue, from_bits: u32, signed: bool, to_bits: u32) -> *mut LLVMValue {
+        let llvm_type = int_type(to_bits);
+        if from_bits > to_bits {
+            build_trunc(self.lc.builder, value, llvm_type, "")
+        } else if from_bits == to_bits {
+            value
+        } else if signed {
+            build_sext(self.lc.builder, value, llvm_type, "")
+        } else {
+            build_zext(self.lc.builder, value, llvm_type, "")
+        }
+    }
+
+    fn eval_boolean_infix(&mut self, infix: Infix, l: *mut LLVMValue, r: *mut LLVMValue, span: &Span) -> Object {
+        let builder = self.lc.builder;
+        match infix {
+            Infix::Equal => Object::Boolean(build_int_eq(builder, l, r, "")),
+            Infix::NotEqual | Infix::BitXor => Object::Boolean(build_int_ne(builder, l, r, "")),
+            Infix::BitAnd => Object::Boolean(and_variable(builder, l, r, "")),
+            Infix::BitOr => Object::Boolean(or_variable(builder, l, r, "")),
+            infix => Object::Error(format!("{}: `{}` is not defined for bool", span, infix)),
+        }
+    }
+
+    pub fn eval_call_expression(&mut self, func: Expr, args: Vec<Expr>, span: &Span, env: &mut Environment) -> Object {
+        let member = match &func.kind {
+            ExprKind::Field { object, field } => self.module_member(object, field, span, env),
+            _ => None,
+        };
+        let func_span = func.span;
+        let function = match (member, func.kind) {
+            (Some(Object::Error(err)), _) => return Object::Error(err),
+            (Some(member), _) => member,
+            (None, ExprKind::Ident(ident)) => match env.get(&ident.0) {
+                Object::Error(_) => return Object::Error(format!("{}: function `{}` is not found", span, ident.0)),
+                object => object,
+            },
+            // Anything else has to evaluate to a closure.
+            (None, func) => match self.eval_expression(Expr::new(func, func_span), env) {
+                Object::Error(err) => return Object::Error(err),
+                object => object,
+            },
+        };
+        // The key is evaluated against the key type of the map.
+        if let Object::BuildIn(build_in @ (BuildIn::Contains | BuildIn::Remove)) = function {
+            return self.eval_map_builtin(build_in, args, span, env);
+        }
+
+        let mut param_types = match function {
+            Object::Function(ref function) => function.param_types.clone(),
+            Object::Closure(ref param_types, _, _) => param_types.clone(),
+            Object::Generic(_, index) => self.generics[index].param_types.clone(),
+            _ => vec![],
+        };
+
+        // An unsuffixed literal passed to a generic function takes the type
+        // the other arguments infer for its parameter, so it is evaluated
+        // after them. Literals have no side effects, so this does not change
+        // the program's behaviour.
+        let is_generic = matches!(function, Object::Generic(_, _));
+        let mut objects: Vec<Option<Object>> = vec![];
+        let mut deferred = vec![];
+        for (index, arg) in args.into_iter().enumerate() {
+            if is_generic && is_unsuffixed_int_literal(&arg) {
+                deferred.push((index, arg));
+                objects.push(None);
+                continue;
+            }
+            let hint = param_types.get(index).and_then(int_kind_of);
+            match self.eval_expression_with_hint(arg, hint, env) {
+                Object::Error(err) => return Object::Error(err),
+                object => objects.push(Some(object)),
+            }
+        }
+        if let Object::Generic(_, index) = function {
+            let generic = &self.generics[index];
+            let mut bindings = HashMap::new();
+            for (param_type, object) in generic.param_types.iter().zip(objects.iter()) {
+                if let Some(object) = object {
+                    infer(param_type, &get_expression_type_from_object(object), &generic.type_params, &mut bindings);
+                }
+            }
+            let hints: Vec<Option<IntKind>> = deferred
+                .iter()
+                .map(|(index, _)| param_types.get(*index).and_then(|param_type| int_kind_of(&substitute(param_type, &bindings))))
+                .collect();
+            for ((index, arg), hint) in deferred.into_iter().zip(hints) {
+                objects[index] = Some(self.eval_expression_with_hint(arg, hint, env));
+            }
+        }
+
+        let mut llvm_args = vec![];
+        let mut arg_objects = vec![];
+        for mut object in objects.into_iter().flatten() {
+            match object {
+                Object::Error(err) => return Object::Error(err),
+                _ if object.is_variable() => {
+                    llvm_args.push(unwrap_object(&mut object));
+                    arg_objects.push(object);
+                }
+                _ => return Object::Error(format!("{}: {} cannot be passed as an argument", span, object)),
+            }
+        }
+        let function = match function {
+            Object::Generic(_, index) => {
+                let arg_types: Vec<LLVMExpressionType> = arg_objects.iter().map(get_expression_type_from_object).collect();
+                match self.instantiate(index, &arg_types, span) {
+                    Object::Function(function) => {
+                        param_types = function.param_types.clone();
+                        Object::Function(function)
+                    }
+                    object => return object,
+                }
+            }
+            function => function,
+        };
+
+        // A closure is called through its function pointer, with its
+        // environment as a hidden first argument.
+        let (callee, fn_type, mut call_args, return_type) = match function {
+            Object::Function(function) => {
+                (function.llvm_value, get_function_type(function.llvm_value), vec![], function.return_type)
+            }
+            Object::Closure(_, return_type, pair) => {
+                let fn_type = function_signature(vec![pointer_type()], &param_types, &return_type);
+                let pointer = self.build_field_pointer(closure_type(), pair, 0);
+                let pointer = build_load(self.lc.builder, pointer_type(), pointer, "");
+                let environment = self.build_field_pointer(closure_type(), pair, 1);
+                let environment = build_load(self.lc.builder, pointer_type(), environment, "");
+                let callee = cast_type(self.lc.builder, pointer, pointer_to(fn_type), "");
+                (callee, fn_type, vec![environment], return_type)
+            }
+            Object::BuildIn(BuildIn::Printf) => return self.eval_printf(llvm_args, arg_objects, span),
+            Object::BuildIn(BuildIn::Length) => return self.eval_length(arg_objects, span),
+            object => return Object::Error(format!("{}: {} is not a function", span, object)),
+        };
+
+        if param_types.len() != llvm_args.len() {
+            return Object::Error(format!(
+                "{}: expected {} arguments, found {}", span, param_types.len(), llvm_args.len()
+            ));
+        }
+        for (index, param_type) in param_types.iter().enumerate() {
+            let arg_type = get_expression_type_from_object(&arg_objects[index]);
+            if *param_type != arg_type {
+                return Object::Error(format!(
+                    "{}: argument {} should be {}, found {}", span, index + 1, param_type, arg_type
+                ));
+            }
+        }
+        if returns_in_slot(&return_type) {
+            let slot = self.build_entry_alloca(self.storage_type(&return_type), "");
+            let slot = cast_type(self.lc.builder, slot, pointer_type(), "");
+            call_args.insert(0, slot);
+            call_args.extend(llvm_args);
+            call_function(self.lc.builder, fn_type, callee, call_args, "");
+            return wrap_llvm_value(return_type, slot);
+        }
+        call_args.extend(llvm_args);
+        let value = call_function(self.lc.builder, fn_type, callee, call_args, "");
+        wrap_llvm_value(return_type, value)
+    }
+
+    // module_member looks up `module.name` when `object` names an imported
+    // file, and returns None for any other field access. A local variable
+    // hides a file of the same name.
+    fn module_member(&self, object: &Expr, name: &Ident, span: &Span, env: &Environment) -> Option<Object> {
+        let module = match &object.kind {
+            ExprKind::Ident(module) => env.get(&module.0),
+            _ => return None,
+        };
+        match module {
+            Object::Module(module, members) => Some(match members.get(&name.0) {
+                Some(member) => member.clone(),
+                None => Object::Error(format!("{}: module `{}` has no public function `{}`", span, module, name.0)),
+            }),
+            _ => None,
+        }
+    }
+
+    // The length of an array is part of its type; a map counts its entries.
+    fn eval_length(&mut self, arg_objects: Vec<Object>, span: &Span) -> Object {
+        match arg_objects.as_slice() {
+            [Object::Array(_, _, length)] => Object::Integer(IntKind::I32, const_int(int32_type(), *length as u64)),
+            [Object::Map(_, _, map)] => {
+                let length = self.call_map_runtime("flash_map_len", vec![*map]);
+                Object::Integer(IntKind::I32, build_trunc(self.lc.builder, length, int32_type(), ""))
+            }
+            [object] => Object::Error(format!("{}: length expects an array or a map, found {}", span, object)),
+            args => Object::Error(format!("{}: expected 1 arguments, found {}", span, args.len())),
+        }
+    }
+
+    // `contains(m, key)` and `remove(m, key)`. remove reports whether there
+    // was an entry to remove.
+    fn eval_map_builtin(&mut self, build_in: BuildIn, args: Vec<Expr>, span: &Span, env: &mut Environment) -> Object {
+        if args.len() != 2 {
+            return Object::Error(format!("{}: expected 2 arguments, found {}", span, args.len()));
+        }
+        let mut args = args.into_iter();
+        let (key_type, map) = match self.eval_expression(args.next().unwrap(), env) {
+            Object::Map(key_type, _, map) => (key_type, map),
+            Object::Error(err) => return Object::Error(err),
+            object => return Object::Error(format!("{}: {} expects a map, found {}", span, Object::BuildIn(build_in), object)),
+        };
+        let key = match self.eval_map_key(&key_type, args.next().unwrap(), env) {
+            Object::Integer(_, key) => key,
+            object => return object,
+        };
+
+        match build_in {
+            BuildIn::Remove => Object::Boolean(self.call_map_runtime("flash_map_remove", vec![map, key])),
+            _ => {
+                let value = self.call_map_runtime("flash_map_find", vec![map, key]);
+                Object::Boolean(build_is_not_null(self.lc.builder, value, ""))
+            }
+        }
+    }
+
+    // printf takes a format string followed by any number of values. Bools and
+    // narrow integers are widened to int, as C's variadic calls expect.
+    fn eval_printf(&mut self, mut llvm_args: Vec<*mut LLVMValue>, arg_objects: Vec<Object>, span: &Span) -> Object {
+        match arg_objects.first() {
+            Some(Object::String(_)) => {}
+            _ => return Object::Error(format!("{}: printf expects a format string", span)),
+        }
+        for (index, object) in arg_objects.iter().enumerate() {
+            match object {
+                Object::Boolean(value) => {
+                    llvm_args[index] = build_zext(self.lc.builder, *value, int32_type(), "");
+                }
+                Object::Integer(kind, value) if kind.bits() < 32 && kind.is_signed() => {
+                    llvm_args[index] = build_sext(self.lc.builder, *value, int32_type(), "");
+                }
+                Object::Integer(kind, value) if kind.bits() < 32 => {
+                    llvm_args[index] = build_zext(self.lc.builder, *value, int32_type(), "");
+                }
+                _ => {}
+            }
+        }
+
+        let printf = self.lc.built_ins["printf"];
+        let value = call_function(self.lc.builder, get_function_type(printf), printf, llvm_args, "");
+        Object::Integer(IntKind::I32, value)
+    }
+
+    pub fn set_value_to_identify(
+        &mut self,
+        llvm_value: *mut LLVMValue,
+        mut object: Object,
+        name: &str,
+        env: &mut Environment,
+    ) {
+        let llvm_type = match object {
+            Object::Array(_, _, _) | Object::Named(_, _) | Object::Tuple(_, _) | Object::Closure(_, _, _) => {
+                self.storage_type(&get_expression_type_from_object(&object))
+            }
+            _ => get_llvm_type_from_object(&mut object),
+        };
+        let mut llvm_value_ref = self.build_entry_alloca(llvm_type, name);
+        let value = rewrap_llvm_value_ref(object, llvm_value);
+        self.store_value(&value, llvm_value_ref);
+        if let Object::Array(_, _, _) | Object::Named(_, _) | Object::Tuple(_, _) | Object::Closure(_, _, _) = value {
+            llvm_value_ref = cast_type(self.lc.builder, llvm_value_ref, pointer_type(), "");
+        }
+        let rewraped_object = rewrap_llvm_value_ref(value, llvm_value_ref);
+        env.set(name.to_string(), rewraped_object);
+    }
+
+    // store_value writes a value into the stack slot `target`. Arrays and
+    // structs are copied element by element.
+    fn store_value(&mut self, object: &Object, target: *mut LLVMValue) {
+        match object {
+            Object::Array(_, source, _) | Object::Named(_, source) | Object::Tuple(_, source) | Object::Closure(_, _, source) => {
+                self.copy_value(&get_expression_type_from_object(object), *source, target);
+            }
+            object => {
+                build_store(self.lc.builder, unwrap_object(&mut object.clone()), target);
+            }
+        }
+    }
+
+    // copy_value copies a value of `value_type` from `source` to `target`,
+    // recursing into the elements of arrays and the fields of structs. An
+    // enum is copied as its tag and payload words, whichever variant it is.
+    fn copy_value(&mut self, value_type: &LLVMExpressionType, source: *mut LLVMValue, target: *mut LLVMValue) {
+        let storage_type = self.storage_type(value_type);
+        let source = cast_type(self.lc.builder, source, pointer_to(storage_type), "");
+        let target = cast_type(self.lc.builder, target, pointer_to(storage_type), "");
+        let parts: Vec<(LLVMExpressionType, *mut LLVMValue)> = match value_type {
+            LLVMExpressionType::Array(element_type, length) => (0..*length)
+                .map(|index| (*element_type.clone(), const_int(int64_type(), index as u64)))
+                .collect(),
+            LLVMExpressionType::Named(name) => match self.structs.get(name) {
+                Some(definition) => definition
+                    .fields
+                    .iter()
+                    .enumerate()
+                    .map(|(index, (_, field_type))| (field_type.clone(), const_int(int32_type(), index as u64)))
+                    .collect(),
+                None => {
+                    let payload_type = LLVMExpressionType::Array(
+                        Box::new(LLVMExpressionType::Integer(IntKind::I64)),
+                        self.enums[name].payload_words,
+                    );
+                    vec![
+                        (LLVMExpressionType::Integer(IntKind::I64), const_int(int32_type(), 0)),
+                        (payload_type, const_int(int32_type(), 1)),
+                    ]
+                }
+            },
+            LLVMExpressionType::Tuple(elements) => elements
+                .iter()
+                .enumerate()
+                .map(|(index, element)| (element.clone(), const_int(int32_type(), index as u64)))
+                .collect(),
+            // The function and environment pointers are copied like strings,
+            // which are pointers too.
+            LLVMExpressionType::Function(_, _) => vec![
+                (LLVMExpressionType::String, const_int(int32_type(), 0)),
+                (LLVMExpressionType::String, const_int(int32_type(), 1)),
+            ],
+            _ => {
+                let value = build_load(self.lc.builder, storage_type, source, "");
+                build_store(self.lc.builder, value, target);
+                return;
+            }
+        };
+
+        for (part_type, index) in parts.into_iter() {
+            let mut indices = [const_int(int32_type(), 0), index];
+            let from = build_in_bounds_gep(self.lc.builder, storage_type, source, &mut indices, "");
+            let to = build_in_bounds_gep(self.lc.builder, storage_type, target, &mut indices, "");
+            self.copy_value(&part_type, from, to);
+        }
+    }
+
+    // build_entry_alloca places stack slots at the top of the function's entry
+    // block so that they are allocated once, however often the code runs.
+    fn build_entry_alloca(&mut self, llvm_type: *mut LLVMType, name: &str) -> *mut LLVMValue {
+        let current_block = get_insert_block(self.lc.builder);
+        let entry_block = get_entry_basic_block(self.function_stack.last());
+        let first_instruction = get_first_instruction(entry_block);
+        if first_instruction.is_null() {
+            build_position_at_end(self.lc.builder, entry_block);
+        } else {
+            build_position_before(self.lc.builder, first_instruction);
+        }
+        let llvm_value_ref = build_alloca(self.lc.builder, llvm_type, name);
+        build_position_at_end(self.lc.builder, current_block);
+        llvm_value_ref
+    }
+
+    fn is_terminated(&self) -> bool {
+        !get_basic_block_terminator(get_insert_block(self.lc.builder)).is_null()
+    }
+}
+
+// A struct, an enum, a tuple or a closure is returned through a slot the
+// caller passes first.
+fn returns_in_slot(return_type: &LLVMExpressionType) -> bool {
+    matches!(return_type, LLVMExpressionType::Named(_) | LLVMExpressionType::Tuple(_) | LLVMExpressionType::Function(_, _))
+}
+
+// function_signature returns the LLVM type of a function taking `hidden`
+// parameters before its declared ones.
+fn function_signature(
+    mut hidden: Vec<*mut LLVMType>,
+    param_types: &[LLVMExpressionType],
+    return_type: &LLVMExpressionType,
+) -> *mut LLVMType {
+    if returns_in_slot(return_type) {
+        hidden.insert(0, pointer_type());
+    }
+    hidden.extend(param_types.iter().map(|param_type| convert_llvm_type(param_type.clone())));
+    let llvm_return_type = if returns_in_slot(return_type) {
+        void_type()
+    } else {
+        convert_llvm_type(return_type.clone())
+    };
+    function_type(llvm_return_type, &mut hidden)
+}
+
+// A closure is a function pointer followed by an environment pointer.
+fn closure_type() -> *mut LLVMType {
+    struct_type(&mut [pointer_type(), pointer_type()])
+}
+
+fn int_kind_of(expression_type: &LLVMExpressionType) -> Option<IntKind> {
+    match expression_type {
+        LLVMExpressionType::Integer(kind) => Some(*kind),
+        LLVMExpressionType::Array(element_type, _) => int_kind_of(element_type),
+        _ => None,
+    }
+}
+
+fn int_kind_of_object(object: &Object) -> Option<IntKind> {
+    match object {
+        Object::Integer(kind, _) => Some(*kind),
+        _ => None,
+    }
+}
+
+fn is_unsuffixed_int_literal(expr: &Expr) -> bool {
+    match &expr.kind {
+        ExprKind::Literal(Literal::Int(_, None)) => true,
+        ExprKind::Prefix(Prefix::Minus, right) => is_unsuffixed_int_literal(right),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::test_util::*;
+    use crate::lexer::new_lexer;
+    use crate::parser::Parser;
+
+    fn compile(input: &str) -> (Eval, Object) {
+        let mut parser = Parser::new(new_lexer(input));
+        let program = parser.parse().unwrap().remove(0);
+        let mut eval = Eval::new();
+        let result = eval.entry_eval_program(program, &mut Environment::new());
+        (eval, result)
+    }
+
+    fn run(input: &str) -> u64 {
+        let (eval, result) = compile(input);
+        if let Object::Error(err) = result {
+            panic!("compile failed: {}", err);
+        }
+        execute_test_ir_function(eval.lc.module, eval.function_stack.main_func)
+    }
+
+    fn compile_files(files: &[(&str, &str)]) -> (Eval, Object) {
+        let modules = crate::loader::load_files(files).unwrap();
+        let mut eval = Eval::new();
+        let result = eval.entry_eval_modules(modules);
+        (eval, result)
+    }
+
+    // assert_compile_errors checks that each input fails to compile with
+    // the error given next to it.
+    fn assert_compile_errors(tests: &[(&str, &str)]) {
+        assert_errors_with(tests, |input| compile(input).1);
+    }
+
+    // assert_errors_with is assert_compile_errors for inputs that need more
+    // than `compile`, such as shared declarations or other files.
+    fn assert_errors_with(tests: &[(&str, &str)], compile: impl Fn(&str) -> Object) {
+        for (input, expected) in tests {
+            match compile(input) {
+                Object::Error(err) => assert_eq!(err, *expected, "{}", input),
+                object => panic!("expected an error for {}, got {:?}", input, object),
+            }
+        }
+    }
+
+    #[test]
+    fn test_functions() {
+        let input = "
+        let x: int = pick(true, -1, 2);
+        x = pick(false, x, 42);
+        return x;
+
+        func pick(first: bool, a: int, b: int): int {
+            if (first) {
+                return a;
+            } else {
+                return b;
+            }
+        }";
+        assert_eq!(run(input), 42);
+    }
+
+    #[test]
+    fn test_strings() {
+        let input = r#"
+        let label: string = "total:\t";
+        return width(label, "x");
+
+        func width(label: string, unit: string): int {
+            printf("%s%d%s\n", label, 10, unit);
+            return 10;
+        }"#;
+        let (eval, result) = compile(input);
+        assert!(!matches!(result, Object::Error(_)), "{:?}", result);
+        let ir = eval.lc.print_to_string();
+        assert!(ir.contains(r#"c"total:\09\00""#), "{}", ir);
+        assert!(ir.contains(r#"c"%s%d%s\0A\00""#), "{}", ir);
+        assert!(ir.contains("@printf"), "{}", ir);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_compile_errors(&[("let s: string = 1;", "<input>:1:1: `s` is declared as string but initialised with int")]);
+
+        assert_compile_errors(&[("func f(): int {\n  return missing;\n}", "<input>:2:10: `missing` is not found")]);
+    }
+
+    #[test]
+    fn test_operators() {
+        let tests = vec![
+            ("return 2 + 3 * 4 - 10 / 3;", 11),
+            ("return -7 / 2 * 10 + -7 % 2;", (-31i32) as u32 as u64),
+            ("return (1 << 4 | 3) ^ 1 & 7;", 18),
+            ("return -16 >> 2;", (-4i32) as u32 as u64),
+            ("let x: int = 10; x += 5; x *= 2; x -= 1; x %= 7; return x;", 1),
+            ("if (-1 < 1 && 2 >= 2 && !(3 <= 2) || false) { return 1; } return 0;", 1),
+            ("if (true != false == true) { return 1; } return 0;", 1),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_sized_integers() {
+        let tests = vec![
+            ("let x: i64 = 3000000000; if (x / 1000000000i64 == 3) { return 1; } return 0;", 1),
+            ("let x: u8 = 255; let y: u8 = x + 1; if (y == 0u8) { return 1; } return 0;", 1),
+            ("let x: i8 = -128; if (x < 0) { return 1; } return 0;", 1),
+            ("let x: u32 = 0xffff_fff0; if (x > 16u32 && x >> 4 == 0x0fff_ffff) { return 1; } return 0;", 1),
+            ("let x: u8 = 200; let y: u8 = 7; if (x / y == 28 && x % y == 4) { return 1; } return 0;", 1),
+            ("return 0b1010 | 0o5 << 4;", 0b1010 | 0o5 << 4),
+            ("if (wide(7) == 7000000000) { return 1; } return 0;\nfunc wide(x: i64): i64 { return x * 1000000000; }", 1),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_integer_literal_diagnostics() {
+        let tests = vec![
+            ("let x: int = 3000000000;", "<input>:1:14: literal `3000000000` is out of range for int"),
+            ("let x: i8 = -129;", "<input>:1:13: literal `-129` is out of range for i8"),
+            ("let x: u8 = 256u8;", "<input>:1:13: literal `256` is out of range for u8"),
+            ("let x: u64 = -1;", "<input>:1:14: literal `-1` is out of range for u64"),
+            ("let x: i64 = 1; let y: int = 2; return x + y;", "<input>:1:40: unsupported operands for `+`: i64 and int"),
+        ];
+
+        assert_compile_errors(&tests);
+    }
+
+    #[test]
+    fn test_floats() {
+        let tests = vec![
+            ("let x: float = 1.5; let y: float = x * 2.0 - 0.5; if (y == 2.5) { return 1; } return 0;", 1),
+            ("let x: float = 1e-9; if (x > 0.0 && -x < 0.0 && x <= 1e-9) { return 1; } return 0;", 1),
+            ("if (7.5 % 2.0 == 1.5 && 1.0 / 4.0 >= 0.25) { return 1; } return 0;", 1),
+            ("if (half(3.0) != 1.5) { return 0; } return 1;\nfunc half(x: float): float { return x / 2.0; }", 1),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), expected, "{}", input);
+        }
+
+        assert_compile_errors(&[("let x: float = 1.5; return x + 1;", "<input>:1:28: unsupported operands for `+`: float and int")]);
+    }
+
+    #[test]
+    fn test_chars_and_casts() {
+        let tests = vec![
+            ("let c: char = 'a'; return c as int;", 97),
+            ("let c: char = '\\u{1F600}'; return c as int;", 0x1F600),
+            ("let c: char = '\\u{1F600}'; let b: u8 = c as u8; return b as int;", 0x00),
+            ("let b: u8 = 200; let c: char = b as char; return c as int;", 200),
+            ("let c: char = 65 as char; if (c == 'A' && c < 'a') { return 1; } return 0;", 1),
+            ("let x: int = -1; return (x as u8) as int;", 255),
+            ("let x: i8 = -3; let y: i64 = x as i64; if (y == -3) { return 1; } return 0;", 1),
+            ("let f: float = 7.9; return f as int + (-2.5 as int);", 5),
+            ("let x: int = 3; let f: float = x as float / 2.0; if (f == 1.5) { return 1; } return 0;", 1),
+            ("return (true as int) + (false as int);", 1),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), expected, "{}", input);
+        }
+
+        let errors = vec![
+            ("let x: int = 65; let c: char = x as char;", "<input>:1:32: only u8 can be cast to char, not int"),
+            ("let c: char = 'a' + 'b';", "<input>:1:15: `+` is not defined for char"),
+            ("let s: string = \"a\"; let x: int = s as int;", "<input>:1:35: cannot cast string to int"),
+        ];
+        assert_compile_errors(&errors);
+    }
+
+    #[test]
+    fn test_loops() {
+        let tests = vec![
+            ("let i: int = 0; while i < 10 { i += 1; } return i;", 10),
+            ("let sum: int = 0; for let i: int = 1; i <= 4; i += 1 { sum += i; } return sum;", 10),
+            ("let sum: int = 0; let i: int = 0; for ; i < 3; { i += 1; sum += i; } return sum;", 6),
+            ("let i: int = 0; for ;; { i += 1; if (i == 7) { break; } } return i;", 7),
+            ("let sum: int = 0; for let i: int = 0; i < 10; i += 1 { if (i % 2 == 0) { continue; } sum += i; } return sum;", 25),
+            ("let n: int = 0; while false { n = 1; } return n;", 0),
+            (
+                "let count: int = 0;
+                outer: for let i: int = 0; i < 5; i += 1 {
+                    for let j: int = 0; j < 5; j += 1 {
+                        if (j == 3) { continue outer; }
+                        if (i == 3) { break outer; }
+                        count += 1;
+                    }
+                }
+                return count;",
+                9,
+            ),
+            ("func find(n: int): int { let i: int = 0; for ;; i += 1 { if (i * i >= n) { return i; } } } return find(50);", 8),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), expected, "{}", input);
+        }
+
+        let errors = vec![
+            ("break;", "<input>:1:1: `break` outside of a loop"),
+            ("while true { continue inner; }", "<input>:1:14: no loop labelled `inner`"),
+            ("while 1 { }", "<input>:1:7: condition must be a bool"),
+            ("for let i: int = 0; i < 3; i += 1 { } return i;", "<input>:1:46: `i` is not found"),
+        ];
+        assert_compile_errors(&errors);
+    }
+
+    #[test]
+    fn test_if_expressions() {
+        let tests = vec![
+            ("let c: bool = true; let x: int = if (c) { 1 } else { 2 }; return x;", 1),
+            ("let c: bool = false; let x: int = if (c) { 1 } else { 2 }; return x;", 2),
+            ("let n: int = 7; return if (n < 0) { 1 } else if (n < 5) { 2 } else if (n < 10) { 3 } else { 4 };", 3),
+            ("let n: int = -4; let x: int = if (n < 0) { let m: int = -n; m * 10 } else { n }; return x;", 40),
+            ("let x: u8 = if (true) { 200 } else { 1 }; return x as int;", 200),
+            ("let big: i64 = 5; let y: i64 = if (false) { big } else { 9 }; return y as int;", 9),
+            ("func pick(c: bool): int { let x: int = if (c) { 3 } else { return 4; }; return x * 2; } return pick(true) + pick(false);", 10),
+            ("let f: float = if (1 > 2) { 1.5 } else { 2.5 }; return (f * 2.0) as int;", 5),
+            ("let n: int = 0; if (n == 0) { n = 5; } else if (n == 1) { n = 6; } return n;", 5),
+            ("let s: string = if (true) { \"a\" } else { \"b\" }; printf(s); return 0;", 0),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), expected, "{}", input);
+        }
+
+        let errors = vec![
+            ("let x: int = if (true) { 1 } else { 2.0 };", "<input>:1:14: `if` arms have different types: int and float"),
+            ("let x: int = if (true) { 1 } else { let y: int = 2; };", "<input>:1:14: `if` arms have different types: int and no value"),
+            ("let x: int = if (true) { 1 };", "<input>:1:14: `if` without `else` has no value"),
+            ("let x: int = if (1) { 1 } else { 2 };", "<input>:1:18: condition must be a bool"),
+        ];
+        assert_compile_errors(&errors);
+    }
+
+    #[test]
+    fn test_short_circuit() {
+        let tests = vec![
+            // The division would trap if the guard did not short-circuit.
+            ("let x: int = 0; if (x != 0 && 10 / x > 1) { return 1; } return 2;", 2),
+            ("let x: int = 5; if (x != 0 && 10 / x > 1) { return 1; } return 2;", 1),
+            ("let x: int = 0; if (x == 0 || 10 / x > 1) { return 1; } return 2;", 1),
+            ("let x: int = 20; if (x == 0 || 10 / x > 1) { return 1; } return 2;", 2),
+            ("let a: int = 1; let b: int = 2; if (a == 1 && b == 2 || a == 3) { return 1; } return 0;", 1),
+            ("let a: int = 3; if (a == 1 && a == 2 || a == 3) { return 1; } return 0;", 1),
+            ("let a: int = 1; if (a == 1 || a == 2 && a == 3) { return 1; } return 0;", 1),
+            ("let t: bool = true; let f: bool = false; if (!(t && f) && (f || t) && (t && (f || (t && t)))) { return 1; } return 0;", 1),
+            ("func check(x: int): bool { return x > 0 && 100 % x == 0; } if (check(0) || check(7)) { return 1; } if (check(5)) { return 2; } return 3;", 2),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), expected, "{}", input);
+        }
+
+        assert_compile_errors(&[("let x: int = 1; if (x && true) { }", "<input>:1:21: `&&` expects bool operands, not int")]);
+    }
+
+    #[test]
+    fn test_arrays() {
+        let tests = vec![
+            ("let a: [int; 4] = [1, 2, 3, 4]; return a[0] + a[3];", 5),
+            ("let a: [int; 3] = [1, 2, 3]; let i: int = 2; return a[i] * 10 + length(a);", 33),
+            ("let a: [int; 3] = [0, 0, 0]; for let i: int = 0; i < length(a); i += 1 { a[i] = i * i; } a[1] += 5; return a[1] + a[2];", 10),
+            ("let a: [int; 2] = [1, 2]; let b: [int; 2] = a; b[0] = 9; return a[0] * 10 + b[0];", 19),
+            ("let a: [int; 2] = [1, 2]; let b: [int; 2] = [3, 4]; a = b; b[1] = 0; return a[1];", 4),
+            ("let m: [[int; 2]; 2] = [[1, 2], [3, 4]]; m[1][0] = 7; return m[1][0] * 10 + m[0][1];", 72),
+            ("let m: [[int; 2]; 2] = [[1, 2], [3, 4]]; let row: [int; 2] = m[1]; row[0] = 0; return m[1][0] + length(m[0]);", 5),
+            ("func sum(xs: [int; 3]): int { let total: int = 0; for let i: int = 0; i < 3; i += 1 { total += xs[i]; } xs[0] = 100; return total; } let a: [int; 3] = [4, 5, 6]; return sum(a) + a[0];", 19),
+            ("let a: [u8; 2] = [200, 100]; let i: u8 = 1; return (a[0] + a[i] - 50) as int;", 250),
+            ("let cs: [char; 2] = ['h', 'i']; return cs[1] as int;", 105),
+            ("let c: bool = false; let a: [int; 2] = if (c) { [1, 2] } else { [3, 4] }; return a[1];", 4),
+            ("return [10, 20, 30][1];", 20),
+            // The index of a compound assignment is evaluated once.
+            ("func next(c: map[int]int): int { c[0] += 1; return c[0]; } let c: map[int]int = map[int]int{0: 0}; let xs: [int; 3] = [0, 0, 0]; xs[next(c)] += 5; return xs[1] * 10 + xs[2] + c[0];", 51),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), expected, "{}", input);
+        }
+
+        let errors = vec![
+            ("let a: [int; 2] = [1, 2.0];", "<input>:1:23: array elements have different types: int and float"),
+            ("let a: [int; 2] = [1, 2, 3];", "<input>:1:1: `a` is declared as [int; 2] but initialised with [int; 3]"),
+            ("let a: [int; 2] = [1, 2]; return a[2];", "<input>:1:36: index 2 is out of bounds for [int; 2]"),
+            ("let a: [int; 2] = [1, 2]; return a[true];", "<input>:1:36: array index must be an integer, not bool"),
+            ("let x: int = 1; return x[0];", "<input>:1:24: cannot index into int"),
+            ("let a: [int; 2] = [1, 2]; a[0] = 1.5;", "<input>:1:27: cannot assign float to an element of type int"),
+            ("let x: int = length(3);", "<input>:1:14: length expects an array or a map, found int"),
+            ("let a: [int; 1] = [];", "<input>:1:19: cannot infer the element type of an empty array"),
+            ("func f(): [int; 2] { return [1, 2]; }", "<input>:1:1: function `f` cannot return an array"),
+        ];
+        assert_compile_errors(&errors);
+
+        // Out-of-bounds accesses at run time print the location and abort.
+        let (eval, result) = compile("let a: [int; 2] = [1, 2];\nlet i: int = 5;\nreturn a[i];");
+        assert!(!matches!(result, Object::Error(_)));
+        let ir = eval.lc.print_to_string();
+        assert!(ir.contains("<input>:3:8: index out of bounds: the length is 2 but the index is %lld"), "{}", ir);
+        assert!(ir.contains("call void @abort()"), "{}", ir);
+    }
+
+    #[test]
+    fn test_maps() {
+        let tests = vec![
+            ("let m: map[string]int = map[string]int{\"a\": 1, \"b\": 2}; return m[\"b\"];", 2),
+            ("let m: map[string]int = map[string]int{\"a\": 1}; m[\"a\"] += 4; m[\"c\"] = 3; return m[\"a\"] * 10 + length(m);", 52),
+            ("let m: map[int]int = map[int]int{}; for let i: int = 0; i < 50; i += 1 { m[i * i] = i; } return m[49 * 49] + length(m);", 99),
+            ("let m: map[char]bool = map[char]bool{'a': true}; let r: bool = remove(m, 'a'); return if (r && !contains(m, 'a') && !remove(m, 'a')) { length(m) + 1 } else { 0 };", 1),
+            ("let m: map[u8]int = map[u8]int{1: 10, 1: 20}; let k: u8 = 1; return m[k] + length(m);", 21),
+            ("let m: map[int][int; 2] = map[int][int; 2]{7: [1, 2]}; m[7][1] = 5; let row: [int; 2] = m[7]; row[0] = 9; return m[7][0] + row[1];", 6),
+            ("func add(m: map[string]int, key: string): int { m[key] = 1; return length(m); } let m: map[string]int = map[string]int{}; let shared: map[string]int = m; add(shared, \"x\"); return add(m, \"y\") + m[\"x\"];", 3),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), expected, "{}", input);
+        }
+
+        let errors = vec![
+            ("let m: map[string]int = map[string]int{1: 2};", "<input>:1:40: map key should be string, found int"),
+            ("let m: map[string]int = map[string]int{\"a\": 'b'};", "<input>:1:45: map value should be int, found char"),
+            ("let m: map[float]int = map[float]int{};", "<input>:1:24: float cannot be used as a map key"),
+            ("let m: map[int]int = map[int]int{}; m[1] = true;", "<input>:1:37: cannot assign bool to an element of type int"),
+            ("let m: map[int]int = map[int]int{}; return contains(m, \"a\");", "<input>:1:56: map key should be int, found string"),
+            ("let a: [int; 1] = [1]; remove(a, 0);", "<input>:1:24: remove expects a map, found [int; 1]"),
+            ("let m: map[int]int = map[int]int{}; contains(m);", "<input>:1:37: expected 2 arguments, found 1"),
+        ];
+        assert_compile_errors(&errors);
+
+        // Looking up a missing key prints the location and aborts.
+        let (eval, result) = compile("let m: map[int]int = map[int]int{};\nreturn m[3];");
+        assert!(!matches!(result, Object::Error(_)));
+        let ir = eval.lc.print_to_string();
+        assert!(ir.contains("<input>:2:8: key not found in map"), "{}", ir);
+    }
+
+    #[test]
+    fn test_structs() {
+        let point = "struct Point { x: int, y: int }\n";
+        let tests = vec![
+            ("let p: Point = Point { x: 1, y: 2 }; return p.x * 10 + p.y;", 12),
+            ("let p: Point = Point { y: 2, x: 1 }; p.x = 3; p.y += 4; return p.x * 10 + p.y;", 36),
+            ("struct Line { from: Point, to: Point } let l: Line = Line { from: Point { x: 1, y: 2 }, to: Point { x: 3, y: 4 } }; l.to.y = 7; return l.from.x + l.to.y;", 8),
+            ("let ps: [Point; 2] = [Point { x: 1, y: 2 }, Point { x: 3, y: 4 }]; ps[1].x = 5; return ps[0].y + ps[1].x;", 7),
+            ("let p: Point = Point { x: 1, y: 2 }; let q: Point = p; q.x = 9; return p.x;", 1),
+            ("func bump(p: Point): int { p.x += 1; return p.x; } let p: Point = Point { x: 1, y: 2 }; return bump(p) * 10 + p.x;", 21),
+            ("func swap(p: Point): Point { return Point { x: p.y, y: p.x }; } let p: Point = swap(Point { x: 1, y: 2 }); return p.x * 10 + swap(p).x;", 21),
+            ("func origin(): Point { let o: Point = Point { x: 0, y: 0 }; return o; } let p: Point = origin(); p.x = 4; return origin().x + p.x;", 4),
+            ("let m: map[int]Point = map[int]Point{1: Point { x: 1, y: 2 }}; m[1].y = 5; return m[1].x + m[1].y;", 6),
+            ("func next(c: map[int]int): int { c[0] += 1; return c[0]; } let c: map[int]int = map[int]int{0: 0}; let ps: [Point; 3] = [Point { x: 0, y: 0 }, Point { x: 0, y: 0 }, Point { x: 0, y: 0 }]; ps[next(c)].x += 5; return ps[1].x * 10 + ps[2].x + c[0];", 51),
+            ("struct Pair { a: Later, n: i64 } struct Later { v: [int; 2] } let p: Pair = Pair { a: Later { v: [3, 4] }, n: 1 }; return p.a.v[1];", 4),
+        ];
+
+        for (input, expected) in tests {
+            let input = format!("{}{}", point, input);
+            assert_eq!(run(&input), expected, "{}", input);
+        }
+
+        let errors = vec![
+            ("let p: Point = Point { x: 1 };", "<input>:2:16: missing field `y` in `Point`"),
+            ("let p: Point = Point { x: 1, y: 2, z: 3 };", "<input>:2:39: `Point` has no field `z`"),
+            ("let p: Point = Point { x: 1, x: 2, y: 3 };", "<input>:2:33: field `x` is given twice"),
+            ("let p: Point = Point { x: 1, y: true };", "<input>:2:33: field `y` of `Point` should be int, found bool"),
+            ("let p: Point = Point { x: 1, y: 2 }; p.z = 1;", "<input>:2:38: `Point` has no field `z`"),
+            ("let p: Point = Point { x: 1, y: 2 }; p.x = 'a';", "<input>:2:38: cannot assign char to field `x` of type int"),
+            ("let a: int = 1; return a.x;", "<input>:2:24: int has no fields"),
+            ("let p: Point = Pointer { x: 1 };", "<input>:2:16: unknown struct `Pointer`"),
+            ("let q: Pointer = 1;", "<input>:2:1: unknown type `Pointer`"),
+            ("struct Point { z: int }", "<input>:2:1: struct `Point` is already declared"),
+            ("struct Node { next: Node }", "<input>:2:1: struct `Node` contains itself"),
+            ("struct A { b: [B; 2] } struct B { a: A }", "<input>:2:1: struct `A` contains itself"),
+            ("struct A { x: int, x: int }", "<input>:2:1: field `x` is declared twice in `A`"),
+            ("func f(p: Pointer): int { return 1; }", "<input>:2:1: unknown type `Pointer`"),
+        ];
+        assert_errors_with(&errors, |input| compile(&format!("{}{}", point, input)).1);
+    }
+
+    #[test]
+    fn test_enums() {
+        let shape = "enum Shape { Circle(int), Rect(int, int), Empty }\n";
+        let tests = vec![
+            ("let s: Shape = Shape::Rect(3, 4); return match s { Shape::Circle(r) => r * r, Shape::Rect(w, h) => w * h, Shape::Empty => 0 };", 12),
+            ("func area(s: Shape): int { return match s { Shape::Circle(r) => 3 * r * r, Shape::Rect(w, h) => w * h, _ => 0 }; } return area(Shape::Circle(2)) + area(Shape::Empty) * 100;", 12),
+            ("func area(s: Shape): int { return match s { Shape::Rect(w, h) if w == h => 1, Shape::Rect(_, h) => h, _ => 7 }; } return area(Shape::Rect(2, 2)) * 100 + area(Shape::Rect(1, 5)) * 10 + area(Shape::Circle(9));", 157),
+            ("let total: int = 0; for let i: int = 0; i < 5; i += 1 { total += match i { 0 => 10, 1 => 0, 2 => 0, n if n > 3 => n, _ => 1 }; } return total;", 15),
+            ("func unit(n: int): Shape { if (n == 0) { return Shape::Empty; } return Shape::Rect(n, n + 1); } let a: Shape = unit(2); let b: Shape = a; b = unit(0); return match a { Shape::Rect(w, h) => w * 10 + h, _ => 0 } + match b { Shape::Empty => 100, _ => 0 };", 123),
+            ("let c: char = 'b'; return match c { 'a' => 1, 'b' => 2, _ => 3 };", 2),
+            ("let b: bool = false; return match b { true => 1, false => 2 };", 2),
+            ("let n: i64 = -3; return match n { -3 => 1, _ => 2 };", 1),
+            ("enum Tree { Leaf(int), Pair(Shape, Shape) } let t: Tree = Tree::Pair(Shape::Circle(4), Shape::Empty); return match t { Tree::Pair(Shape::Circle(r), Shape::Empty) => r, Tree::Pair(_, _) => 1, Tree::Leaf(n) => n };", 4),
+            ("enum Holder { Points([int; 3]), Name(string) } let h: Holder = Holder::Points([1, 2, 3]); let h2: Holder = h; return match h2 { Holder::Points(p) => p[0] + p[2], Holder::Name(_) => 0 };", 4),
+            ("struct Point { x: int, y: int } enum Move { To(Point), Stop } let m: Move = Move::To(Point { x: 3, y: 5 }); match m { Move::To(p) => { return p.y; }, Move::Stop => {} } return 0;", 5),
+        ];
+
+        for (input, expected) in tests {
+            let input = format!("{}{}", shape, input);
+            assert_eq!(run(&input), expected, "{}", input);
+        }
+
+        let errors = vec![
+            ("let s: Shape = Shape::Empty; return match s { Shape::Circle(r) => r, Shape::Empty => 0 };", "<input>:2:37: match is not exhaustive: `Shape::Rect(_, _)` is not covered"),
+            ("let s: Shape = Shape::Empty; return match s { Shape::Rect(1, h) => h, Shape::Circle(_) => 0, Shape::Empty => 0 };", "<input>:2:37: match is not exhaustive: `Shape::Rect(_, _)` is not covered"),
+            ("let s: Shape = Shape::Empty; return match s { Shape::Circle(r) if r > 1 => r, Shape::Rect(_, _) => 0, Shape::Empty => 0 };", "<input>:2:37: match is not exhaustive: `Shape::Circle(_)` is not covered"),
+            ("let n: int = 1; return match n { 1 => 1, 2 => 2 };", "<input>:2:24: match is not exhaustive: `_` is not covered"),
+            ("let b: bool = true; return match b { true => 1 };", "<input>:2:28: match is not exhaustive: `false` is not covered"),
+            ("let s: Shape = Shape::Square(1);", "<input>:2:16: `Shape` has no variant `Square`"),
+            ("let s: Shape = Shape::Circle(1, 2);", "<input>:2:16: `Shape::Circle` takes 1 values, found 2"),
+            ("let s: Shape = Shape::Circle('a');", "<input>:2:30: value 1 of `Shape::Circle` should be int, found char"),
+            ("let s: Shape = Sha::Circle(1);", "<input>:2:16: unknown enum `Sha`"),
+            ("let s: Shape = Shape::Empty; return match s { Shape::Circle(a, b) => 0, _ => 1 };", "<input>:2:37: `Shape::Circle` has 1 values, found 2"),
+            ("let s: Shape = Shape::Empty; return match s { Shape::Rect(a, a) => 0, _ => 1 };", "<input>:2:37: `a` is bound twice in the same pattern"),
+            ("let n: int = 1; return match n { 'a' => 0, _ => 1 };", "<input>:2:34: expected a pattern for int, found char"),
+            ("let n: int = 1; return match n { Shape::Empty => 0, _ => 1 };", "<input>:2:24: expected a pattern for int, found `Shape::Empty`"),
+            ("let f: float = 1.0; return match f { _ => 1 };", "<input>:2:34: cannot match on float"),
+            ("let s: Shape = Shape::Empty; return match s { Shape::Empty => 1, _ => 'a' };", "<input>:2:37: match arms have different types: int and char"),
+            ("let s: Shape = Shape::Empty; return match s { Shape::Empty => 1, _ => { let y: int = 2; } };", "<input>:2:37: match arms have different types: int and no value"),
+            ("enum Shape { A }", "<input>:2:1: enum `Shape` is already declared"),
+            ("enum E { A, A }", "<input>:2:1: variant `A` is declared twice in `E`"),
+            ("enum E { A(E) }", "<input>:2:1: enum `E` contains itself"),
+            ("enum E { A(void) }", "<input>:2:1: variant `A` cannot hold void"),
+        ];
+        assert_errors_with(&errors, |input| compile(&format!("{}{}", shape, input)).1);
+    }
+
+    #[test]
+    fn test_closures() {
+        let tests = vec![
+            ("func add(a: int, b: int): int { return a + b; } let f: func(int, int): int = add; return f(2, 3);", 5),
+            ("func apply(f: func(int): int, x: int): int { return f(x); } func double(x: int): int { return x * 2; } return apply(double, 4) + apply(func(x: int): int { return x + 1; }, 4);", 13),
+            ("let k: int = 10; let f: func(int): int = func(x: int): int { return x + k; }; k = 100; return f(1);", 11),
+            ("let counts: map[string]int = map[string]int{\"n\": 0}; let count: func(): int = func(): int { counts[\"n\"] += 1; return counts[\"n\"]; }; count(); count(); return count() * 10 + counts[\"n\"];", 33),
+            ("let total: int = 5; let f: func(int): int = func(x: int): int { let total: int = x; total += 1; return total; }; return f(1) * 10 + total;", 25),
+            ("func adder(k: int): func(int): int { return func(x: int): int { return x + k; }; } let add2: func(int): int = adder(2); return add2(1) * 10 + adder(5)(1);", 36),
+            ("func compose(f: func(int): int, g: func(int): int): func(int): int { return func(x: int): int { return g(f(x)); }; } let inc: func(int): int = func(x: int): int { return x + 1; }; return compose(inc, func(x: int): int { return x * 3; })(1);", 6),
+            ("struct Point { x: int, y: int } let p: Point = Point { x: 1, y: 2 }; let f: func(): Point = func(): Point { return Point { x: p.y, y: p.x }; }; p.x = 7; return f().x * 10 + f().y;", 21),
+            ("let fs: [func(int): int; 2] = [func(x: int): int { return x + 1; }, func(x: int): int { return x * 2; }]; let g: func(int): int = fs[1]; fs[1] = fs[0]; return fs[1](5) * 100 + g(5);", 610),
+            ("let m: map[string]func(int): int = map[string]func(int): int{\"neg\": func(x: int): int { return -x; }}; return m[\"neg\"](-3);", 3),
+            ("let xs: [int; 3] = [1, 2, 3]; let sum: func(): int = func(): int { let s: int = 0; for let i: int = 0; i < 3; i += 1 { s += xs[i]; } return s; }; xs[0] = 10; return sum();", 6),
+            ("let a: int = 1; let outer: func(): func(): int = func(): func(): int { return func(): int { return a + 1; }; }; return outer()();", 2),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), expected, "{}", input);
+        }
+
+        let errors = vec![
+            ("let f: func(int): int = func(x: int): int { return x; }; return f(true);", "<input>:1:65: argument 1 should be int, found bool"),
+            ("let f: func(int): int = func(x: int): int { return x; }; return f(1, 2);", "<input>:1:65: expected 1 arguments, found 2"),
+            ("let f: func(int): int = func(x: int): bool { return x > 0; };", "<input>:1:1: `f` is declared as func(int): int but initialised with func(int): bool"),
+            ("let f: func(): int = func(): int { let y: int = 1; };", "<input>:1:22: closure is missing a return"),
+            ("let x: int = 1; return x(2);", "<input>:1:24: int is not a function"),
+            ("while true { let f: func(): void = func(): void { break; }; }", "<input>:1:51: `break` outside of a loop"),
+            ("let f: func(): [int; 2] = func(): [int; 2] { return [1, 2]; };", "<input>:1:27: closure cannot return an array"),
+            ("let f: func(Pointer): int = func(p: Pointer): int { return 1; };", "<input>:1:1: unknown type `Pointer`"),
+            ("let total: int = 0; let add: func(int): void = func(x: int): void { total += x; };", "<input>:1:69: cannot assign to captured variable `total`"),
+            ("let xs: [int; 2] = [1, 2]; let f: func(): void = func(): void { xs[0] = 3; };", "<input>:1:65: cannot assign to captured variable `xs`"),
+            ("struct P { x: int, pair: (int, int) } let p: P = P { x: 1, pair: (2, 3) }; let f: func(): void = func(): void { p.pair.0 += 1; };", "<input>:1:113: cannot assign to captured variable `p`"),
+        ];
+        assert_compile_errors(&errors);
+    }
+
+    #[test]
+    fn test_let_inference() {
+        let tests = vec![
+            ("let a = 2; let b = a * 3; return b;", 6),
+            ("let x = 3u8; let y = x + 250; return y as int;", 253),
+            ("let xs = [1i64, 2, 3]; let n: i64 = xs[2]; return n as int;", 3),
+            ("struct Point { x: int, y: int } let p = Point { x: 1, y: 2 }; let q = p; q.x = 5; return p.x + q.x;", 6),
+            ("let m = map[string]int{\"a\": 1}; return m[\"a\"];", 1),
+            ("let f = func(x: int): int { return x * 2; }; let g = f; return g(4);", 8),
+            ("let b = 1 < 2; return if (b) { 1 } else { 0 };", 1),
+            ("let t = 0; for let i = 0; i < 4; i += 1 { t += i; } return t;", 6),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), expected, "{}", input);
+        }
+
+        let errors = vec![
+            ("func f(): void {} let x = f();", "<input>:1:19: cannot infer the type of `x`: its initializer has no value"),
+            ("let a = [];", "<input>:1:9: cannot infer the element type of an empty array"),
+            ("let a = 1; let b: u8 = a;", "<input>:1:12: `b` is declared as u8 but initialised with int"),
+            ("let p = printf;", "<input>:1:1: `p` cannot hold a value of this kind"),
+        ];
+        assert_compile_errors(&errors);
+    }
+
+    #[test]
+    fn test_modules() {
+        let math = "func mul(a: int, b: int): int { return a * b; }\npub func square(x: int): int { return mul(x, x); }\npub func twice<T>(f: func(T): T, x: T): T { return f(f(x)); }\nstruct Pair { a: int, b: int }";
+        let shapes = "import \"../math.flash\";\nstruct Rect { w: int, h: int }\npub func rect(w: int, h: int): Rect { return Rect { w: w, h: h }; }\npub func area(w: int, h: int): int { let r = rect(w, h); return r.w * r.h + math.square(0); }\n\
+            enum Shape { Square(int), Empty }\npub func square(n: int): Shape { let p = math.Pair { a: n, b: 0 }; return Shape::Square(p.a); }";
+        let tests = vec![
+            ("import math;\nimport \"geo/shapes.flash\";\nreturn math.square(3) + shapes.area(2, 5);", 19),
+            ("import math;\nfunc square(x: int): int { return x; }\nreturn math.square(4) + square(4);", 20),
+            ("import math;\nlet f: func(int): int = math.square;\nreturn f(5);", 25),
+            ("import math;\nreturn math.twice(math.square, 3);", 81),
+            ("import \"geo/shapes.flash\";\nlet r = shapes.rect(3, 4);\nreturn r.w + r.h;", 7),
+            // Each file has its own types; the ones of an import are qualified with its name.
+            ("import \"geo/shapes.flash\";\nstruct Rect { w: int }\nlet mine = Rect { w: 1 };\nlet r: shapes.Rect = shapes.Rect { w: 2, h: 3 };\nreturn mine.w + shapes.area(r.w, r.h);", 7),
+            ("import \"geo/shapes.flash\";\nlet s = shapes.square(4);\nreturn match s { shapes.Shape::Square(n) => n, shapes.Shape::Empty => 0 };", 4),
+        ];
+        for (main, expected) in tests {
+            let (eval, result) = compile_files(&[("main.flash", main), ("math.flash", math), ("geo/shapes.flash", shapes)]);
+            if let Object::Error(err) = result {
+                panic!("compile failed: {}", err);
+            }
+            assert_eq!(execute_test_ir_function(eval.lc.module, eval.function_stack.main_func), expected, "{}", main);
+        }
+
+        let errors = vec![
+            ("import math;\nreturn math.mul(2, 3);", "main.flash:2:8: module `math` has no public function `mul`"),
+            ("import math;\nreturn math;", "main.flash:2:1: cannot return module `math` from a function returning int"),
+        ];
+        assert_errors_with(&errors, |main| compile_files(&[("main.flash", main), ("math.flash", math)]).1);
+        assert_errors_with(
+            &[("import math;\nreturn 0;", "math.flash:1:1: only declarations can appear at the top level of an imported file")],
+            |main| compile_files(&[("main.flash", main), ("math.flash", "let x = 1;")]).1,
+        );
+        let errors = vec![
+            ("import \"geo/shapes.flash\";\nreturn Rect { w: 1, h: 2 }.w;", "main.flash:2:8: unknown struct `Rect`"),
+            ("import \"geo/shapes.flash\";\nlet s: shapes.Circle = 1;", "main.flash:2:1: unknown type `shapes.Circle`"),
+            ("import \"geo/shapes.flash\";\nlet p = math.Pair { a: 1, b: 2 };", "main.flash:2:9: unknown struct `math.Pair`"),
+        ];
+        assert_errors_with(&errors, |main| compile_files(&[("main.flash", main), ("math.flash", math), ("geo/shapes.flash", shapes)]).1);
+        let point = "struct Point { y: int, z: int }\npub func f(): int { let p = Point { y: 2, z: 3 }; return p.y * p.z; }";
+        let (eval, result) = compile_files(&[("main.flash", "import a;\nstruct Point { x: int }\nlet p = Point { x: 1 };\nreturn p.x + a.f();"), ("a.flash", point)]);
+        assert!(!matches!(result, Object::Error(_)), "{:?}", result);
+        assert_eq!(execute_test_ir_function(eval.lc.module, eval.function_stack.main_func), 7);
+        assert_errors_with(&[("import a;\nstruct Point { x: int }\nreturn a.f();", "a.flash:1:29: unknown struct `Point`")], |main| {
+            compile_files(&[("main.flash", main), ("a.flash", "pub func f(): int { let p = Point { x: 1 }; return p.x; }")]).1
+        });
+        assert_compile_errors(&[("import math;", "<input>:1:1: module `math` is not loaded")]);
+    }
+
+    #[test]
+    fn test_generics() {
+        let generic = "func max<T>(a: T, b: T): T { if (a > b) { return a; } return b; }\n\
+            func first<T>(xs: [T; 3]): T { let x: T = xs[0]; return x; }\n\
+            func apply<A, B>(f: func(A): B, a: A): B { return f(a); }\n\
+            func count<T>(n: int, x: T): int { if (n == 0) { return 0; } return 1 + count(n - 1, x); }\n\
+            struct Point { x: int, y: int }\n";
+        let tests = vec![
+            ("return max(3, 7);", 7),
+            ("return max(2.5, 1.0) as int + max(4u8, 9u8) as int;", 11),
+            ("return if (max('a', 'c') == 'c') { 1 } else { 0 };", 1),
+            ("return first([4, 5, 6]) + first([1u8, 2, 3]) as int;", 5),
+            ("let p = first([Point { x: 1, y: 2 }, Point { x: 3, y: 4 }, Point { x: 5, y: 6 }]); return p.y;", 2),
+            ("return apply(func(x: int): int { return x * 3; }, 5);", 15),
+            ("let b = apply(func(s: string): bool { return true; }, \"s\"); return if (b) { 1 } else { 0 };", 1),
+            ("return count(4, true) + count(2, 1.5);", 6),
+            // Unsuffixed literals take the type the other arguments infer.
+            ("let x: u8 = 200; return max(x, 1) as int + max(7, x) as int;", 400),
+            ("let x: i64 = 5; return first([x, 9, 1]) as int + count(3, 2);", 8),
+        ];
+        for (input, expected) in tests {
+            let input = format!("{}{}", generic, input);
+            assert_eq!(run(&input), expected, "{}", input);
+        }
+
+        // Each list of type arguments gets its own function, compiled once.
+        let (eval, result) = compile(&format!("{}return max(1, 2) + max(3, 4) + max(1u8, 2u8) as int;", generic));
+        assert!(!matches!(result, Object::Error(_)));
+        assert!(!get_named_function(eval.lc.module, "max<int>").is_null());
+        assert!(!get_named_function(eval.lc.module, "max<u8>").is_null());
+        assert_eq!(eval.generics[0].instances.len(), 2);
+        assert!(get_named_function(eval.lc.module, "first<int>").is_null());
+
+        let errors = vec![
+            ("return max(true, false) as int;", "<input>:1:34: `>` is not defined for bool, in `max<bool>` called at <input>:6:8"),
+            ("return max(1, true);", "<input>:6:8: argument 2 should be int, found bool"),
+            ("func none<T>(): T { return 1; }\nreturn none();", "<input>:7:8: cannot infer type parameter `T` of `none` from the arguments"),
+            ("let f = max;", "<input>:6:9: generic function `max` can only be called"),
+            ("return max(1);", "<input>:6:8: expected 2 arguments, found 1"),
+            ("func twice<T, T>(a: T): T { return a; }", "<input>:6:1: type parameter `T` of `twice` is declared twice"),
+            ("func bad<T>(a: T): int { return a; }\nreturn bad(true);", "<input>:6:26: cannot return bool from a function returning int, in `bad<bool>` called at <input>:7:8"),
+        ];
+        assert_errors_with(&errors, |input| compile(&format!("{}{}", generic, input)).1);
+    }
+
+    #[test]
+    fn test_tuples() {
+        let divmod = "func divmod(a: int, b: int): (int, int) { return (a / b, a % b); }\n";
+        let tests = vec![
+            ("let (q, r) = divmod(17, 5); return q * 10 + r;", 32),
+            ("let (q, _): (int, int) = divmod(9, 2); return q;", 4),
+            ("let t = divmod(7, 3); return t.0 + t.1;", 3),
+            ("let t = (1, (2u8, true)); if (t.1.1) { return t.0 + t.1.0 as int; } return 0;", 3),
+            ("let t = (1, 2); let u = t; u.0 = 5; return t.0 * 10 + u.0;", 15),
+            ("struct P { x: int, pair: (int, bool) } let p = P { x: 1, pair: (4, true) }; p.pair.0 += 1; return p.pair.0;", 5),
+            ("func swap(t: (int, string)): (string, int) { return (t.1, t.0); } let (s, n) = swap((3, \"x\")); return n;", 3),
+            ("let f = func(): (int, int) { return (6, 7); }; let (a, b) = f(); return a * b;", 42),
+            ("func pair<A, B>(a: A, b: B): (B, A) { return (b, a); } let (x, y) = pair(true, 9); return x;", 9),
+            ("let m = map[int](int, int){1: (2, 3)}; return m[1].1;", 3),
+            ("let xs = [(1, 2), (3, 4)]; let (a, b) = xs[1]; return a + b;", 7),
+        ];
+        for (input, expected) in tests {
+            let input = format!("{}{}", divmod, input);
+            assert_eq!(run(&input), expected, "{}", input);
+        }
+
+        let errors = vec![
+            ("let (a, b, c) = divmod(1, 1);", "<input>:2:1: cannot destructure (int, int) into 3 names"),
+            ("let (a, b) = 1;", "<input>:2:1: cannot destructure int, which is not a tuple"),
+            ("let (a, b): (int, bool) = divmod(1, 1);", "<input>:2:1: the tuple is declared as (int, bool) but initialised with (int, int)"),
+            ("let t = (1, 2); return t.2;", "<input>:2:24: (int, int) has no element `2`"),
+            ("let t = (1, 2); return t.x;", "<input>:2:24: (int, int) has no element `x`"),
+            ("func f(): (int, int) { return (1, true); }", "<input>:2:24: cannot return (int, bool) from a function returning (int, int)"),
+            ("let t = (1, printf);", "<input>:2:13: printf cannot be stored in a tuple"),
+        ];
+        assert_errors_with(&errors, |input| compile(&format!("{}{}", divmod, input)).1);
+    }
+}
@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+
+use crate::parser::ast::*;
+
+// infer binds the type parameters that appear in `param_type` by matching
+// it against the type of the argument passed for it. A parameter keeps the
+// first type it is bound to; a later argument that disagrees is reported
+// when the call checks its arguments against the instantiated function.
+pub fn infer(
+    param_type: &LLVMExpressionType,
+    arg_type: &LLVMExpressionType,
+    type_params: &[Ident],
+    bindings: &mut HashMap<String, LLVMExpressionType>,
+) {
+    match (param_type, arg_type) {
+        (LLVMExpressionType::Named(name), _) if type_params.iter().any(|param| param.0 == *name) => {
+            bindings.entry(name.clone()).or_insert_with(|| arg_type.clone());
+        }
+        (LLVMExpressionType::Array(param_element, _), LLVMExpressionType::Array(arg_element, _)) => {
+            infer(param_element, arg_element, type_params, bindings)
+        }
+        (LLVMExpressionType::Map(param_key, param_value), LLVMExpressionType::Map(arg_key, arg_value)) => {
+            infer(param_key, arg_key, type_params, bindings);
+            infer(param_value, arg_value, type_params, bindings);
+        }
+        (LLVMExpressionType::Function(param_params, param_return), LLVMExpressionType::Function(arg_params, arg_return)) => {
+            for (param, arg) in param_params.iter().zip(arg_params.iter()) {
+                infer(param, arg, type_params, bindings);
+            }
+            infer(param_return, arg_return, type_params, bindings);
+        }
+        (LLVMExpressionType::Tuple(param_elements), LLVMExpressionType::Tuple(arg_elements)) => {
+            for (param, arg) in param_elements.iter().zip(arg_elements.iter()) {
+                infer(param, arg, type_params, bindings);
+            }
+        }
+        _ => {}
+    }
+}
+
+// substitute replaces the type parameters in `value_type` with the types
+// they are bound to.
+pub fn substitute(value_type: &LLVMExpressionType, bindings: &HashMap<String, LLVMExpressionType>) -> LLVMExpressionType {
+    match value_type {
+        LLVMExpressionType::Named(name) => match bindings.get(name) {
+            Some(bound) => bound.clone(),
+            None => value_type.clone(),
+        },
+        LLVMExpressionType::Array(element, length) => {
+            LLVMExpressionType::Array(Box::new(substitute(element, bindings)), *length)
+        }
+        LLVMExpressionType::Map(key, value) => {
+            LLVMExpressionType::Map(Box::new(substitute(key, bindings)), Box::new(substitute(value, bindings)))
+        }
+        LLVMExpressionType::Function(params, return_type) => LLVMExpressionType::Function(
+            params.iter().map(|param| substitute(param, bindings)).collect(),
+            Box::new(substitute(return_type, bindings)),
+        ),
+        LLVMExpressionType::Tuple(elements) => {
+            LLVMExpressionType::Tuple(elements.iter().map(|element| substitute(element, bindings)).collect())
+        }
+        value_type => value_type.clone(),
+    }
+}
+
+// substitute_block rewrites every type written in a function body: let
+// annotations, casts, map literals and closure signatures.
+pub fn substitute_block(program: &mut BlockStmt, bindings: &HashMap<String, LLVMExpressionType>) {
+    Substitution(bindings).visit_program_mut(program);
+}
+
+struct Substitution<'a>(&'a HashMap<String, LLVMExpressionType>);
+
+impl MutVisitor for Substitution<'_> {
+    fn visit_type_mut(&mut self, value_type: &mut LLVMExpressionType) {
+        *value_type = substitute(value_type, self.0);
+    }
+}
@@ -1,4 +1,7 @@
-//pub mod stack;
+pub mod stack;
 pub mod object;
-//pub mod eval;
-//pub mod environment;
+pub mod eval;
+pub mod environment;
+pub mod capture;
+pub mod generics;
+pub mod namespace;
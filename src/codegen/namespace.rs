@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+
+use crate::lexer::Span;
+use crate::parser::ast::*;
+
+// qualify_types rewrites the struct and enum names used in one file to the
+// names they are declared under in the module. `names` maps what the file
+// may write, its own types and `shapes.Rect` for the types of an import, to
+// those names. When `strict` is set, any other name is reported: it could
+// only match a type of a file this one does not import.
+pub fn qualify_types(program: &mut Program, names: &HashMap<String, String>, strict: bool) -> Result<(), String> {
+    let mut qualifier = Qualifier {
+        names,
+        strict,
+        type_params: vec![],
+        span: Span::default(),
+        error: None,
+    };
+    qualifier.visit_program_mut(program);
+    match qualifier.error {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
+
+struct Qualifier<'a> {
+    names: &'a HashMap<String, String>,
+    strict: bool,
+    // The type parameters of the generic function being walked, which
+    // shadow any type of the same name.
+    type_params: Vec<String>,
+    // The innermost statement or expression, which errors point at.
+    span: Span,
+    error: Option<String>,
+}
+
+impl Qualifier<'_> {
+    fn qualify(&mut self, name: &mut String, kind: &str) {
+        if self.type_params.contains(name) {
+            return;
+        }
+        match self.names.get(name.as_str()) {
+            Some(qualified) => *name = qualified.clone(),
+            // Only the first error is reported.
+            None if (self.strict || name.contains('.')) && self.error.is_none() => {
+                self.error = Some(format!("{}: unknown {} `{}`", self.span, kind, name));
+            }
+            None => {}
+        }
+    }
+
+    fn qualify_type(&mut self, value_type: &mut LLVMExpressionType) {
+        match value_type {
+            LLVMExpressionType::Named(name) => self.qualify(name, "type"),
+            LLVMExpressionType::Array(element_type, _) => self.qualify_type(element_type),
+            LLVMExpressionType::Map(key_type, value_type) => {
+                self.qualify_type(key_type);
+                self.qualify_type(value_type);
+            }
+            LLVMExpressionType::Function(param_types, return_type) => {
+                param_types.iter_mut().for_each(|param_type| self.qualify_type(param_type));
+                self.qualify_type(return_type);
+            }
+            LLVMExpressionType::Tuple(elements) => elements.iter_mut().for_each(|element| self.qualify_type(element)),
+            _ => {}
+        }
+    }
+}
+
+impl MutVisitor for Qualifier<'_> {
+    fn visit_stmt_mut(&mut self, stmt: &mut Stmt) {
+        let span = std::mem::replace(&mut self.span, stmt.span.clone());
+        match &mut stmt.kind {
+            StmtKind::Struct { name, .. } => self.qualify(&mut name.0, "struct"),
+            StmtKind::Enum { name, .. } => self.qualify(&mut name.0, "enum"),
+            StmtKind::Func { type_params, .. } => self.type_params = type_params.iter().map(|param| param.0.clone()).collect(),
+            _ => {}
+        }
+        walk_stmt_mut(self, stmt);
+        if let StmtKind::Func { .. } = stmt.kind {
+            self.type_params.clear();
+        }
+        self.span = span;
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        let span = std::mem::replace(&mut self.span, expr.span.clone());
+        match &mut expr.kind {
+            ExprKind::Struct { name, .. } => self.qualify(&mut name.0, "struct"),
+            ExprKind::Variant { name, .. } => self.qualify(&mut name.0, "enum"),
+            _ => {}
+        }
+        walk_expr_mut(self, expr);
+        self.span = span;
+    }
+
+    fn visit_pattern_mut(&mut self, pattern: &mut Pattern) {
+        if let Pattern::Variant { name, .. } = pattern {
+            self.qualify(&mut name.0, "enum");
+        }
+        walk_pattern_mut(self, pattern);
+    }
+
+    fn visit_type_mut(&mut self, value_type: &mut LLVMExpressionType) {
+        self.qualify_type(value_type);
+    }
+}
@@ -1,16 +1,42 @@
+use std::collections::HashMap;
 use std::fmt;
 
+use crate::codegen::environment::Environment;
+use crate::lexer::{IntKind, Span};
 use crate::parser::ast::*;
 
 use llvm_sys::*;
 
 #[derive(Debug, Clone)]
 pub enum Object {
-    Integer(*mut LLVMValue),
+    Integer(IntKind, *mut LLVMValue),
+    Float(*mut LLVMValue),
+    // A Unicode scalar value, stored as an i32.
+    Char(*mut LLVMValue),
     Boolean(*mut LLVMValue),
- //   String(*mut LLVMValue, u32),
-//    Array(LLVMExpressionType, *mut LLVMValue, u32),
+    String(*mut LLVMValue),
+    // The element type, a pointer to the elements and their number. Arrays
+    // are values: binding or assigning one copies the elements.
+    Array(LLVMExpressionType, *mut LLVMValue, u32),
+    // The key type, the value type and a pointer to the hash table. Maps are
+    // references: binding or assigning one shares the table.
+    Map(LLVMExpressionType, LLVMExpressionType, *mut LLVMValue),
+    // The name of a struct or an enum and a pointer to its storage. Like
+    // arrays, structs and enums are values.
+    Named(String, *mut LLVMValue),
+    // The element types and a pointer to the elements, held like the
+    // fields of a struct.
+    Tuple(Vec<LLVMExpressionType>, *mut LLVMValue),
+    // The parameter types, the return type and a pointer to a function
+    // pointer and environment pair. Copying a closure copies the pair, so
+    // the copies share the captured variables.
+    Closure(Vec<LLVMExpressionType>, LLVMExpressionType, *mut LLVMValue),
     Function(Function),
+    // A function with type parameters: its name and its index in
+    // Eval.generics. It can only be called.
+    Generic(String, usize),
+    // An imported file: its name and its public functions.
+    Module(String, HashMap<String, Object>),
     Null,
     Error(String),
     BuildIn(BuildIn),
@@ -20,28 +46,92 @@ pub enum Object {
 #[derive(Debug, Clone)]
 pub enum BuildIn {
     Printf,
+    Length,
+    Contains,
+    Remove,
+}
+
+// StructType is a declared struct: its LLVM type and its fields in order.
+#[derive(Debug, Clone)]
+pub struct StructType {
+    pub llvm_type: *mut LLVMType,
+    pub fields: Vec<(String, LLVMExpressionType)>,
+}
+
+// EnumType is a declared enum. Its storage is an i32 tag followed by
+// `payload_words` i64s, enough for the largest variant; each variant reads
+// the storage through its own struct type, which starts with the tag.
+#[derive(Debug, Clone)]
+pub struct EnumType {
+    pub llvm_type: *mut LLVMType,
+    pub variants: Vec<EnumVariant>,
+    pub payload_words: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct EnumVariant {
+    pub name: String,
+    pub fields: Vec<LLVMExpressionType>,
+    pub llvm_type: *mut LLVMType,
 }
 
 #[derive(Debug, Clone)]
 pub struct Function {
     pub llvm_value: *mut LLVMValue,
     pub llvm_block: *mut LLVMBasicBlock,
+    pub param_types: Vec<LLVMExpressionType>,
+    pub return_type: LLVMExpressionType,
+}
+
+// GenericFunction is a function with type parameters. Its body is compiled
+// once for every list of type arguments it is called with, in the scope of
+// the file that declares it.
+#[derive(Debug, Clone)]
+pub struct GenericFunction {
+    pub name: String,
+    pub llvm_name: String,
+    pub type_params: Vec<Ident>,
+    pub params: Vec<Ident>,
+    pub param_types: Vec<LLVMExpressionType>,
     pub return_type: LLVMExpressionType,
+    pub body: Program,
+    pub span: Span,
+    pub env: Environment,
+    // The instances compiled so far, by name, such as `max<int>`.
+    pub instances: HashMap<String, Function>,
+}
+
+impl Object {
+    // is_variable reports whether the object is a value held in a stack slot.
+    pub fn is_variable(&self) -> bool {
+        matches!(self, Object::Integer(_, _) | Object::Float(_) | Object::Char(_) | Object::Boolean(_) | Object::String(_) | Object::Array(_, _, _) | Object::Map(_, _, _) | Object::Named(_, _) | Object::Tuple(_, _) | Object::Closure(_, _, _))
+    }
 }
 
 impl fmt::Display for Object {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Object::Integer(_) => write!(f, "Integer"),        // TODO
-            Object::Boolean(_) => write!(f, "Boolean"),        // TODO
-   //         Object::String(_, _) => write!(f, "{}", "String"), // TODO
-  //          Object::Array(child_type, _, _) => write!(f, "{}", child_type), // TODO
-            Object::Function(_) => write!(f, "{}", "TODO"),
-            Object::Null => write!(f, "Null"),
+            Object::Integer(kind, _) => write!(f, "{}", LLVMExpressionType::Integer(*kind)),
+            Object::Float(_) => write!(f, "float"),
+            Object::Char(_) => write!(f, "char"),
+            Object::Boolean(_) => write!(f, "bool"),
+            Object::String(_) => write!(f, "string"),
+            Object::Array(element, _, length) => write!(f, "[{}; {}]", element, length),
+            Object::Map(key, value, _) => write!(f, "map[{}]{}", key, value),
+            Object::Named(name, _) => write!(f, "{}", name),
+            Object::Tuple(elements, _) => write!(f, "{}", LLVMExpressionType::Tuple(elements.clone())),
+            Object::Closure(params, return_type, _) | Object::Function(Function { param_types: params, return_type, .. }) => {
+                write!(f, "{}", LLVMExpressionType::Function(params.clone(), Box::new(return_type.clone())))
+            }
+            Object::Generic(name, _) => write!(f, "generic function `{}`", name),
+            Object::Module(name, _) => write!(f, "module `{}`", name),
+            Object::Null => write!(f, "no value"),
             Object::Error(string) => write!(f, "{}", string),
             Object::BuildIn(build_in) => match build_in {
                 BuildIn::Printf => write!(f, "printf"),
-//                BuildIn::Length => write!(f, "length"),
+                BuildIn::Length => write!(f, "length"),
+                BuildIn::Contains => write!(f, "contains"),
+                BuildIn::Remove => write!(f, "remove"),
             },
             Object::Argument(_, _, _) => write!(f, "TODO"),
         }
@@ -1,23 +1,30 @@
 use llvm_sys::*;
 
+use crate::lexer::IntKind;
+use crate::parser::ast::*;
+
 pub struct FunctionStack {
     pub stack: Vec<*mut LLVMValue>,
+    pub return_types: Vec<LLVMExpressionType>,
     pub main_func: *mut LLVMValue,
 }
 
 impl FunctionStack {
     pub fn new(main_func: *mut LLVMValue) -> Self {
-        FunctionStck {
+        FunctionStack {
             stack: Vec::new(),
+            return_types: Vec::new(),
             main_func: main_func
         }
     }
 
-    pub fn push(&mut self, function: *mut LLVMValue){
+    pub fn push(&mut self, function: *mut LLVMValue, return_type: LLVMExpressionType){
         self.stack.push(function);
+        self.return_types.push(return_type);
     }
 
     pub fn pop(&mut self) -> *mut LLVMValue {
+        self.return_types.pop();
         if let Some(function) = self.stack.pop() {
             function
         }else{
@@ -25,6 +32,14 @@ impl FunctionStack {
         }
     }
 
+    // main returns an int exit code.
+    pub fn last_return_type(&self) -> LLVMExpressionType {
+        match self.return_types.last() {
+            Some(return_type) => return_type.clone(),
+            None => LLVMExpressionType::Integer(IntKind::I32),
+        }
+    }
+
     pub fn last(&mut self) -> *mut LLVMValue {
         if let Some(function) = self.stack.last_mut() {
             *function
@@ -33,3 +48,42 @@ impl FunctionStack {
         }
     }
 }
+
+// Loop holds the blocks `continue` and `break` jump to for one loop.
+pub struct Loop {
+    pub label: Option<String>,
+    pub continue_block: *mut LLVMBasicBlock,
+    pub break_block: *mut LLVMBasicBlock,
+}
+
+pub struct LoopStack {
+    pub stack: Vec<Loop>,
+}
+
+impl LoopStack {
+    pub fn new() -> Self {
+        LoopStack { stack: Vec::new() }
+    }
+
+    pub fn push(&mut self, label: Option<String>, continue_block: *mut LLVMBasicBlock, break_block: *mut LLVMBasicBlock) {
+        self.stack.push(Loop { label, continue_block, break_block });
+    }
+
+    pub fn pop(&mut self) {
+        self.stack.pop();
+    }
+
+    // find returns the innermost loop, or the one with the given label.
+    pub fn find(&self, label: Option<&str>) -> Option<&Loop> {
+        match label {
+            Some(label) => self.stack.iter().rev().find(|l| l.label.as_deref() == Some(label)),
+            None => self.stack.last(),
+        }
+    }
+}
+
+impl Default for LoopStack {
+    fn default() -> Self {
+        Self::new()
+    }
+}
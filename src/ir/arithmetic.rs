@@ -63,6 +63,151 @@ pub fn divide_variable(
     unsafe { LLVMBuildUDiv(builder, var_a, var_b, c_string!(name).as_ptr()) }
 }
 
+#[allow(dead_code)]
+pub fn signed_rem_variable(
+    builder: *mut LLVMBuilder,
+    var_a: *mut LLVMValue,
+    var_b: *mut LLVMValue,
+    name: &str,
+) -> *mut LLVMValue {
+    unsafe { LLVMBuildSRem(builder, var_a, var_b, c_string!(name).as_ptr()) }
+}
+
+#[allow(dead_code)]
+pub fn signed_divide_variable(
+    builder: *mut LLVMBuilder,
+    var_a: *mut LLVMValue,
+    var_b: *mut LLVMValue,
+    name: &str,
+) -> *mut LLVMValue {
+    unsafe { LLVMBuildSDiv(builder, var_a, var_b, c_string!(name).as_ptr()) }
+}
+
+#[allow(dead_code)]
+pub fn and_variable(
+    builder: *mut LLVMBuilder,
+    var_a: *mut LLVMValue,
+    var_b: *mut LLVMValue,
+    name: &str,
+) -> *mut LLVMValue {
+    unsafe { LLVMBuildAnd(builder, var_a, var_b, c_string!(name).as_ptr()) }
+}
+
+#[allow(dead_code)]
+pub fn or_variable(
+    builder: *mut LLVMBuilder,
+    var_a: *mut LLVMValue,
+    var_b: *mut LLVMValue,
+    name: &str,
+) -> *mut LLVMValue {
+    unsafe { LLVMBuildOr(builder, var_a, var_b, c_string!(name).as_ptr()) }
+}
+
+#[allow(dead_code)]
+pub fn xor_variable(
+    builder: *mut LLVMBuilder,
+    var_a: *mut LLVMValue,
+    var_b: *mut LLVMValue,
+    name: &str,
+) -> *mut LLVMValue {
+    unsafe { LLVMBuildXor(builder, var_a, var_b, c_string!(name).as_ptr()) }
+}
+
+#[allow(dead_code)]
+pub fn shift_left_variable(
+    builder: *mut LLVMBuilder,
+    var_a: *mut LLVMValue,
+    var_b: *mut LLVMValue,
+    name: &str,
+) -> *mut LLVMValue {
+    unsafe { LLVMBuildShl(builder, var_a, var_b, c_string!(name).as_ptr()) }
+}
+
+#[allow(dead_code)]
+pub fn arithmetic_shift_right_variable(
+    builder: *mut LLVMBuilder,
+    var_a: *mut LLVMValue,
+    var_b: *mut LLVMValue,
+    name: &str,
+) -> *mut LLVMValue {
+    unsafe { LLVMBuildAShr(builder, var_a, var_b, c_string!(name).as_ptr()) }
+}
+
+#[allow(dead_code)]
+pub fn logical_shift_right_variable(
+    builder: *mut LLVMBuilder,
+    var_a: *mut LLVMValue,
+    var_b: *mut LLVMValue,
+    name: &str,
+) -> *mut LLVMValue {
+    unsafe { LLVMBuildLShr(builder, var_a, var_b, c_string!(name).as_ptr()) }
+}
+
+#[allow(dead_code)]
+pub fn neg_variable(builder: *mut LLVMBuilder, var: *mut LLVMValue, name: &str) -> *mut LLVMValue {
+    unsafe { LLVMBuildNeg(builder, var, c_string!(name).as_ptr()) }
+}
+
+#[allow(dead_code)]
+pub fn not_variable(builder: *mut LLVMBuilder, var: *mut LLVMValue, name: &str) -> *mut LLVMValue {
+    unsafe { LLVMBuildNot(builder, var, c_string!(name).as_ptr()) }
+}
+
+#[allow(dead_code)]
+pub fn float_add_variable(
+    builder: *mut LLVMBuilder,
+    var_a: *mut LLVMValue,
+    var_b: *mut LLVMValue,
+    name: &str,
+) -> *mut LLVMValue {
+    unsafe { LLVMBuildFAdd(builder, var_a, var_b, c_string!(name).as_ptr()) }
+}
+
+#[allow(dead_code)]
+pub fn float_sub_variable(
+    builder: *mut LLVMBuilder,
+    var_a: *mut LLVMValue,
+    var_b: *mut LLVMValue,
+    name: &str,
+) -> *mut LLVMValue {
+    unsafe { LLVMBuildFSub(builder, var_a, var_b, c_string!(name).as_ptr()) }
+}
+
+#[allow(dead_code)]
+pub fn float_multiple_variable(
+    builder: *mut LLVMBuilder,
+    var_a: *mut LLVMValue,
+    var_b: *mut LLVMValue,
+    name: &str,
+) -> *mut LLVMValue {
+    unsafe { LLVMBuildFMul(builder, var_a, var_b, c_string!(name).as_ptr()) }
+}
+
+#[allow(dead_code)]
+pub fn float_divide_variable(
+    builder: *mut LLVMBuilder,
+    var_a: *mut LLVMValue,
+    var_b: *mut LLVMValue,
+    name: &str,
+) -> *mut LLVMValue {
+    unsafe { LLVMBuildFDiv(builder, var_a, var_b, c_string!(name).as_ptr()) }
+}
+
+#[allow(dead_code)]
+pub fn float_rem_variable(
+    builder: *mut LLVMBuilder,
+    var_a: *mut LLVMValue,
+    var_b: *mut LLVMValue,
+    name: &str,
+) -> *mut LLVMValue {
+    unsafe { LLVMBuildFRem(builder, var_a, var_b, c_string!(name).as_ptr()) }
+}
+
+#[allow(dead_code)]
+pub fn float_neg_variable(builder: *mut LLVMBuilder, var: *mut LLVMValue, name: &str) -> *mut LLVMValue {
+    unsafe { LLVMBuildFNeg(builder, var, c_string!(name).as_ptr()) }
+}
+
 #[allow(dead_code)]
 fn setup_llvm() -> LLVMCreator {
     let mut lc = LLVMCreator::new("test_module");
@@ -97,3 +242,41 @@ fn add() {
         );
     }
 }
+
+#[test]
+fn float_arithmetic() {
+    unsafe {
+        let lc = setup_llvm();
+        let mut loses_info = 0;
+        let value = float_divide_variable(
+            lc.builder,
+            float_multiple_variable(lc.builder, LLVMConstReal(double_type(), 1.5), LLVMConstReal(double_type(), 3.0), ""),
+            LLVMConstReal(double_type(), 2.0),
+            "",
+        );
+        assert_eq!(LLVMConstRealGetDouble(value, &mut loses_info), 2.25);
+
+        let value = float_sub_variable(lc.builder, LLVMConstReal(double_type(), 0.5), LLVMConstReal(double_type(), 1e-9), "");
+        assert_eq!(LLVMConstRealGetDouble(value, &mut loses_info), 0.5 - 1e-9);
+    }
+}
+
+#[test]
+fn signed_division() {
+    unsafe {
+        let lc = setup_llvm();
+        let minus_seven = LLVMConstInt(int32_type(), (-7i64) as u64, 1);
+        int_arithmetic_assert(
+            signed_divide_variable(lc.builder, minus_seven, LLVMConstInt(int32_type(), 2, 0), ""),
+            LLVMConstInt(int32_type(), (-3i64) as u64, 1),
+        );
+        int_arithmetic_assert(
+            signed_rem_variable(lc.builder, minus_seven, LLVMConstInt(int32_type(), 2, 0), ""),
+            LLVMConstInt(int32_type(), (-1i64) as u64, 1),
+        );
+        int_arithmetic_assert(
+            arithmetic_shift_right_variable(lc.builder, minus_seven, LLVMConstInt(int32_type(), 1, 0), ""),
+            LLVMConstInt(int32_type(), (-4i64) as u64, 1),
+        );
+    }
+}
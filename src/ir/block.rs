@@ -12,3 +12,23 @@ pub fn append_basic_block_in_context(
 ) -> *mut LLVMBasicBlock {
     unsafe { LLVMAppendBasicBlockInContext(context, function, c_string!(function_name).as_ptr()) }
 }
+
+#[allow(dead_code)]
+pub fn get_entry_basic_block(function: *mut LLVMValue) -> *mut LLVMBasicBlock {
+    unsafe { LLVMGetEntryBasicBlock(function) }
+}
+
+#[allow(dead_code)]
+pub fn get_first_instruction(block: *mut LLVMBasicBlock) -> *mut LLVMValue {
+    unsafe { LLVMGetFirstInstruction(block) }
+}
+
+#[allow(dead_code)]
+pub fn get_basic_block_terminator(block: *mut LLVMBasicBlock) -> *mut LLVMValue {
+    unsafe { LLVMGetBasicBlockTerminator(block) }
+}
+
+#[allow(dead_code)]
+pub fn block_has_predecessors(block: *mut LLVMBasicBlock) -> bool {
+    unsafe { !LLVMGetFirstUse(LLVMBasicBlockAsValue(block)).is_null() }
+}
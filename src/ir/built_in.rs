@@ -7,7 +7,21 @@ use crate::c_string;
 #[allow(dead_code)]
 pub fn create_printf(module: *mut LLVMModule) -> *mut LLVMValue {
     let mut printf_args_type_list = vec![pointer_type()];
-    let printf_type = function_type_var_arg(pointer_type(), &mut printf_args_type_list);
+    let printf_type = function_type_var_arg(int32_type(), &mut printf_args_type_list);
 
     add_function(module, printf_type, "printf")
 }
+
+#[allow(dead_code)]
+pub fn create_malloc(module: *mut LLVMModule) -> *mut LLVMValue {
+    let malloc_type = function_type(pointer_type(), &mut [int64_type()]);
+
+    add_function(module, malloc_type, "malloc")
+}
+
+#[allow(dead_code)]
+pub fn create_abort(module: *mut LLVMModule) -> *mut LLVMValue {
+    let abort_type = function_type(void_type(), &mut []);
+
+    add_function(module, abort_type, "abort")
+}
@@ -4,6 +4,7 @@ use llvm_sys::core::*;
 use llvm_sys::*;
 
 use llvm_sys::LLVMIntPredicate;
+use llvm_sys::LLVMRealPredicate;
 
 use crate::ir::block::*;
 use crate::ir::const_value::*;
@@ -39,6 +40,46 @@ pub fn build_br(builder: *mut LLVMBuilder, block: *mut LLVMBasicBlock) {
     };
 }
 
+// build_phi merges `values`, each arriving from the block at the same index
+// in `blocks`.
+#[allow(dead_code)]
+pub fn build_phi(
+    builder: *mut LLVMBuilder,
+    llvm_type: *mut LLVMType,
+    values: &mut [*mut LLVMValue],
+    blocks: &mut [*mut LLVMBasicBlock],
+    name: &str,
+) -> *mut LLVMValue {
+    unsafe {
+        let phi = LLVMBuildPhi(builder, llvm_type, c_string!(name).as_ptr());
+        LLVMAddIncoming(phi, values.as_mut_ptr(), blocks.as_mut_ptr(), values.len() as u32);
+        phi
+    }
+}
+
+// build_switch jumps to the block paired with the constant equal to `value`,
+// or to `default_block` when there is none.
+#[allow(dead_code)]
+pub fn build_switch(
+    builder: *mut LLVMBuilder,
+    value: *mut LLVMValue,
+    default_block: *mut LLVMBasicBlock,
+    cases: &[(*mut LLVMValue, *mut LLVMBasicBlock)],
+) -> *mut LLVMValue {
+    unsafe {
+        let switch = LLVMBuildSwitch(builder, value, default_block, cases.len() as u32);
+        for (case, block) in cases {
+            LLVMAddCase(switch, *case, *block);
+        }
+        switch
+    }
+}
+
+#[allow(dead_code)]
+pub fn build_is_not_null(builder: *mut LLVMBuilder, value: *mut LLVMValue, name: &str) -> *mut LLVMValue {
+    unsafe { LLVMBuildIsNotNull(builder, value, c_string!(name).as_ptr()) }
+}
+
 macro_rules! create_build_i_cmp {
     ($name:ident, $condition:expr) => {
         #[allow(dead_code)]
@@ -72,6 +113,37 @@ create_build_i_cmp!(build_int_sge, LLVMIntPredicate::LLVMIntSGE);
 create_build_i_cmp!(build_int_slt, LLVMIntPredicate::LLVMIntSLT);
 create_build_i_cmp!(build_int_sle, LLVMIntPredicate::LLVMIntSLE);
 
+macro_rules! create_build_f_cmp {
+    ($name:ident, $condition:expr) => {
+        #[allow(dead_code)]
+        pub fn $name(
+            builder: *mut LLVMBuilder,
+            left_val: *mut LLVMValue,
+            right_val: *mut LLVMValue,
+            name: &str,
+        ) -> *mut LLVMValue {
+            unsafe {
+                LLVMBuildFCmp(
+                    builder,
+                    $condition,
+                    left_val,
+                    right_val,
+                    c_string!(name).as_ptr(),
+                )
+            }
+        }
+    };
+}
+
+// Ordered comparisons are false when either side is NaN; `!=` is unordered so
+// that NaN != NaN holds.
+create_build_f_cmp!(build_float_oeq, LLVMRealPredicate::LLVMRealOEQ);
+create_build_f_cmp!(build_float_une, LLVMRealPredicate::LLVMRealUNE);
+create_build_f_cmp!(build_float_ogt, LLVMRealPredicate::LLVMRealOGT);
+create_build_f_cmp!(build_float_oge, LLVMRealPredicate::LLVMRealOGE);
+create_build_f_cmp!(build_float_olt, LLVMRealPredicate::LLVMRealOLT);
+create_build_f_cmp!(build_float_ole, LLVMRealPredicate::LLVMRealOLE);
+
 #[allow(dead_code)]
 pub fn create_if_else_test(
     lc: &mut LLVMCreator,
@@ -157,6 +229,83 @@ fn cond_int_cmp_false() {
     );
 }
 
+#[test]
+fn cond_float_cmp() {
+    let mut lc = LLVMCreator::new("test_module");
+    let main = setup_main(&mut lc);
+
+    let llvm_bool = build_float_olt(
+        lc.builder,
+        const_float(double_type(), 0.1),
+        const_float(double_type(), 0.2),
+        "",
+    );
+    assert!(
+        create_if_else_test(&mut lc, int32_type(), main, llvm_bool) == 1,
+        "failed cond_float_cmp"
+    );
+
+    let mut lc = LLVMCreator::new("test_module");
+    let main = setup_main(&mut lc);
+    let nan = const_float(double_type(), f64::NAN);
+    let llvm_bool = build_float_oeq(lc.builder, nan, nan, "");
+    assert!(
+        create_if_else_test(&mut lc, int32_type(), main, llvm_bool) == 2,
+        "failed cond_float_cmp with NaN"
+    );
+}
+
+#[test]
+fn cond_phi() {
+    for (condition, expected) in [(1, 10), (0, 20)] {
+        let mut lc = LLVMCreator::new("test_module");
+        let main = setup_main(&mut lc);
+        let left_block = append_basic_block_in_context(lc.context, main, "");
+        let right_block = append_basic_block_in_context(lc.context, main, "");
+        let end_block = append_basic_block_in_context(lc.context, main, "");
+
+        build_cond_br(lc.builder, const_int(int1_type(), condition), left_block, right_block);
+        build_position_at_end(lc.builder, left_block);
+        build_br(lc.builder, end_block);
+        build_position_at_end(lc.builder, right_block);
+        build_br(lc.builder, end_block);
+
+        build_position_at_end(lc.builder, end_block);
+        let phi = build_phi(
+            lc.builder,
+            int32_type(),
+            &mut [const_int(int32_type(), 10), const_int(int32_type(), 20)],
+            &mut [left_block, right_block],
+            "",
+        );
+        build_ret(lc.builder, phi);
+
+        assert!(execute_test_ir_function(lc.module, main) == expected, "failed cond_phi");
+    }
+}
+
+#[test]
+fn cond_switch() {
+    for (value, expected) in [(0, 10), (2, 30), (7, 40)] {
+        let mut lc = LLVMCreator::new("test_module");
+        let main = setup_main(&mut lc);
+        let blocks: Vec<_> = (0..4).map(|_| append_basic_block_in_context(lc.context, main, "")).collect();
+
+        let cases = [
+            (const_int(int32_type(), 0), blocks[0]),
+            (const_int(int32_type(), 1), blocks[1]),
+            (const_int(int32_type(), 2), blocks[2]),
+        ];
+        build_switch(lc.builder, const_int(int32_type(), value), blocks[3], &cases);
+        for (index, block) in blocks.iter().enumerate() {
+            build_position_at_end(lc.builder, *block);
+            build_ret(lc.builder, const_int(int32_type(), (index as u64 + 1) * 10));
+        }
+
+        assert!(execute_test_ir_function(lc.module, main) == expected, "failed cond_switch");
+    }
+}
+
 #[test]
 fn build_while() {
     let lc = LLVMCreator::new("test_module");
@@ -13,16 +13,55 @@ pub fn const_int(llvm_type: *mut LLVMType, value: u64) -> *mut LLVMValue {
     unsafe { LLVMConstInt(llvm_type, value, 0) }
 }
 
+#[allow(dead_code)]
+pub fn const_float(llvm_type: *mut LLVMType, value: f64) -> *mut LLVMValue {
+    unsafe { LLVMConstReal(llvm_type, value) }
+}
+
 #[allow(dead_code)]
 pub fn const_neg(value: *mut LLVMValue) -> *mut LLVMValue {
     unsafe { LLVMConstNeg(value) }
 }
 
+// size_of returns the size of `llvm_type` in bytes as an i64 constant.
+#[allow(dead_code)]
+pub fn size_of(llvm_type: *mut LLVMType) -> *mut LLVMValue {
+    unsafe { LLVMSizeOf(llvm_type) }
+}
+
+#[allow(dead_code)]
+pub fn const_null(llvm_type: *mut LLVMType) -> *mut LLVMValue {
+    unsafe { LLVMConstNull(llvm_type) }
+}
+
 #[allow(dead_code)]
 pub fn const_int_signed(llvm_type: *mut LLVMType, value: u64) -> *mut LLVMValue {
     unsafe { LLVMConstInt(llvm_type, value, 0) }
 }
 
+// const_string emits `value` as a private, NUL terminated global and returns
+// a pointer to its first byte.
+#[allow(dead_code)]
+pub fn const_string(lc: &mut LLVMCreator, value: &str) -> *mut LLVMValue {
+    let llvm_string =
+        unsafe { LLVMConstString(value.as_ptr() as *const _, value.len() as u32, 0) };
+    let global_string_val = add_global(lc.module, type_of(llvm_string), ".str");
+    set_linkage(global_string_val, LLVMPrivateLinkage);
+    set_initializer(global_string_val, llvm_string);
+    set_global_constant(global_string_val);
+    set_unnamed_address(global_string_val);
+
+    let mut indices = [const_int(int32_type(), 0), const_int(int32_type(), 0)];
+    unsafe {
+        LLVMConstInBoundsGEP2(
+            type_of(llvm_string),
+            global_string_val,
+            indices.as_mut_ptr(),
+            indices.len() as u32,
+        )
+    }
+}
+
 #[allow(dead_code)]
 pub fn const_array(
     lc: &mut LLVMCreator,
@@ -2,59 +2,122 @@ use llvm_sys::*;
 
 use crate::codegen::object::*;
 use crate::ir::llvm_type::*;
+use crate::lexer::IntKind;
 use crate::parser::ast::*;
 
 pub fn get_llvm_type_from_object(object: &mut Object) -> *mut LLVMType {
     match *object {
-        Object::Integer(_) => int32_type(),
+        Object::Integer(kind, _) => integer_type(kind),
+        Object::Float(_) => double_type(),
+        Object::Char(_) => int32_type(),
         Object::Boolean(_) => int1_type(),
-        Object::Function(_) => int1_type(), // need to fix
+        Object::String(_) => pointer_type(),
+        Object::Array(_, _, _) => pointer_type(),
+        Object::Map(_, _, _) => pointer_type(),
+        Object::Named(_, _) => pointer_type(),
+        Object::Tuple(_, _) => pointer_type(),
+        Object::Closure(_, _, _) => pointer_type(),
+        Object::Function(_) => pointer_type(),
         _ => panic!("failed to get llvm_type: {:?}", object),
     }
 }
 
+pub fn get_expression_type_from_object(object: &Object) -> LLVMExpressionType {
+    match *object {
+        Object::Integer(kind, _) => LLVMExpressionType::Integer(kind),
+        Object::Float(_) => LLVMExpressionType::Float,
+        Object::Char(_) => LLVMExpressionType::Char,
+        Object::Boolean(_) => LLVMExpressionType::Boolean,
+        Object::String(_) => LLVMExpressionType::String,
+        Object::Array(ref element, _, length) => LLVMExpressionType::Array(Box::new(element.clone()), length),
+        Object::Map(ref key, ref value, _) => LLVMExpressionType::Map(Box::new(key.clone()), Box::new(value.clone())),
+        Object::Named(ref name, _) => LLVMExpressionType::Named(name.clone()),
+        Object::Tuple(ref elements, _) => LLVMExpressionType::Tuple(elements.clone()),
+        Object::Closure(ref params, ref return_type, _) => {
+            LLVMExpressionType::Function(params.clone(), Box::new(return_type.clone()))
+        }
+        Object::Function(ref func) => {
+            LLVMExpressionType::Function(func.param_types.clone(), Box::new(func.return_type.clone()))
+        }
+        _ => LLVMExpressionType::Null,
+    }
+}
+
 pub fn convert_llvm_type(expression_type: LLVMExpressionType) -> *mut LLVMType {
     match expression_type {
-        LLVMExpressionType::Integer => int32_type(),
+        LLVMExpressionType::Integer(kind) => integer_type(kind),
+        LLVMExpressionType::Float => double_type(),
+        LLVMExpressionType::Char => int32_type(),
         LLVMExpressionType::Boolean => int1_type(),
-//        LLVMExpressionType::String(length) => array_type(int8_type(), length),
+        LLVMExpressionType::String => pointer_type(),
         LLVMExpressionType::Null => void_type(),
- //       LLVMExpressionType::Function => int32_type(), // need to fix
-  /*      LLVMExpressionType::Array(child_type, length) => {
-            let mut child_type = convert_llvm_type(*child_type);
-            array_type(child_type, length)
-        }*/
+        // Arrays, structs and function values are handled through a pointer
+        // to their storage, maps through a pointer to their table.
+        LLVMExpressionType::Array(_, _) => pointer_type(),
+        LLVMExpressionType::Map(_, _) => pointer_type(),
+        LLVMExpressionType::Named(_) => pointer_type(),
+        LLVMExpressionType::Tuple(_) => pointer_type(),
+        LLVMExpressionType::Function(_, _) => pointer_type(),
         LLVMExpressionType::Call => void_type(),
     }
 }
 
+// integer_type picks the ir helper for each width; a byte is an
+// `int8_type()`.
+pub fn integer_type(kind: IntKind) -> *mut LLVMType {
+    match kind {
+        IntKind::I8 | IntKind::U8 => int8_type(),
+        IntKind::I16 | IntKind::U16 => int16_type(),
+        IntKind::I32 | IntKind::U32 => int32_type(),
+        IntKind::I64 | IntKind::U64 => int64_type(),
+    }
+}
+
 pub fn unwrap_object(object: &mut Object) -> *mut LLVMValue {
     match *object {
-        Object::Integer(llvm_value) => llvm_value,
-  //      Object::String(llvm_value, _) => llvm_value,
+        Object::Integer(_, llvm_value) => llvm_value,
+        Object::Float(llvm_value) => llvm_value,
+        Object::Char(llvm_value) => llvm_value,
+        Object::String(llvm_value) => llvm_value,
         Object::Boolean(llvm_value) => llvm_value,
         Object::Function(ref func) => func.llvm_value,
-   //     Object::Array(_, llvm_value, _) => llvm_value,
+        Object::Array(_, llvm_value, _) => llvm_value,
+        Object::Map(_, _, llvm_value) => llvm_value,
+        Object::Named(_, llvm_value) => llvm_value,
+        Object::Tuple(_, llvm_value) => llvm_value,
+        Object::Closure(_, _, llvm_value) => llvm_value,
         _ => panic!("failed to unwrap object: {:?}", object),
     }
 }
 
 pub fn wrap_llvm_value(expression_type: LLVMExpressionType, llvm_value: *mut LLVMValue) -> Object {
     match expression_type {
-        LLVMExpressionType::Integer => Object::Integer(llvm_value),
+        LLVMExpressionType::Integer(kind) => Object::Integer(kind, llvm_value),
+        LLVMExpressionType::Float => Object::Float(llvm_value),
+        LLVMExpressionType::Char => Object::Char(llvm_value),
         LLVMExpressionType::Boolean => Object::Boolean(llvm_value),
+        LLVMExpressionType::String => Object::String(llvm_value),
+        LLVMExpressionType::Array(element, length) => Object::Array(*element, llvm_value, length),
+        LLVMExpressionType::Map(key, value) => Object::Map(*key, *value, llvm_value),
+        LLVMExpressionType::Named(name) => Object::Named(name, llvm_value),
+        LLVMExpressionType::Tuple(elements) => Object::Tuple(elements, llvm_value),
+        LLVMExpressionType::Function(params, return_type) => Object::Closure(params, *return_type, llvm_value),
         _ => Object::Null,
     }
 }
 
 pub fn rewrap_llvm_value_ref(object: Object, llvm_value_ref: *mut LLVMValue) -> Object {
     match object {
-        Object::Integer(_) => Object::Integer(llvm_value_ref),
-  //      Object::String(_, length) => Object::String(llvm_value_ref, length),
+        Object::Integer(kind, _) => Object::Integer(kind, llvm_value_ref),
+        Object::Float(_) => Object::Float(llvm_value_ref),
+        Object::Char(_) => Object::Char(llvm_value_ref),
+        Object::String(_) => Object::String(llvm_value_ref),
         Object::Boolean(_) => Object::Boolean(llvm_value_ref),
-   //     Object::Array(llvm_child_type, _, array_length) => {
-    //        Object::Array(llvm_child_type, llvm_value_ref, array_length)
-     //   }
+        Object::Array(element, _, length) => Object::Array(element, llvm_value_ref, length),
+        Object::Map(key, value, _) => Object::Map(key, value, llvm_value_ref),
+        Object::Named(name, _) => Object::Named(name, llvm_value_ref),
+        Object::Tuple(elements, _) => Object::Tuple(elements, llvm_value_ref),
+        Object::Closure(params, return_type, _) => Object::Closure(params, return_type, llvm_value_ref),
         _ => object,
     }
 }
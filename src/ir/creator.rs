@@ -37,6 +37,8 @@ impl LLVMCreator {
     #[allow(dead_code)]
     pub fn setup_builtin(&mut self) {
         self.built_ins.insert("printf", create_printf(self.module));
+        self.built_ins.insert("abort", create_abort(self.module));
+        self.built_ins.insert("malloc", create_malloc(self.module));
     }
 
     #[allow(dead_code)]
@@ -44,6 +46,16 @@ impl LLVMCreator {
         unsafe { LLVMDumpModule(self.module) }
     }
 
+    #[allow(dead_code)]
+    pub fn print_to_string(&self) -> String {
+        unsafe {
+            let raw = LLVMPrintModuleToString(self.module);
+            let ir = std::ffi::CStr::from_ptr(raw).to_string_lossy().into_owned();
+            LLVMDisposeMessage(raw);
+            ir
+        }
+    }
+
     #[allow(dead_code)]
     pub fn emit_file<P: AsRef<Path>>(&self, path: P) {
         let path = path
@@ -68,6 +68,30 @@ pub fn create_function(
     (function, block)
 }
 
+#[allow(dead_code)]
+pub fn get_function_type(function: *mut LLVMValue) -> *mut LLVMType {
+    unsafe { LLVMGlobalGetValueType(function) }
+}
+
+#[allow(dead_code)]
+pub fn count_param_types(function_type: *mut LLVMType) -> u32 {
+    unsafe { LLVMCountParamTypes(function_type) }
+}
+
+#[allow(dead_code)]
+pub fn get_return_type(function_type: *mut LLVMType) -> *mut LLVMType {
+    unsafe { LLVMGetReturnType(function_type) }
+}
+
+#[allow(dead_code)]
+pub fn get_value_name(value: *mut LLVMValue) -> String {
+    unsafe {
+        let mut length = 0;
+        let name = LLVMGetValueName2(value, &mut length);
+        String::from_utf8_lossy(std::slice::from_raw_parts(name as *const u8, length)).into_owned()
+    }
+}
+
 #[allow(dead_code)]
 pub fn get_named_function(module: *mut LLVMModule, name: &str) -> *mut LLVMValue {
     unsafe { LLVMGetNamedFunction(module, c_string!(name).as_ptr()) }
@@ -1,6 +1,7 @@
 use std::ffi::CString;
 
 use llvm_sys::core::*;
+use llvm_sys::target::*;
 use llvm_sys::*;
 use crate::c_string;
 
@@ -9,11 +10,31 @@ pub fn int32_type() -> *mut LLVMType {
     unsafe { LLVMInt32Type() }
 }
 
+#[allow(dead_code)]
+pub fn int64_type() -> *mut LLVMType {
+    unsafe { LLVMInt64Type() }
+}
+
+#[allow(dead_code)]
+pub fn int16_type() -> *mut LLVMType {
+    unsafe { LLVMInt16Type() }
+}
+
+#[allow(dead_code)]
+pub fn int_type(bits: u32) -> *mut LLVMType {
+    unsafe { LLVMIntType(bits) }
+}
+
 #[allow(dead_code)]
 pub fn int8_type() -> *mut LLVMType {
     unsafe { LLVMInt8Type() }
 }
 
+#[allow(dead_code)]
+pub fn double_type() -> *mut LLVMType {
+    unsafe { LLVMDoubleType() }
+}
+
 #[allow(dead_code)]
 pub fn int1_type() -> *mut LLVMType {
     unsafe { LLVMInt1Type() }
@@ -24,11 +45,45 @@ pub fn array_type(llvm_type: *mut LLVMType, length: u32) -> *mut LLVMType {
     unsafe { LLVMArrayType(llvm_type, length) }
 }
 
+#[allow(dead_code)]
+pub fn struct_type(fields: &mut [*mut LLVMType]) -> *mut LLVMType {
+    unsafe { LLVMStructType(fields.as_mut_ptr(), fields.len() as u32, 0) }
+}
+
+// named_struct_type creates an opaque struct type; its fields are set with
+// set_struct_body.
+#[allow(dead_code)]
+pub fn named_struct_type(name: &str) -> *mut LLVMType {
+    unsafe { LLVMStructCreateNamed(LLVMGetGlobalContext(), c_string!(name).as_ptr()) }
+}
+
+#[allow(dead_code)]
+pub fn set_struct_body(struct_type: *mut LLVMType, fields: &mut [*mut LLVMType]) {
+    unsafe { LLVMStructSetBody(struct_type, fields.as_mut_ptr(), fields.len() as u32, 0) }
+}
+
+// abi_size_of returns the size of a sized type in bytes under the data
+// layout of `module`.
+#[allow(dead_code)]
+pub fn abi_size_of(module: *mut LLVMModule, llvm_type: *mut LLVMType) -> u64 {
+    unsafe {
+        let data_layout = LLVMCreateTargetData(LLVMGetDataLayoutStr(module));
+        let size = LLVMABISizeOfType(data_layout, llvm_type);
+        LLVMDisposeTargetData(data_layout);
+        size
+    }
+}
+
 #[allow(dead_code)]
 pub fn pointer_type() -> *mut LLVMType {
     unsafe { LLVMPointerType(int8_type(), 0) }
 }
 
+#[allow(dead_code)]
+pub fn pointer_to(llvm_type: *mut LLVMType) -> *mut LLVMType {
+    unsafe { LLVMPointerType(llvm_type, 0) }
+}
+
 #[allow(dead_code)]
 pub fn function_type(ret_type: *mut LLVMType, args: &mut [*mut LLVMType]) -> *mut LLVMType {
     unsafe { LLVMFunctionType(ret_type, args.as_mut_ptr(), args.len() as u32, 0) }
@@ -36,7 +91,7 @@ pub fn function_type(ret_type: *mut LLVMType, args: &mut [*mut LLVMType]) -> *mu
 
 #[allow(dead_code)]
 pub fn function_type_var_arg(ret_type: *mut LLVMType, args: &mut [*mut LLVMType]) -> *mut LLVMType {
-    unsafe { LLVMFunctionType(ret_type, args.as_mut_ptr(), 0, 1) }
+    unsafe { LLVMFunctionType(ret_type, args.as_mut_ptr(), args.len() as u32, 1) }
 }
 
 #[allow(dead_code)]
@@ -0,0 +1,347 @@
+; Runtime support for `map[K]V`: an open addressing hash table with linear
+; probing. Keys are passed as i64; integers, chars and bools are widened and
+; strings are passed by address and compared by content.
+;
+; The table header holds, in order: the number of entries, the number of used
+; slots (entries and removed entries), the capacity (a power of two), whether
+; keys are strings, the size of a slot and a pointer to the slots. A slot is
+; laid out as [state: i64][key: i64][value], where state is 0 for empty, 1 for
+; an entry and 2 for a removed entry.
+
+%flash.map = type { i64, i64, i64, i64, i64, i8* }
+
+declare i8* @malloc(i64)
+declare i8* @calloc(i64, i64)
+declare void @free(i8*)
+
+define i8* @flash_map_new(i64 %key_is_string, i64 %value_size) {
+entry:
+  %raw = call i8* @malloc(i64 48)
+  %map = bitcast i8* %raw to %flash.map*
+  %padded = add i64 %value_size, 7
+  %aligned = and i64 %padded, -8
+  %slot_size = add i64 %aligned, 16
+  %slots = call i8* @calloc(i64 8, i64 %slot_size)
+  %len_ptr = getelementptr inbounds %flash.map, %flash.map* %map, i32 0, i32 0
+  store i64 0, i64* %len_ptr
+  %used_ptr = getelementptr inbounds %flash.map, %flash.map* %map, i32 0, i32 1
+  store i64 0, i64* %used_ptr
+  %capacity_ptr = getelementptr inbounds %flash.map, %flash.map* %map, i32 0, i32 2
+  store i64 8, i64* %capacity_ptr
+  %key_is_string_ptr = getelementptr inbounds %flash.map, %flash.map* %map, i32 0, i32 3
+  store i64 %key_is_string, i64* %key_is_string_ptr
+  %slot_size_ptr = getelementptr inbounds %flash.map, %flash.map* %map, i32 0, i32 4
+  store i64 %slot_size, i64* %slot_size_ptr
+  %slots_ptr = getelementptr inbounds %flash.map, %flash.map* %map, i32 0, i32 5
+  store i8* %slots, i8** %slots_ptr
+  ret i8* %raw
+}
+
+define i64 @flash_map_len(i8* %raw) {
+entry:
+  %map = bitcast i8* %raw to %flash.map*
+  %len_ptr = getelementptr inbounds %flash.map, %flash.map* %map, i32 0, i32 0
+  %len = load i64, i64* %len_ptr
+  ret i64 %len
+}
+
+; flash_map_find returns a pointer to the value stored under %key, or null.
+define i8* @flash_map_find(i8* %raw, i64 %key) {
+entry:
+  %result = call i64 @flash_map_probe(i8* %raw, i64 %key)
+  %found = icmp sge i64 %result, 0
+  br i1 %found, label %present, label %missing
+
+present:
+  %value = call i8* @flash_map_value(i8* %raw, i64 %result)
+  ret i8* %value
+
+missing:
+  ret i8* null
+}
+
+; flash_map_insert returns a pointer to the value stored under %key, adding
+; an entry for it first if there is none. The caller writes the value.
+define i8* @flash_map_insert(i8* %raw, i64 %key) {
+entry:
+  %map = bitcast i8* %raw to %flash.map*
+  %used_ptr = getelementptr inbounds %flash.map, %flash.map* %map, i32 0, i32 1
+  %used = load i64, i64* %used_ptr
+  %capacity_ptr = getelementptr inbounds %flash.map, %flash.map* %map, i32 0, i32 2
+  %capacity = load i64, i64* %capacity_ptr
+  ; Grow before more than three quarters of the slots are used, so that
+  ; probing always reaches an empty slot.
+  %next_used = add i64 %used, 1
+  %load = mul i64 %next_used, 4
+  %limit = mul i64 %capacity, 3
+  %crowded = icmp ugt i64 %load, %limit
+  br i1 %crowded, label %grow, label %probe
+
+grow:
+  call void @flash_map_grow(i8* %raw)
+  br label %probe
+
+probe:
+  %result = call i64 @flash_map_probe(i8* %raw, i64 %key)
+  %found = icmp sge i64 %result, 0
+  br i1 %found, label %present, label %add
+
+present:
+  %value = call i8* @flash_map_value(i8* %raw, i64 %result)
+  ret i8* %value
+
+add:
+  %index = sub i64 -1, %result
+  %slot = call i64* @flash_map_slot(i8* %raw, i64 %index)
+  %state = load i64, i64* %slot
+  %was_empty = icmp eq i64 %state, 0
+  br i1 %was_empty, label %take_empty, label %fill
+
+take_empty:
+  %current_used = load i64, i64* %used_ptr
+  %new_used = add i64 %current_used, 1
+  store i64 %new_used, i64* %used_ptr
+  br label %fill
+
+fill:
+  %len_ptr = getelementptr inbounds %flash.map, %flash.map* %map, i32 0, i32 0
+  %len = load i64, i64* %len_ptr
+  %new_len = add i64 %len, 1
+  store i64 %new_len, i64* %len_ptr
+  store i64 1, i64* %slot
+  %key_ptr = getelementptr inbounds i64, i64* %slot, i64 1
+  store i64 %key, i64* %key_ptr
+  %new_value = call i8* @flash_map_value(i8* %raw, i64 %index)
+  ret i8* %new_value
+}
+
+; flash_map_remove deletes the entry for %key and reports whether there was
+; one.
+define i1 @flash_map_remove(i8* %raw, i64 %key) {
+entry:
+  %result = call i64 @flash_map_probe(i8* %raw, i64 %key)
+  %found = icmp sge i64 %result, 0
+  br i1 %found, label %present, label %missing
+
+present:
+  %slot = call i64* @flash_map_slot(i8* %raw, i64 %result)
+  store i64 2, i64* %slot
+  %map = bitcast i8* %raw to %flash.map*
+  %len_ptr = getelementptr inbounds %flash.map, %flash.map* %map, i32 0, i32 0
+  %len = load i64, i64* %len_ptr
+  %new_len = sub i64 %len, 1
+  store i64 %new_len, i64* %len_ptr
+  ret i1 true
+
+missing:
+  ret i1 false
+}
+
+; flash_map_probe returns the index of the slot holding %key or, when there
+; is none, -1 - i where i is the slot an insertion should use.
+define internal i64 @flash_map_probe(i8* %raw, i64 %key) {
+entry:
+  %map = bitcast i8* %raw to %flash.map*
+  %capacity_ptr = getelementptr inbounds %flash.map, %flash.map* %map, i32 0, i32 2
+  %capacity = load i64, i64* %capacity_ptr
+  %key_is_string_ptr = getelementptr inbounds %flash.map, %flash.map* %map, i32 0, i32 3
+  %key_is_string = load i64, i64* %key_is_string_ptr
+  %hash = call i64 @flash_map_hash(i64 %key, i64 %key_is_string)
+  %mask = sub i64 %capacity, 1
+  %start = and i64 %hash, %mask
+  br label %loop
+
+loop:
+  %index = phi i64 [ %start, %entry ], [ %next, %advance ]
+  %reusable = phi i64 [ -1, %entry ], [ %next_reusable, %advance ]
+  %slot = call i64* @flash_map_slot(i8* %raw, i64 %index)
+  %state = load i64, i64* %slot
+  switch i64 %state, label %removed [
+    i64 0, label %empty
+    i64 1, label %occupied
+  ]
+
+empty:
+  %has_reusable = icmp sge i64 %reusable, 0
+  %target = select i1 %has_reusable, i64 %reusable, i64 %index
+  %missing = sub i64 -1, %target
+  ret i64 %missing
+
+occupied:
+  %key_ptr = getelementptr inbounds i64, i64* %slot, i64 1
+  %stored = load i64, i64* %key_ptr
+  %equal = call i1 @flash_map_keys_equal(i64 %stored, i64 %key, i64 %key_is_string)
+  br i1 %equal, label %found, label %advance
+
+found:
+  ret i64 %index
+
+removed:
+  %first_removed = icmp slt i64 %reusable, 0
+  %removed_reusable = select i1 %first_removed, i64 %index, i64 %reusable
+  br label %advance
+
+advance:
+  %next_reusable = phi i64 [ %reusable, %occupied ], [ %removed_reusable, %removed ]
+  %incremented = add i64 %index, 1
+  %next = and i64 %incremented, %mask
+  br label %loop
+}
+
+; flash_map_grow doubles the capacity and moves every entry into the new
+; slots, dropping removed entries.
+define internal void @flash_map_grow(i8* %raw) {
+entry:
+  %map = bitcast i8* %raw to %flash.map*
+  %capacity_ptr = getelementptr inbounds %flash.map, %flash.map* %map, i32 0, i32 2
+  %capacity = load i64, i64* %capacity_ptr
+  %slot_size_ptr = getelementptr inbounds %flash.map, %flash.map* %map, i32 0, i32 4
+  %slot_size = load i64, i64* %slot_size_ptr
+  %slots_ptr = getelementptr inbounds %flash.map, %flash.map* %map, i32 0, i32 5
+  %old_slots = load i8*, i8** %slots_ptr
+  %new_capacity = mul i64 %capacity, 2
+  %new_slots = call i8* @calloc(i64 %new_capacity, i64 %slot_size)
+  store i64 %new_capacity, i64* %capacity_ptr
+  store i8* %new_slots, i8** %slots_ptr
+  %words = udiv i64 %slot_size, 8
+  br label %loop
+
+loop:
+  %index = phi i64 [ 0, %entry ], [ %next, %advance ]
+  %more = icmp ult i64 %index, %capacity
+  br i1 %more, label %body, label %done
+
+body:
+  %old_offset = mul i64 %index, %slot_size
+  %old_byte = getelementptr inbounds i8, i8* %old_slots, i64 %old_offset
+  %old = bitcast i8* %old_byte to i64*
+  %state = load i64, i64* %old
+  %occupied = icmp eq i64 %state, 1
+  br i1 %occupied, label %move, label %advance
+
+move:
+  %key_ptr = getelementptr inbounds i64, i64* %old, i64 1
+  %key = load i64, i64* %key_ptr
+  %result = call i64 @flash_map_probe(i8* %raw, i64 %key)
+  %new_index = sub i64 -1, %result
+  %new = call i64* @flash_map_slot(i8* %raw, i64 %new_index)
+  br label %copy
+
+copy:
+  %word = phi i64 [ 0, %move ], [ %next_word, %copy_word ]
+  %copy_more = icmp ult i64 %word, %words
+  br i1 %copy_more, label %copy_word, label %advance
+
+copy_word:
+  %from = getelementptr inbounds i64, i64* %old, i64 %word
+  %to = getelementptr inbounds i64, i64* %new, i64 %word
+  %value = load i64, i64* %from
+  store i64 %value, i64* %to
+  %next_word = add i64 %word, 1
+  br label %copy
+
+advance:
+  %next = add i64 %index, 1
+  br label %loop
+
+done:
+  call void @free(i8* %old_slots)
+  %len_ptr = getelementptr inbounds %flash.map, %flash.map* %map, i32 0, i32 0
+  %len = load i64, i64* %len_ptr
+  %used_ptr = getelementptr inbounds %flash.map, %flash.map* %map, i32 0, i32 1
+  store i64 %len, i64* %used_ptr
+  ret void
+}
+
+define internal i64* @flash_map_slot(i8* %raw, i64 %index) {
+entry:
+  %map = bitcast i8* %raw to %flash.map*
+  %slot_size_ptr = getelementptr inbounds %flash.map, %flash.map* %map, i32 0, i32 4
+  %slot_size = load i64, i64* %slot_size_ptr
+  %slots_ptr = getelementptr inbounds %flash.map, %flash.map* %map, i32 0, i32 5
+  %slots = load i8*, i8** %slots_ptr
+  %offset = mul i64 %index, %slot_size
+  %byte = getelementptr inbounds i8, i8* %slots, i64 %offset
+  %slot = bitcast i8* %byte to i64*
+  ret i64* %slot
+}
+
+define internal i8* @flash_map_value(i8* %raw, i64 %index) {
+entry:
+  %slot = call i64* @flash_map_slot(i8* %raw, i64 %index)
+  %value_ptr = getelementptr inbounds i64, i64* %slot, i64 2
+  %value = bitcast i64* %value_ptr to i8*
+  ret i8* %value
+}
+
+; Integers are mixed with a multiplicative hash, strings with FNV-1a.
+define internal i64 @flash_map_hash(i64 %key, i64 %key_is_string) {
+entry:
+  %is_string = icmp ne i64 %key_is_string, 0
+  br i1 %is_string, label %string, label %integer
+
+integer:
+  %product = mul i64 %key, -7046029254386353131
+  %high = lshr i64 %product, 29
+  %mixed = xor i64 %product, %high
+  ret i64 %mixed
+
+string:
+  %start = inttoptr i64 %key to i8*
+  br label %loop
+
+loop:
+  %byte_ptr = phi i8* [ %start, %string ], [ %next_ptr, %body ]
+  %hash = phi i64 [ -3750763034362895579, %string ], [ %next_hash, %body ]
+  %byte = load i8, i8* %byte_ptr
+  %at_end = icmp eq i8 %byte, 0
+  br i1 %at_end, label %done, label %body
+
+body:
+  %widened = zext i8 %byte to i64
+  %xored = xor i64 %hash, %widened
+  %next_hash = mul i64 %xored, 1099511628211
+  %next_ptr = getelementptr inbounds i8, i8* %byte_ptr, i64 1
+  br label %loop
+
+done:
+  ret i64 %hash
+}
+
+define internal i1 @flash_map_keys_equal(i64 %left, i64 %right, i64 %key_is_string) {
+entry:
+  %same = icmp eq i64 %left, %right
+  br i1 %same, label %equal, label %check
+
+check:
+  %is_string = icmp ne i64 %key_is_string, 0
+  br i1 %is_string, label %string, label %different
+
+string:
+  %left_start = inttoptr i64 %left to i8*
+  %right_start = inttoptr i64 %right to i8*
+  br label %loop
+
+loop:
+  %left_ptr = phi i8* [ %left_start, %string ], [ %left_next, %advance ]
+  %right_ptr = phi i8* [ %right_start, %string ], [ %right_next, %advance ]
+  %left_byte = load i8, i8* %left_ptr
+  %right_byte = load i8, i8* %right_ptr
+  %bytes_equal = icmp eq i8 %left_byte, %right_byte
+  br i1 %bytes_equal, label %same_byte, label %different
+
+same_byte:
+  %at_end = icmp eq i8 %left_byte, 0
+  br i1 %at_end, label %equal, label %advance
+
+advance:
+  %left_next = getelementptr inbounds i8, i8* %left_ptr, i64 1
+  %right_next = getelementptr inbounds i8, i8* %right_ptr, i64 1
+  br label %loop
+
+equal:
+  ret i1 true
+
+different:
+  ret i1 false
+}
@@ -11,6 +11,7 @@ pub mod test_util;
 pub mod const_value;
 pub mod scope;
 pub mod converter;
+pub mod runtime;
 /*use std::collections::HashMap;
 
 use inkwell::builder::Builder;
@@ -71,3 +71,121 @@ pub fn build_position_at_end(builder: *mut LLVMBuilder, block: *mut LLVMBasicBlo
 pub fn get_u64_from_llvm_value(llvm_const_value: *mut LLVMValue) -> u64 {
     unsafe { LLVMConstIntGetZExtValue(llvm_const_value) }
 }
+
+#[allow(dead_code)]
+pub fn get_insert_block(builder: *mut LLVMBuilder) -> *mut LLVMBasicBlock {
+    unsafe { LLVMGetInsertBlock(builder) }
+}
+
+#[allow(dead_code)]
+pub fn build_position_before(builder: *mut LLVMBuilder, instruction: *mut LLVMValue) {
+    unsafe {
+        LLVMPositionBuilderBefore(builder, instruction);
+    };
+}
+
+#[allow(dead_code)]
+pub fn build_unreachable(builder: *mut LLVMBuilder) -> *mut LLVMValue {
+    unsafe { LLVMBuildUnreachable(builder) }
+}
+
+#[allow(dead_code)]
+pub fn build_sext(
+    builder: *mut LLVMBuilder,
+    value: *mut LLVMValue,
+    dest_type: *mut LLVMType,
+    name: &str,
+) -> *mut LLVMValue {
+    unsafe { LLVMBuildSExt(builder, value, dest_type, c_string!(name).as_ptr()) }
+}
+
+#[allow(dead_code)]
+pub fn build_trunc(
+    builder: *mut LLVMBuilder,
+    value: *mut LLVMValue,
+    dest_type: *mut LLVMType,
+    name: &str,
+) -> *mut LLVMValue {
+    unsafe { LLVMBuildTrunc(builder, value, dest_type, c_string!(name).as_ptr()) }
+}
+
+#[allow(dead_code)]
+pub fn build_signed_int_to_float(
+    builder: *mut LLVMBuilder,
+    value: *mut LLVMValue,
+    dest_type: *mut LLVMType,
+    name: &str,
+) -> *mut LLVMValue {
+    unsafe { LLVMBuildSIToFP(builder, value, dest_type, c_string!(name).as_ptr()) }
+}
+
+#[allow(dead_code)]
+pub fn build_unsigned_int_to_float(
+    builder: *mut LLVMBuilder,
+    value: *mut LLVMValue,
+    dest_type: *mut LLVMType,
+    name: &str,
+) -> *mut LLVMValue {
+    unsafe { LLVMBuildUIToFP(builder, value, dest_type, c_string!(name).as_ptr()) }
+}
+
+#[allow(dead_code)]
+pub fn build_float_to_signed_int(
+    builder: *mut LLVMBuilder,
+    value: *mut LLVMValue,
+    dest_type: *mut LLVMType,
+    name: &str,
+) -> *mut LLVMValue {
+    unsafe { LLVMBuildFPToSI(builder, value, dest_type, c_string!(name).as_ptr()) }
+}
+
+#[allow(dead_code)]
+pub fn build_float_to_unsigned_int(
+    builder: *mut LLVMBuilder,
+    value: *mut LLVMValue,
+    dest_type: *mut LLVMType,
+    name: &str,
+) -> *mut LLVMValue {
+    unsafe { LLVMBuildFPToUI(builder, value, dest_type, c_string!(name).as_ptr()) }
+}
+
+#[allow(dead_code)]
+pub fn build_zext(
+    builder: *mut LLVMBuilder,
+    value: *mut LLVMValue,
+    dest_type: *mut LLVMType,
+    name: &str,
+) -> *mut LLVMValue {
+    unsafe { LLVMBuildZExt(builder, value, dest_type, c_string!(name).as_ptr()) }
+}
+
+#[allow(dead_code)]
+pub fn build_ptr_to_int(
+    builder: *mut LLVMBuilder,
+    value: *mut LLVMValue,
+    dest_type: *mut LLVMType,
+    name: &str,
+) -> *mut LLVMValue {
+    unsafe { LLVMBuildPtrToInt(builder, value, dest_type, c_string!(name).as_ptr()) }
+}
+
+// build_in_bounds_gep returns a pointer into the `llvm_type` value at `pointer`.
+#[allow(dead_code)]
+pub fn build_in_bounds_gep(
+    builder: *mut LLVMBuilder,
+    llvm_type: *mut LLVMType,
+    pointer: *mut LLVMValue,
+    indices: &mut [*mut LLVMValue],
+    name: &str,
+) -> *mut LLVMValue {
+    unsafe {
+        LLVMBuildInBoundsGEP2(
+            builder,
+            llvm_type,
+            pointer,
+            indices.as_mut_ptr(),
+            indices.len() as u32,
+            c_string!(name).as_ptr(),
+        )
+    }
+}
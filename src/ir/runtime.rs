@@ -0,0 +1,90 @@
+use std::ffi::CString;
+
+use llvm_sys::core::*;
+use llvm_sys::ir_reader::*;
+use llvm_sys::linker::*;
+use llvm_sys::*;
+
+use crate::ir::creator::*;
+use crate::ir::function::*;
+use crate::c_string;
+use crate::string_from_raw;
+
+const MAP_RUNTIME: &str = include_str!("map_runtime.ll");
+
+const MAP_FUNCTIONS: [&str; 5] = [
+    "flash_map_new",
+    "flash_map_len",
+    "flash_map_find",
+    "flash_map_insert",
+    "flash_map_remove",
+];
+
+// Links the hash table behind `map[K]V` into the module, once per module,
+// and registers its entry points as built-ins.
+pub fn link_map_runtime(lc: &mut LLVMCreator) {
+    if lc.built_ins.contains_key(MAP_FUNCTIONS[0]) {
+        return;
+    }
+    let runtime = parse_runtime(lc.module, MAP_RUNTIME, "map_runtime");
+    let failed = unsafe { LLVMLinkModules2(lc.module, runtime) };
+    if failed != 0 {
+        panic!("failed to link the map runtime");
+    }
+    for name in MAP_FUNCTIONS {
+        lc.built_ins.insert(name, get_named_function(lc.module, name));
+    }
+}
+
+fn parse_runtime(module: *mut LLVMModule, source: &str, name: &str) -> *mut LLVMModule {
+    let buffer_name = c_string!(name);
+    let mut runtime = std::ptr::null_mut();
+    let mut error = std::ptr::null_mut();
+    let failed = unsafe {
+        let buffer = LLVMCreateMemoryBufferWithMemoryRangeCopy(
+            source.as_ptr() as *const _,
+            source.len(),
+            buffer_name.as_ptr(),
+        );
+        LLVMParseIRInContext(LLVMGetModuleContext(module), buffer, &mut runtime, &mut error)
+    };
+    if failed != 0 {
+        panic!("failed to parse {}: {}", name, string_from_raw!(error));
+    }
+    runtime
+}
+
+#[test]
+fn map_runtime() {
+    use crate::ir::arithmetic::*;
+    use crate::ir::const_value::*;
+    use crate::ir::llvm_type::*;
+    use crate::ir::operate::*;
+    use crate::ir::test_util::*;
+
+    let mut lc = LLVMCreator::new("test_module");
+    link_map_runtime(&mut lc);
+    let main = setup_main(&mut lc);
+    let call = |lc: &LLVMCreator, name: &str, args: Vec<*mut LLVMValue>| {
+        let function = lc.built_ins[name];
+        call_function(lc.builder, get_function_type(function), function, args, "")
+    };
+
+    // Insert enough keys to make the table grow, then remove one.
+    let map = call(&lc, "flash_map_new", vec![const_int(int64_type(), 0), const_int(int64_type(), 4)]);
+    for key in 0..20 {
+        let value = call(&lc, "flash_map_insert", vec![map, const_int(int64_type(), key * 7)]);
+        let value = cast_type(lc.builder, value, pointer_to(int32_type()), "");
+        build_store(lc.builder, const_int(int32_type(), key), value);
+    }
+    call(&lc, "flash_map_remove", vec![map, const_int(int64_type(), 0)]);
+
+    let value = call(&lc, "flash_map_find", vec![map, const_int(int64_type(), 133)]);
+    let value = cast_type(lc.builder, value, pointer_to(int32_type()), "");
+    let value = build_load(lc.builder, int32_type(), value, "");
+    let len = call(&lc, "flash_map_len", vec![map]);
+    let len = build_trunc(lc.builder, len, int32_type(), "");
+    build_ret(lc.builder, add_variable(lc.builder, value, len, ""));
+
+    assert_eq!(execute_test_ir_function(lc.module, main), 19 + 19);
+}
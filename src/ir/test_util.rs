@@ -1,6 +1,7 @@
 use std::ffi::CString;
 
 use llvm_sys::execution_engine::*;
+use llvm_sys::target::*;
 use llvm_sys::*;
 
 use crate::ir::block::*;
@@ -13,21 +14,26 @@ use crate::string_from_raw;
 
 const LLVM_ERROR: i32 = 1;
 
+// Modules run under MCJIT, which resolves external functions such as calloc
+// by name. The interpreter caches them by the address of their declaration,
+// which a later module may reuse for a different function once the engine is
+// disposed.
 #[allow(dead_code)]
 fn create_llvm_engine(module: *mut LLVMModule) -> LLVMExecutionEngineRef {
-    let error = 0 as *mut i8;
+    validate_module(module);
+    let mut error = std::ptr::null_mut();
     let mut engine: LLVMExecutionEngineRef = 0 as LLVMExecutionEngineRef;
     let ok = unsafe {
-        let mut error = 0 as *mut i8;
         let buf: *mut *mut i8 = &mut error;
         let engine_ref: *mut LLVMExecutionEngineRef = &mut engine;
-        LLVMLinkInInterpreter();
-        LLVMCreateInterpreterForModule(engine_ref, module, buf)
+        LLVMLinkInMCJIT();
+        LLVM_InitializeNativeTarget();
+        LLVM_InitializeNativeAsmPrinter();
+        LLVMCreateJITCompilerForModule(engine_ref, module, 0, buf)
     };
     if ok == LLVM_ERROR {
         panic!("Execution error: {}", string_from_raw!(error));
     }
-    validate_module(module);
     engine
 }
 
@@ -0,0 +1,67 @@
+use std::fmt;
+
+use super::Span;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LexErrorKind {
+    UnexpectedChar(char),
+    UnterminatedString,
+    UnterminatedChar,
+    EmptyChar,
+    CharTooLong,
+    UnterminatedComment,
+    InvalidEscape(char),
+    InvalidUnicodeEscape,
+    InvalidDigit { digit: char, radix: u32 },
+    InvalidSuffix(String),
+    MalformedNumber,
+    IntegerTooLarge,
+    FloatOutOfRange,
+}
+
+// LexError describes input the lexer could not turn into a token. The lexer
+// reports it as a Token::Illegal and carries on after the offending text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LexError {
+    pub kind: LexErrorKind,
+    pub text: String,
+    pub span: Span,
+}
+
+impl LexError {
+    pub fn new(kind: LexErrorKind) -> Self {
+        LexError {
+            kind,
+            text: String::new(),
+            span: Span::default(),
+        }
+    }
+}
+
+impl fmt::Display for LexErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LexErrorKind::UnexpectedChar('.') => write!(f, "unexpected character `.`, expected a digit on both sides of a decimal point"),
+            LexErrorKind::UnexpectedChar(chr) if chr.is_control() => write!(f, "unexpected character {:?}", chr),
+            LexErrorKind::UnexpectedChar(chr) => write!(f, "unexpected character `{}`", chr),
+            LexErrorKind::UnterminatedString => write!(f, "unterminated string, expected a closing `\"`"),
+            LexErrorKind::UnterminatedChar => write!(f, "unterminated character literal, expected a closing `'`"),
+            LexErrorKind::EmptyChar => write!(f, "empty character literal"),
+            LexErrorKind::CharTooLong => write!(f, "character literal holds more than one character, use `\"` for strings"),
+            LexErrorKind::UnterminatedComment => write!(f, "unterminated block comment, expected `*/`"),
+            LexErrorKind::InvalidEscape(chr) => write!(f, "unknown escape sequence `\\{}`", chr),
+            LexErrorKind::InvalidUnicodeEscape => write!(f, "invalid unicode escape, expected `\\u{{...}}` with 1 to 6 hex digits"),
+            LexErrorKind::InvalidDigit { digit, radix } => write!(f, "invalid digit `{}` in a base {} literal", digit, radix),
+            LexErrorKind::InvalidSuffix(suffix) => write!(f, "unknown integer suffix `{}`", suffix),
+            LexErrorKind::MalformedNumber => write!(f, "malformed number"),
+            LexErrorKind::IntegerTooLarge => write!(f, "integer literal does not fit in 64 bits"),
+            LexErrorKind::FloatOutOfRange => write!(f, "float literal is out of range"),
+        }
+    }
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.span, self.kind)
+    }
+}
@@ -1,83 +1,337 @@
+use std::fmt;
 use std::io::Read;
+use std::rc::Rc;
 
-#[derive(Debug, Eq, PartialEq, Clone)]
+use unicode_xid::UnicodeXID;
+
+pub mod span;
+pub use span::*;
+pub mod trivia;
+pub use trivia::*;
+pub mod error;
+pub use error::*;
+
+#[derive(Debug, PartialEq, Clone)]
 pub enum Token {
-    Illegal,
+    Illegal(LexError),
     Eof,
 
     // Keywords
     Dis,
+    Pub,
+    Import,
+    Struct,
+    Enum,
+    Match,
     Let,
     Func,
     If,
     Else,
     For,
+    While,
+    Break,
+    Continue,
     True,
     False,
     Return,
     Run,
+    As,
 
     // Idents & Literals
     Ident(String),
-    Int(i64),
+    Int(u64, Option<IntKind>),
+    Float(f64),
     Bool(bool),
+    Char(char),
+    String(String),
+    DocComment(String),
 
 
     // types
     IntType,
+    SizedIntType(IntKind),
+    FloatType,
     BoolType,
+    CharType,
+    StringType,
+    VoidType,
+    MapType,
 
     // Operators
     Assign,
     Plus,
     Minus,
+    Asterisk,
+    Slash,
+    Percent,
     And,
+    Or,
+    Ampersand,
+    Pipe,
+    Caret,
+    ShiftLeft,
+    ShiftRight,
     Equal,
     NotEqual,
     Bang,
     GreaterThan,
     LessThan,
+    GreaterEqual,
+    LessEqual,
+
+    // Compound assignments
+    PlusAssign,
+    MinusAssign,
+    AsteriskAssign,
+    SlashAssign,
+    PercentAssign,
+    AmpersandAssign,
+    PipeAssign,
+    CaretAssign,
+    ShiftLeftAssign,
+    ShiftRightAssign,
 
     // Delimeters
     Comma,
     Semicolon,
     Colon,
+    DoubleColon,
+    FatArrow,
+    Dot,
 
     LParen,
     RParen,
     LBrace,
-    RBrace
+    RBrace,
+    LBracket,
+    RBracket,
+}
+
+// Tokens display as they would be written in source, for diagnostics.
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Illegal(error) => write!(f, "{}", error.text),
+            Token::Eof => write!(f, "end of file"),
+            Token::Dis => write!(f, "dis"),
+            Token::Pub => write!(f, "pub"),
+            Token::Import => write!(f, "import"),
+            Token::Struct => write!(f, "struct"),
+            Token::Enum => write!(f, "enum"),
+            Token::Match => write!(f, "match"),
+            Token::Let => write!(f, "let"),
+            Token::Func => write!(f, "func"),
+            Token::If => write!(f, "if"),
+            Token::Else => write!(f, "else"),
+            Token::For => write!(f, "for"),
+            Token::While => write!(f, "while"),
+            Token::Break => write!(f, "break"),
+            Token::Continue => write!(f, "continue"),
+            Token::True => write!(f, "true"),
+            Token::False => write!(f, "false"),
+            Token::Return => write!(f, "return"),
+            Token::Run => write!(f, "run"),
+            Token::As => write!(f, "as"),
+            Token::Ident(name) => write!(f, "{}", name),
+            Token::Int(value, None) => write!(f, "{}", value),
+            Token::Int(value, Some(kind)) => write!(f, "{}{}", value, kind.name()),
+            Token::Float(value) => write!(f, "{:?}", value),
+            Token::Bool(value) => write!(f, "{}", value),
+            Token::Char(value) => write!(f, "{:?}", value),
+            Token::String(value) => write!(f, "{:?}", value),
+            Token::DocComment(text) => write!(f, "/// {}", text),
+            Token::IntType => write!(f, "int"),
+            Token::SizedIntType(kind) => write!(f, "{}", kind.name()),
+            Token::FloatType => write!(f, "float"),
+            Token::BoolType => write!(f, "bool"),
+            Token::CharType => write!(f, "char"),
+            Token::StringType => write!(f, "string"),
+            Token::VoidType => write!(f, "void"),
+            Token::MapType => write!(f, "map"),
+            Token::Assign => write!(f, "="),
+            Token::Plus => write!(f, "+"),
+            Token::Minus => write!(f, "-"),
+            Token::Asterisk => write!(f, "*"),
+            Token::Slash => write!(f, "/"),
+            Token::Percent => write!(f, "%"),
+            Token::And => write!(f, "&&"),
+            Token::Or => write!(f, "||"),
+            Token::Ampersand => write!(f, "&"),
+            Token::Pipe => write!(f, "|"),
+            Token::Caret => write!(f, "^"),
+            Token::ShiftLeft => write!(f, "<<"),
+            Token::ShiftRight => write!(f, ">>"),
+            Token::Equal => write!(f, "=="),
+            Token::NotEqual => write!(f, "!="),
+            Token::Bang => write!(f, "!"),
+            Token::GreaterThan => write!(f, ">"),
+            Token::LessThan => write!(f, "<"),
+            Token::GreaterEqual => write!(f, ">="),
+            Token::LessEqual => write!(f, "<="),
+            Token::PlusAssign => write!(f, "+="),
+            Token::MinusAssign => write!(f, "-="),
+            Token::AsteriskAssign => write!(f, "*="),
+            Token::SlashAssign => write!(f, "/="),
+            Token::PercentAssign => write!(f, "%="),
+            Token::AmpersandAssign => write!(f, "&="),
+            Token::PipeAssign => write!(f, "|="),
+            Token::CaretAssign => write!(f, "^="),
+            Token::ShiftLeftAssign => write!(f, "<<="),
+            Token::ShiftRightAssign => write!(f, ">>="),
+            Token::Comma => write!(f, ","),
+            Token::Semicolon => write!(f, ";"),
+            Token::Colon => write!(f, ":"),
+            Token::DoubleColon => write!(f, "::"),
+            Token::FatArrow => write!(f, "=>"),
+            Token::Dot => write!(f, "."),
+            Token::LParen => write!(f, "("),
+            Token::RParen => write!(f, ")"),
+            Token::LBrace => write!(f, "{{"),
+            Token::RBrace => write!(f, "}}"),
+            Token::LBracket => write!(f, "["),
+            Token::RBracket => write!(f, "]"),
+        }
+    }
+}
+
+// IntKind is the width and signedness of an integer type. `int` is an `I32`.
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Hash)]
+pub enum IntKind {
+    I8,
+    I16,
+    I32,
+    I64,
+    U8,
+    U16,
+    U32,
+    U64,
+}
+
+impl IntKind {
+    pub fn from_name(name: &str) -> Option<IntKind> {
+        match name {
+            "i8" => Some(IntKind::I8),
+            "i16" => Some(IntKind::I16),
+            "i32" => Some(IntKind::I32),
+            "i64" => Some(IntKind::I64),
+            "u8" => Some(IntKind::U8),
+            "u16" => Some(IntKind::U16),
+            "u32" => Some(IntKind::U32),
+            "u64" => Some(IntKind::U64),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            IntKind::I8 => "i8",
+            IntKind::I16 => "i16",
+            IntKind::I32 => "i32",
+            IntKind::I64 => "i64",
+            IntKind::U8 => "u8",
+            IntKind::U16 => "u16",
+            IntKind::U32 => "u32",
+            IntKind::U64 => "u64",
+        }
+    }
+
+    pub fn bits(&self) -> u32 {
+        match self {
+            IntKind::I8 | IntKind::U8 => 8,
+            IntKind::I16 | IntKind::U16 => 16,
+            IntKind::I32 | IntKind::U32 => 32,
+            IntKind::I64 | IntKind::U64 => 64,
+        }
+    }
+
+    pub fn is_signed(&self) -> bool {
+        matches!(self, IntKind::I8 | IntKind::I16 | IntKind::I32 | IntKind::I64)
+    }
+
+    pub fn min_value(&self) -> i128 {
+        if self.is_signed() {
+            -(1i128 << (self.bits() - 1))
+        } else {
+            0
+        }
+    }
+
+    pub fn max_value(&self) -> i128 {
+        if self.is_signed() {
+            (1i128 << (self.bits() - 1)) - 1
+        } else {
+            (1i128 << self.bits()) - 1
+        }
+    }
+
+    pub fn contains(&self, value: i128) -> bool {
+        self.min_value() <= value && value <= self.max_value()
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
 }
 
 pub struct Lexer {
     input: String,
-    bytes: Vec<u8>,
+    file: Rc<str>,
     position: usize,
     read_position: usize,
-    ch: u8,
+    ch: char,
+    line: usize,
+    column: usize,
     ended: bool, 
+    // Set when the input ends inside a block comment; reported by next_token.
+    comment_error: Option<LexError>,
 }
 
+// The lexer walks the input one `char` at a time. `position` and
+// `read_position` are byte offsets of the current and the next char, so spans
+// always fall on code point boundaries; `column` counts chars.
 impl Lexer {
     fn read_char(&mut self) {
-        if self.read_position >= self.input.len() {
-            self.ch = 0;
-            self.ended = true;
-        }else{
-            self.ch = self.bytes[self.read_position];
+        if self.ch == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
         }
         self.position = self.read_position;
-        self.read_position += 1;
+        match self.peek_char() {
+            Some(chr) => {
+                self.ch = chr;
+                self.read_position += chr.len_utf8();
+            }
+            None => {
+                self.ch = '\0';
+                self.ended = true;
+            }
+        }
+    }
+
+    fn peek_char(&self) -> Option<char> {
+        self.input[self.read_position..].chars().next()
     }
 
-    fn peek_char(&mut self) -> Option<u8> {
-        if self.read_position >= self.input.len() {
-            return None
+    fn peek_nth_char(&self, n: usize) -> Option<char> {
+        self.input[self.read_position..].chars().nth(n)
+    }
+
+    // read_operator returns `assign` when the operator is followed by `=`,
+    // consuming it, and `single` otherwise.
+    fn read_operator(&mut self, single: Token, assign: Token) -> Token {
+        if self.next_char_is('=') {
+            self.read_char();
+            assign
+        } else {
+            single
         }
-        return Some(self.bytes[self.read_position])
     }
 
-    fn next_char_is(&mut self, chr: u8) -> bool {
+    fn next_char_is(&self, chr: char) -> bool {
         if let Some(ch) = self.peek_char() {
             if ch == chr {
                 return true;
@@ -86,139 +340,540 @@ impl Lexer {
         return false;
     }
 
-    pub fn next_token(&mut self) -> Token {
-        self.skip_whitespace();
+    pub fn next_token(&mut self) -> SpannedToken {
+        self.skip_whitespace_and_comments();
+        let (start, line, column) = (self.position, self.line, self.column);
+        let span = Span::new(self.file.clone(), start, start, line, column);
+        if let Some(error) = self.comment_error.take() {
+            return SpannedToken { token: Token::Illegal(error), span };
+        }
+
+        let mut token = self.read_token();
+        let span = Span::new(self.file.clone(), start, self.position, line, column);
+        if let Token::Illegal(ref mut error) = token {
+            error.text = self.input[start..self.position].to_string();
+            error.span = span.clone();
+        }
+        SpannedToken { token, span }
+    }
+
+    // next_lossless_token is next_token for tools that need the exact source:
+    // the whitespace and comments skipped before the token are kept as trivia.
+    pub fn next_lossless_token(&mut self) -> LosslessToken {
+        let leading = self.read_trivia();
+        let SpannedToken { token, span } = self.next_token();
+        LosslessToken {
+            leading,
+            token,
+            text: self.input[span.start..span.end].to_string(),
+            span,
+        }
+    }
+
+    fn read_trivia(&mut self) -> Vec<Trivia> {
+        let mut trivia = vec![];
+        loop {
+            let (start, line, column) = (self.position, self.line, self.column);
+            let kind = match self.ch {
+                ' ' | '\t' | '\n' | '\r' => {
+                    while matches!(self.ch, ' ' | '\t' | '\n' | '\r') {
+                        self.read_char();
+                    }
+                    TriviaKind::Whitespace
+                }
+                '/' if self.next_char_is('/') && !self.is_doc_comment() => {
+                    self.skip_line_comment();
+                    TriviaKind::LineComment
+                }
+                '/' if self.next_char_is('*') => {
+                    self.skip_block_comment();
+                    TriviaKind::BlockComment
+                }
+                _ => return trivia,
+            };
+            trivia.push(Trivia {
+                kind,
+                text: self.input[start..self.position].to_string(),
+                span: Span::new(self.file.clone(), start, self.position, line, column),
+            });
+        }
+    }
+
+    fn read_token(&mut self) -> Token {
         let tok = match self.ch {
-            b'=' => {
-                if self.next_char_is(b'=') {
+            '/' if self.is_doc_comment() => {
+                return Token::DocComment(self.read_doc_comment());
+            }
+            '=' => {
+                if self.next_char_is('=') {
                     self.read_char();
                     Token::Equal
+                }else if self.next_char_is('>') {
+                    self.read_char();
+                    Token::FatArrow
                 }else{
                     Token::Assign
                 }
             }
-            b'+' => Token::Plus,
-            b'-' => Token::Minus,
-            b':' => Token::Colon,
-            b'!' => {
-                if self.next_char_is(b'='){
+            '+' => self.read_operator(Token::Plus, Token::PlusAssign),
+            '-' => self.read_operator(Token::Minus, Token::MinusAssign),
+            '*' => self.read_operator(Token::Asterisk, Token::AsteriskAssign),
+            '/' => self.read_operator(Token::Slash, Token::SlashAssign),
+            '%' => self.read_operator(Token::Percent, Token::PercentAssign),
+            '^' => self.read_operator(Token::Caret, Token::CaretAssign),
+            ':' => {
+                if self.next_char_is(':') {
+                    self.read_char();
+                    Token::DoubleColon
+                }else{
+                    Token::Colon
+                }
+            }
+            '.' => Token::Dot,
+            '!' => {
+                if self.next_char_is('='){
                     self.read_char();
                     Token::NotEqual
                 }else{
                     Token::Bang
                 }
             },
-            b'>' => Token::GreaterThan,
-            b'<' => Token::LessThan,
-            b',' => Token::Comma,
-            b'&' => {
-                if self.next_char_is(b'&') {
+            '>' => {
+                if self.next_char_is('>') {
+                    self.read_char();
+                    self.read_operator(Token::ShiftRight, Token::ShiftRightAssign)
+                }else{
+                    self.read_operator(Token::GreaterThan, Token::GreaterEqual)
+                }
+            }
+            '<' => {
+                if self.next_char_is('<') {
+                    self.read_char();
+                    self.read_operator(Token::ShiftLeft, Token::ShiftLeftAssign)
+                }else{
+                    self.read_operator(Token::LessThan, Token::LessEqual)
+                }
+            }
+            ',' => Token::Comma,
+            '&' => {
+                if self.next_char_is('&') {
                     self.read_char();
                     Token::And
                 }else{
-                    Token::Illegal
+                    self.read_operator(Token::Ampersand, Token::AmpersandAssign)
                 }
             }
-            b';' => Token::Semicolon,
-            b'(' => Token::LParen,
-            b')' => Token::RParen,
-            b'{' => Token::LBrace,
-            b'}' => Token::RBrace,
-            b'a'..=b'z' | b'A'..=b'Z' => {
+            '|' => {
+                if self.next_char_is('|') {
+                    self.read_char();
+                    Token::Or
+                }else{
+                    self.read_operator(Token::Pipe, Token::PipeAssign)
+                }
+            }
+            '"' => match self.read_string() {
+                Ok(string) => Token::String(string),
+                Err(kind) => Token::Illegal(LexError::new(kind)),
+            },
+            '\'' => match self.read_char_literal() {
+                Ok(chr) => Token::Char(chr),
+                Err(kind) => Token::Illegal(LexError::new(kind)),
+            },
+            ';' => Token::Semicolon,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '{' => Token::LBrace,
+            '}' => Token::RBrace,
+            '[' => Token::LBracket,
+            ']' => Token::RBracket,
+            chr if is_letter(chr) => {
                 let iden = self.read_identifier();
                 return match iden.as_str() {
                     // Keywords
                     "dis" => Token::Dis,
+                    "pub" => Token::Pub,
+                    "import" => Token::Import,
+                    "struct" => Token::Struct,
+                    "enum" => Token::Enum,
+                    "match" => Token::Match,
                     "func" => Token::Func,
                     "let" => Token::Let,
                     "if" => Token::If,
                     "else" => Token::Else,
                     "for" => Token::For,
+                    "while" => Token::While,
+                    "break" => Token::Break,
+                    "continue" => Token::Continue,
                     "true" => Token::True,
                     "false" => Token::False,
                     "return" => Token::Return,
                     "run" => Token::Run,
+                    "as" => Token::As,
                     "int" => Token::IntType,
+                    "i8" | "i16" | "i32" | "i64" | "u8" | "u16" | "u32" | "u64" => {
+                        Token::SizedIntType(IntKind::from_name(&iden).unwrap())
+                    }
+                    "float" => Token::FloatType,
                     "bool" => Token::BoolType,
+                    "char" => Token::CharType,
+                    "string" => Token::StringType,
+                    "void" => Token::VoidType,
+                    "map" => Token::MapType,
                     _ => Token::Ident(iden)
                 };
             },
-            b'0'..=b'9' => {
-                let int = self.read_number();
-                return Token::Int(int)
+            '0'..='9' => {
+                return self.read_number().unwrap_or_else(|kind| Token::Illegal(LexError::new(kind)));
             }
             _ => {
                 if self.ended {
                     return Token::Eof;
                 }
-                Token::Illegal
+                Token::Illegal(LexError::new(LexErrorKind::UnexpectedChar(self.ch)))
             }
         };
         self.read_char();
         return tok
     }
 
-    fn skip_whitespace(&mut self) {
+    fn skip_whitespace_and_comments(&mut self) {
         loop {
             match self.ch {
-                b' ' | b'\t' | b'\n' | b'\r' => self.read_char(),
+                ' ' | '\t' | '\n' | '\r' => self.read_char(),
+                '/' if self.next_char_is('/') && !self.is_doc_comment() => self.skip_line_comment(),
+                '/' if self.next_char_is('*') => self.skip_block_comment(),
                 _ => return
             }
         }
     }
 
+    // `///` starts a doc comment, but `////` and longer are plain comments.
+    fn is_doc_comment(&self) -> bool {
+        self.ch == '/'
+            && self.next_char_is('/')
+            && self.peek_nth_char(1) == Some('/')
+            && self.peek_nth_char(2) != Some('/')
+    }
+
+    fn skip_line_comment(&mut self) {
+        while !self.ended && self.ch != '\n' {
+            self.read_char();
+        }
+    }
+
+    // Block comments nest, so `/* a /* b */ c */` is a single comment.
+    fn skip_block_comment(&mut self) {
+        let (start, line, column) = (self.position, self.line, self.column);
+        let mut depth = 0;
+        while !self.ended {
+            if self.ch == '/' && self.next_char_is('*') {
+                self.read_char();
+                depth += 1;
+            } else if self.ch == '*' && self.next_char_is('/') {
+                self.read_char();
+                depth -= 1;
+                if depth == 0 {
+                    self.read_char();
+                    return;
+                }
+            }
+            self.read_char();
+        }
+        self.comment_error = Some(LexError {
+            kind: LexErrorKind::UnterminatedComment,
+            text: self.input[start..].to_string(),
+            span: Span::new(self.file.clone(), start, self.input.len(), line, column),
+        });
+    }
+
+    fn read_doc_comment(&mut self) -> String {
+        for _ in 0..3 {
+            self.read_char();
+        }
+        if self.ch == ' ' {
+            self.read_char();
+        }
+        let pos = self.position;
+        self.skip_line_comment();
+        return (&self.input[pos..self.position]).trim_end().to_string()
+    }
+
     fn read_identifier(&mut self) -> String {
         let pos = self.position;
-        while is_letter(self.ch) || is_digit(self.ch) {
+        while is_identifier_continue(self.ch) {
             self.read_char();
         }
         return (&self.input[pos..self.position]).to_string()
     }
 
-    fn read_number(&mut self) -> i64 {
+    // read_string reads a string literal up to its closing quote. A bad
+    // escape does not end the literal, so the rest of it is not lexed as code.
+    fn read_string(&mut self) -> Result<String, LexErrorKind> {
+        let mut string = String::new();
+        let mut error = None;
+        loop {
+            self.read_char();
+            if self.ended {
+                return Err(LexErrorKind::UnterminatedString);
+            }
+            match self.ch {
+                '"' => break,
+                '\\' => {
+                    match self.read_escape() {
+                        Ok(chr) => string.push(chr),
+                        Err(kind) => {
+                            error.get_or_insert(kind);
+                            // A broken `\u{..` escape may stop on the closing quote.
+                            if self.ch == '"' {
+                                break;
+                            }
+                        }
+                    }
+                }
+                chr => string.push(chr),
+            }
+        }
+        match error {
+            Some(kind) => Err(kind),
+            None => Ok(string),
+        }
+    }
+
+    // read_char_literal reads a literal such as `'a'` or `'\n'` up to its
+    // closing quote. An unclosed literal ends at the end of the line.
+    fn read_char_literal(&mut self) -> Result<char, LexErrorKind> {
+        let mut chars = vec![];
+        let mut error = None;
+        loop {
+            if self.peek_char().is_none_or(|chr| chr == '\n') {
+                return Err(LexErrorKind::UnterminatedChar);
+            }
+            self.read_char();
+            match self.ch {
+                '\'' => break,
+                '\\' => match self.read_escape() {
+                    Ok(chr) => chars.push(chr),
+                    Err(kind) => {
+                        error.get_or_insert(kind);
+                        if self.ch == '\'' {
+                            break;
+                        }
+                    }
+                },
+                chr => chars.push(chr),
+            }
+        }
+        if let Some(kind) = error {
+            return Err(kind);
+        }
+        match chars[..] {
+            [chr] => Ok(chr),
+            [] => Err(LexErrorKind::EmptyChar),
+            _ => Err(LexErrorKind::CharTooLong),
+        }
+    }
+
+    // read_escape reads the escape sequence that starts at the current `\`.
+    fn read_escape(&mut self) -> Result<char, LexErrorKind> {
+        self.read_char();
+        match self.ch {
+            'n' => Ok('\n'),
+            't' => Ok('\t'),
+            'r' => Ok('\r'),
+            '0' => Ok('\0'),
+            '"' => Ok('"'),
+            '\'' => Ok('\''),
+            '\\' => Ok('\\'),
+            'u' => self.read_unicode_escape().ok_or(LexErrorKind::InvalidUnicodeEscape),
+            chr => Err(LexErrorKind::InvalidEscape(chr)),
+        }
+    }
+
+    // read_unicode_escape reads the `{XXXX}` part of a `\u{XXXX}` escape.
+    fn read_unicode_escape(&mut self) -> Option<char> {
+        if !self.next_char_is('{') {
+            return None;
+        }
+        self.read_char();
+        let mut digits = String::new();
+        loop {
+            self.read_char();
+            match self.ch {
+                '}' => break,
+                chr if chr.is_ascii_hexdigit() && digits.len() < 6 => digits.push(chr),
+                _ => return None,
+            }
+        }
+        u32::from_str_radix(&digits, 16).ok().and_then(char::from_u32)
+    }
+
+    // read_number reads a number literal such as `42`, `0xff_ff`, `0b1010`,
+    // `0o17`, `255u8` or `2.5e3`, and reports literals that are malformed or
+    // do not fit in 64 bits.
+    fn read_number(&mut self) -> Result<Token, LexErrorKind> {
         let pos = self.position;
-        while is_digit(self.ch) {
+        while is_digit(self.ch) || self.ch == '_' {
             self.read_char();
         }
-        return (&self.input[pos..self.position]).to_string().parse::<i64>().unwrap();
+        // After a dot the number indexes a tuple, so `t.0.1` is two indexes.
+        let after_dot = self.input[..pos].ends_with('.');
+        if !after_dot && (self.at_fraction() || self.at_exponent()) {
+            return self.read_float(pos);
+        }
+
+        while is_identifier_continue(self.ch) {
+            self.read_char();
+        }
+        let literal = &self.input[pos..self.position];
+
+        let (radix, body) = match literal.get(..2) {
+            Some("0x") => (16, &literal[2..]),
+            Some("0o") => (8, &literal[2..]),
+            Some("0b") => (2, &literal[2..]),
+            _ => (10, literal),
+        };
+        let (digits, suffix) = match body.find(['i', 'u']) {
+            Some(index) => {
+                let suffix = &body[index..];
+                match IntKind::from_name(suffix) {
+                    Some(kind) => (&body[..index], Some(kind)),
+                    None => return Err(LexErrorKind::InvalidSuffix(suffix.to_string())),
+                }
+            }
+            None => (body, None),
+        };
+
+        let mut value: u64 = 0;
+        let mut seen_digit = false;
+        for chr in digits.chars() {
+            if chr == '_' {
+                continue;
+            }
+            let digit = match chr.to_digit(radix) {
+                Some(digit) => digit,
+                None if chr.is_ascii_digit() => return Err(LexErrorKind::InvalidDigit { digit: chr, radix }),
+                None => return Err(LexErrorKind::MalformedNumber),
+            };
+            value = value
+                .checked_mul(radix as u64)
+                .and_then(|value| value.checked_add(digit as u64))
+                .ok_or(LexErrorKind::IntegerTooLarge)?;
+            seen_digit = true;
+        }
+        if !seen_digit {
+            return Err(LexErrorKind::MalformedNumber);
+        }
+        Ok(Token::Int(value, suffix))
     }
-}
 
-fn is_letter(chr: u8) -> bool {
-    return match chr {
-        b'a'..=b'z' | b'A'..=b'Z' => true,
-        _ => false
+    // A `.` only starts a fraction when a digit follows it.
+    fn at_fraction(&self) -> bool {
+        self.ch == '.' && self.peek_char().is_some_and(is_digit)
     }
+
+    fn at_exponent(&self) -> bool {
+        if self.ch != 'e' && self.ch != 'E' {
+            return false;
+        }
+        match self.peek_char() {
+            Some('+') | Some('-') => self.peek_nth_char(1).is_some_and(is_digit),
+            Some(chr) => is_digit(chr),
+            None => false,
+        }
+    }
+
+    // read_float reads the rest of a float literal whose integer part starts
+    // at `pos`, e.g. `3.14`, `1e-9` or `6.022_140e23`.
+    fn read_float(&mut self, pos: usize) -> Result<Token, LexErrorKind> {
+        if self.at_fraction() {
+            self.read_char();
+            while is_digit(self.ch) || self.ch == '_' {
+                self.read_char();
+            }
+        }
+        if self.at_exponent() {
+            self.read_char();
+            if self.ch == '+' || self.ch == '-' {
+                self.read_char();
+            }
+            while is_digit(self.ch) || self.ch == '_' {
+                self.read_char();
+            }
+        }
+
+        let literal = self.input[pos..self.position].replace('_', "");
+        if is_identifier_continue(self.ch) || self.ch == '.' {
+            while is_identifier_continue(self.ch) || self.ch == '.' {
+                self.read_char();
+            }
+            return Err(LexErrorKind::MalformedNumber);
+        }
+        let value: f64 = literal.parse().map_err(|_| LexErrorKind::MalformedNumber)?;
+        if !value.is_finite() {
+            return Err(LexErrorKind::FloatOutOfRange);
+        }
+        Ok(Token::Float(value))
+    }
+}
+
+// Identifiers follow Unicode XID rules, and may also start with `_`.
+fn is_letter(chr: char) -> bool {
+    chr == '_' || chr.is_xid_start()
+}
+
+fn is_identifier_continue(chr: char) -> bool {
+    chr.is_xid_continue()
 }
 
-fn is_digit(chr: u8) -> bool {
+fn is_digit(chr: char) -> bool {
     return match chr {
-        b'0'..=b'9' => true,
+        '0'..='9' => true,
         _ => false
     }
 }
 
 // NewLexer returns a lexer that will read
 pub fn new_lexer(str: impl Into<String>) -> Lexer {
+    new_file_lexer("<input>", str)
+}
+
+// NewFileLexer returns a lexer whose spans point into the named file
+pub fn new_file_lexer(file: &str, str: impl Into<String>) -> Lexer {
     let inp = str.into();
     let mut lex = Lexer{
-        bytes: inp.as_bytes().to_vec(),
         input: inp,
+        file: Rc::from(file),
         position: 0,
         read_position: 0,
-        ch: 0,
+        ch: '\0',
+        line: 1,
+        column: 0,
         ended: false,
+        comment_error: None,
     };
     lex.read_char();
     lex
 }
 #[cfg(test)]
 pub mod tests {
-    use super::{new_lexer, Token};
+    use super::{new_file_lexer, new_lexer, IntKind, LexError, LexErrorKind, Span, Token, TriviaKind};
+
+    fn illegal(kind: LexErrorKind) -> Token {
+        Token::Illegal(LexError::new(kind))
+    }
+
+    // without_position drops the text and span of lexical errors so tests can
+    // compare them by kind.
+    fn without_position(token: Token) -> Token {
+        match token {
+            Token::Illegal(error) => illegal(error.kind),
+            token => token,
+        }
+    }
 
     #[test]
     fn test_simple() {
-        let input = "=+(){},;";
+        let input = "=+(){},;[]";
         let tests: Vec<Token> = vec![
             Token::Assign,
             Token::Plus,
@@ -227,13 +882,15 @@ pub mod tests {
             Token::LBrace,
             Token::RBrace,
             Token::Comma,
-            Token::Semicolon
+            Token::Semicolon,
+            Token::LBracket,
+            Token::RBracket,
         ];
 
         let mut lexer = new_lexer(input);
         let mut index = 0;
         for tok in tests {
-            let next_tok = lexer.next_token();
+            let next_tok = lexer.next_token().token;
             if next_tok != tok {
                 panic!("Lexer failed: Expected Token {:?} got token {:?} at index {}", tok, next_tok, index)
             }
@@ -292,9 +949,9 @@ pub mod tests {
             Token::Run,
             Token::Ident("add".to_owned()),
             Token::LParen,
-            Token::Int(20),
+            Token::Int(20, None),
             Token::Comma,
-            Token::Int(30),
+            Token::Int(30, None),
             Token::RParen,
             Token::Semicolon,
             Token::Let,
@@ -303,9 +960,9 @@ pub mod tests {
             Token::Run,
             Token::Ident("add".to_owned()),
             Token::LParen,
-            Token::Int(20),
+            Token::Int(20, None),
             Token::Comma,
-            Token::Int(30),
+            Token::Int(30, None),
             Token::RParen,
             Token::Semicolon,
             Token::RBrace
@@ -314,7 +971,7 @@ pub mod tests {
         let mut lexer = new_lexer(input);
         let mut index = 0;
         for tok in tests {
-            let next_tok = lexer.next_token();
+            let next_tok = lexer.next_token().token;
             if next_tok != tok {
                 panic!("Lexer failed: Expected Token {:?} got token {:?} at index {}", tok, next_tok, index)
             }
@@ -322,4 +979,340 @@ pub mod tests {
         }
         assert!(true);
     }
+
+    #[test]
+    fn test_spans() {
+        let input = "let x = 10;\n  x == y";
+        let tests: Vec<(Token, usize, usize, usize, usize)> = vec![
+            (Token::Let, 0, 3, 1, 1),
+            (Token::Ident("x".to_owned()), 4, 5, 1, 5),
+            (Token::Assign, 6, 7, 1, 7),
+            (Token::Int(10, None), 8, 10, 1, 9),
+            (Token::Semicolon, 10, 11, 1, 11),
+            (Token::Ident("x".to_owned()), 14, 15, 2, 3),
+            (Token::Equal, 16, 18, 2, 5),
+            (Token::Ident("y".to_owned()), 19, 20, 2, 8),
+            (Token::Eof, 20, 20, 2, 9),
+        ];
+
+        let mut lexer = new_file_lexer("main.flash", input);
+        for (tok, start, end, line, column) in tests {
+            let next_tok = lexer.next_token();
+            assert_eq!(next_tok.token, tok);
+            assert_eq!(next_tok.span, Span::new("main.flash".into(), start, end, line, column));
+        }
+        assert_eq!(format!("{}", lexer.next_token().span), "main.flash:2:9");
+    }
+
+    #[test]
+    fn test_comments() {
+        let input = "
+        // a line comment
+        let /* a /* nested */ block */ x = 1; // trailing
+        /// Adds two numbers.
+        ///
+        /// Second paragraph.
+        //// not a doc comment
+        func";
+        let tests: Vec<Token> = vec![
+            Token::Let,
+            Token::Ident("x".to_owned()),
+            Token::Assign,
+            Token::Int(1, None),
+            Token::Semicolon,
+            Token::DocComment("Adds two numbers.".to_owned()),
+            Token::DocComment("".to_owned()),
+            Token::DocComment("Second paragraph.".to_owned()),
+            Token::Func,
+            Token::Eof,
+        ];
+
+        let mut lexer = new_lexer(input);
+        for tok in tests {
+            assert_eq!(lexer.next_token().token, tok);
+        }
+    }
+
+    #[test]
+    fn test_strings() {
+        let input = r#"let s: string = "a \"label\"\n\t\u{1F600}"; "unterminated"#;
+        let tests: Vec<Token> = vec![
+            Token::Let,
+            Token::Ident("s".to_owned()),
+            Token::Colon,
+            Token::StringType,
+            Token::Assign,
+            Token::String("a \"label\"\n\t\u{1F600}".to_owned()),
+            Token::Semicolon,
+            illegal(LexErrorKind::UnterminatedString),
+            Token::Eof,
+        ];
+
+        let mut lexer = new_lexer(input);
+        for tok in tests {
+            assert_eq!(without_position(lexer.next_token().token), tok);
+        }
+
+        let mut lexer = new_lexer(r#""\q" "\u{110000}" "\u{12"; x"#);
+        assert_eq!(without_position(lexer.next_token().token), illegal(LexErrorKind::InvalidEscape('q')));
+        assert_eq!(without_position(lexer.next_token().token), illegal(LexErrorKind::InvalidUnicodeEscape));
+        assert_eq!(without_position(lexer.next_token().token), illegal(LexErrorKind::InvalidUnicodeEscape));
+        assert_eq!(lexer.next_token().token, Token::Semicolon);
+        assert_eq!(lexer.next_token().token, Token::Ident("x".to_owned()));
+    }
+
+    #[test]
+    fn test_chars() {
+        let input = r"let c: char = 'a'; '\n' '\'' '\u{1F600}' 'é' c as u8";
+        let tests: Vec<Token> = vec![
+            Token::Let,
+            Token::Ident("c".to_owned()),
+            Token::Colon,
+            Token::CharType,
+            Token::Assign,
+            Token::Char('a'),
+            Token::Semicolon,
+            Token::Char('\n'),
+            Token::Char('\''),
+            Token::Char('\u{1F600}'),
+            Token::Char('é'),
+            Token::Ident("c".to_owned()),
+            Token::As,
+            Token::SizedIntType(IntKind::U8),
+            Token::Eof,
+        ];
+
+        let mut lexer = new_lexer(input);
+        for tok in tests {
+            assert_eq!(lexer.next_token().token, tok);
+        }
+
+        let malformed = vec![
+            ("''", LexErrorKind::EmptyChar),
+            ("'ab'", LexErrorKind::CharTooLong),
+            (r"'\q'", LexErrorKind::InvalidEscape('q')),
+            ("'a", LexErrorKind::UnterminatedChar),
+        ];
+        for (input, kind) in malformed {
+            let mut lexer = new_lexer(input);
+            assert_eq!(without_position(lexer.next_token().token), illegal(kind), "{}", input);
+            assert_eq!(lexer.next_token().token, Token::Eof, "{}", input);
+        }
+
+        let mut lexer = new_lexer("'a\nx");
+        assert_eq!(without_position(lexer.next_token().token), illegal(LexErrorKind::UnterminatedChar));
+        assert_eq!(lexer.next_token().token, Token::Ident("x".to_owned()));
+    }
+
+    #[test]
+    fn test_unicode() {
+        let input = "let my_var = _tmp1 + größe; /* ünïcödé */ \"héllo, 世界\" // ✓\nπ2 · x";
+        let tests: Vec<(Token, usize, usize)> = vec![
+            (Token::Let, 1, 1),
+            (Token::Ident("my_var".to_owned()), 1, 5),
+            (Token::Assign, 1, 12),
+            (Token::Ident("_tmp1".to_owned()), 1, 14),
+            (Token::Plus, 1, 20),
+            (Token::Ident("größe".to_owned()), 1, 22),
+            (Token::Semicolon, 1, 27),
+            (Token::String("héllo, 世界".to_owned()), 1, 43),
+            (Token::Ident("π2".to_owned()), 2, 1),
+            (illegal(LexErrorKind::UnexpectedChar('·')), 2, 4),
+            (Token::Ident("x".to_owned()), 2, 6),
+            (Token::Eof, 2, 7),
+        ];
+
+        let mut lexer = new_lexer(input);
+        for (tok, line, column) in tests {
+            let next_tok = lexer.next_token();
+            assert_eq!(without_position(next_tok.token.clone()), tok);
+            assert_eq!((next_tok.span.line, next_tok.span.column), (line, column), "{:?}", next_tok.token);
+            assert!(input.is_char_boundary(next_tok.span.start) && input.is_char_boundary(next_tok.span.end));
+        }
+    }
+
+    #[test]
+    fn test_trivia() {
+        let input = "let x = 1; // one\n/* two /* nested */ */\t/// doc\nx";
+        let mut lexer = new_lexer(input);
+        let mut tokens = vec![];
+        loop {
+            let token = lexer.next_lossless_token();
+            let eof = token.token == Token::Eof;
+            tokens.push(token);
+            if eof {
+                break;
+            }
+        }
+
+        let mut text = String::new();
+        for token in &tokens {
+            token.write_to(&mut text);
+        }
+        assert_eq!(text, input);
+
+        let kinds: Vec<TriviaKind> = tokens[5].leading.iter().map(|trivia| trivia.kind).collect();
+        assert_eq!(tokens[5].token, Token::DocComment("doc".to_owned()));
+        assert_eq!(tokens[5].text, "/// doc");
+        assert_eq!(kinds, vec![
+            TriviaKind::Whitespace,
+            TriviaKind::LineComment,
+            TriviaKind::Whitespace,
+            TriviaKind::BlockComment,
+            TriviaKind::Whitespace,
+        ]);
+        assert_eq!(tokens[5].leading[1].text, "// one");
+        assert_eq!(tokens[5].leading[3].span.line, 2);
+    }
+
+    #[test]
+    fn test_operators() {
+        let input = "* / % <= >= < > << >> & | ^ && || += -= *= /= %= &= |= ^= <<= >>= :: => : = a/b";
+        let tests: Vec<Token> = vec![
+            Token::Asterisk,
+            Token::Slash,
+            Token::Percent,
+            Token::LessEqual,
+            Token::GreaterEqual,
+            Token::LessThan,
+            Token::GreaterThan,
+            Token::ShiftLeft,
+            Token::ShiftRight,
+            Token::Ampersand,
+            Token::Pipe,
+            Token::Caret,
+            Token::And,
+            Token::Or,
+            Token::PlusAssign,
+            Token::MinusAssign,
+            Token::AsteriskAssign,
+            Token::SlashAssign,
+            Token::PercentAssign,
+            Token::AmpersandAssign,
+            Token::PipeAssign,
+            Token::CaretAssign,
+            Token::ShiftLeftAssign,
+            Token::ShiftRightAssign,
+            Token::DoubleColon,
+            Token::FatArrow,
+            Token::Colon,
+            Token::Assign,
+            Token::Ident("a".to_owned()),
+            Token::Slash,
+            Token::Ident("b".to_owned()),
+            Token::Eof,
+        ];
+
+        let mut lexer = new_lexer(input);
+        for tok in tests {
+            assert_eq!(lexer.next_token().token, tok);
+        }
+    }
+
+    #[test]
+    fn test_int_literals() {
+        let input = "0 1_000_000 0xff 0xFF_u8 0o17 0b1010_1010 10i64 255u8 18446744073709551615 i64";
+        let tests: Vec<Token> = vec![
+            Token::Int(0, None),
+            Token::Int(1_000_000, None),
+            Token::Int(0xff, None),
+            Token::Int(0xff, Some(IntKind::U8)),
+            Token::Int(0o17, None),
+            Token::Int(0b1010_1010, None),
+            Token::Int(10, Some(IntKind::I64)),
+            Token::Int(255, Some(IntKind::U8)),
+            Token::Int(u64::MAX, None),
+            Token::SizedIntType(IntKind::I64),
+            Token::Eof,
+        ];
+
+        let mut lexer = new_lexer(input);
+        for tok in tests {
+            assert_eq!(lexer.next_token().token, tok);
+        }
+
+        let malformed = vec![
+            ("18446744073709551616", LexErrorKind::IntegerTooLarge),
+            ("0x", LexErrorKind::MalformedNumber),
+            ("0b102", LexErrorKind::InvalidDigit { digit: '2', radix: 2 }),
+            ("12abc", LexErrorKind::MalformedNumber),
+            ("10i7", LexErrorKind::InvalidSuffix("i7".to_owned())),
+            ("0o8", LexErrorKind::InvalidDigit { digit: '8', radix: 8 }),
+        ];
+        for (input, kind) in malformed {
+            let mut lexer = new_lexer(input);
+            assert_eq!(without_position(lexer.next_token().token), illegal(kind), "{}", input);
+            assert_eq!(lexer.next_token().token, Token::Eof, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_float_literals() {
+        let input = "2.75 1e-9 2.5E+3 6.022_140e23 1.0 0.5; 1 . 5 float";
+        let tests: Vec<Token> = vec![
+            Token::Float(2.75),
+            Token::Float(1e-9),
+            Token::Float(2.5e3),
+            Token::Float(6.022140e23),
+            Token::Float(1.0),
+            Token::Float(0.5),
+            Token::Semicolon,
+            Token::Int(1, None),
+            Token::Dot,
+            Token::Int(5, None),
+            Token::FloatType,
+            Token::Eof,
+        ];
+
+        let mut lexer = new_lexer(input);
+        for tok in tests {
+            assert_eq!(without_position(lexer.next_token().token), tok);
+        }
+
+        let malformed = vec![
+            ("1.5f", LexErrorKind::MalformedNumber),
+            ("1e400", LexErrorKind::FloatOutOfRange),
+            ("1.2.3", LexErrorKind::MalformedNumber),
+            ("2e5u8", LexErrorKind::MalformedNumber),
+        ];
+        for (input, kind) in malformed {
+            let mut lexer = new_lexer(input);
+            assert_eq!(without_position(lexer.next_token().token), illegal(kind), "{}", input);
+            assert_eq!(lexer.next_token().token, Token::Eof, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_lex_errors() {
+        let input = "let a = 1 $ 2;\nlet s = \"bad \\q\";\nlet n = 0b12 # x; /* open";
+        let mut lexer = new_file_lexer("main.flash", input);
+        let mut errors = vec![];
+        loop {
+            match lexer.next_token().token {
+                Token::Illegal(error) => errors.push(error),
+                Token::Eof => break,
+                _ => {}
+            }
+        }
+
+        let messages: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+        assert_eq!(messages, vec![
+            "main.flash:1:11: unexpected character `$`",
+            "main.flash:2:9: unknown escape sequence `\\q`",
+            "main.flash:3:9: invalid digit `2` in a base 2 literal",
+            "main.flash:3:14: unexpected character `#`",
+            "main.flash:3:19: unterminated block comment, expected `*/`",
+        ]);
+        assert_eq!(errors[1].text, "\"bad \\q\"");
+        assert_eq!((errors[0].span.start, errors[0].span.end), (10, 11));
+        assert_eq!(errors[4].text, "/* open");
+    }
+
+    #[test]
+    fn test_int_kind_ranges() {
+        assert!(IntKind::I8.contains(-128) && !IntKind::I8.contains(128));
+        assert!(IntKind::U8.contains(255) && !IntKind::U8.contains(-1));
+        assert_eq!(IntKind::I64.max_value(), i64::MAX as i128);
+        assert_eq!(IntKind::U64.max_value(), u64::MAX as i128);
+    }
 }
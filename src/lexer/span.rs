@@ -0,0 +1,48 @@
+use std::fmt;
+use std::rc::Rc;
+
+// Span marks a region of a source file. `start` and `end` are byte offsets,
+// `line` and `column` are 1-based and point at `start`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub file: Rc<str>,
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    pub fn new(file: Rc<str>, start: usize, end: usize, line: usize, column: usize) -> Self {
+        Span {
+            file,
+            start,
+            end,
+            line,
+            column,
+        }
+    }
+
+    // to returns a span running from the start of `self` to the end of `other`.
+    pub fn to(&self, other: &Span) -> Span {
+        Span {
+            file: self.file.clone(),
+            start: self.start,
+            end: other.end.max(self.end),
+            line: self.line,
+            column: self.column,
+        }
+    }
+}
+
+impl Default for Span {
+    fn default() -> Self {
+        Span::new(Rc::from(""), 0, 0, 0, 0)
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
@@ -0,0 +1,37 @@
+use super::{Span, Token};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriviaKind {
+    Whitespace,
+    LineComment,
+    BlockComment,
+}
+
+// Trivia is source text the parser ignores: runs of whitespace and comments
+// other than doc comments.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub text: String,
+    pub span: Span,
+}
+
+// LosslessToken is a token together with its own source text and the trivia
+// in front of it. Concatenating the trivia and text of every token up to and
+// including Eof gives back the input exactly.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LosslessToken {
+    pub leading: Vec<Trivia>,
+    pub token: Token,
+    pub text: String,
+    pub span: Span,
+}
+
+impl LosslessToken {
+    pub fn write_to(&self, out: &mut String) {
+        for trivia in &self.leading {
+            out.push_str(&trivia.text);
+        }
+        out.push_str(&self.text);
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::rc::Rc;
+
+use crate::lexer::{new_file_lexer, Span};
+use crate::parser::{Diagnostic, Parser, Program, StmtKind};
+
+// Module is one parsed source file. `imports` maps the names the file
+// imports to the index of the imported module in the load order.
+#[derive(Debug, Clone)]
+pub struct Module {
+    pub name: String,
+    pub path: PathBuf,
+    pub program: Program,
+    pub imports: Vec<(String, usize)>,
+}
+
+// load reads the program whose main file is at `path`, along with every
+// file it imports. See load_with.
+#[allow(dead_code)]
+pub fn load(path: &Path) -> Result<Vec<Module>, Vec<Diagnostic>> {
+    load_with(path, |path| fs::read_to_string(path))
+}
+
+// load_with parses the main file at `path` and the files it imports,
+// reading them with `read`. Imported paths are relative to the importing
+// file. The modules come back in dependency order, so every module comes
+// after the ones it imports and the main file comes last.
+pub fn load_with(path: &Path, read: impl FnMut(&Path) -> io::Result<String>) -> Result<Vec<Module>, Vec<Diagnostic>> {
+    let mut loader = Loader {
+        read,
+        modules: vec![],
+        loaded: HashMap::new(),
+        stack: vec![],
+        diagnostics: vec![],
+    };
+    let name = module_name(path);
+    loader.load_module(normalize(path), name, None);
+
+    if loader.diagnostics.is_empty() {
+        Ok(loader.modules)
+    } else {
+        Err(loader.diagnostics)
+    }
+}
+
+struct Loader<F: FnMut(&Path) -> io::Result<String>> {
+    read: F,
+    modules: Vec<Module>,
+    // The index of each file loaded, or None for one that could not be read
+    // or parsed, so that its errors are reported once.
+    loaded: HashMap<PathBuf, Option<usize>>,
+    // The files being loaded, each imported by the one before it.
+    stack: Vec<PathBuf>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<F: FnMut(&Path) -> io::Result<String>> Loader<F> {
+    // load_module loads the file at `path` unless it is already loaded, and
+    // returns its index. `span` is the import that asked for it.
+    fn load_module(&mut self, path: PathBuf, name: String, span: Option<&Span>) -> Option<usize> {
+        if let Some(index) = self.loaded.get(&path) {
+            return *index;
+        }
+        if let Some(start) = self.stack.iter().position(|file| *file == path) {
+            let cycle: Vec<String> = self.stack[start..]
+                .iter()
+                .chain(std::iter::once(&path))
+                .map(|file| file.display().to_string())
+                .collect();
+            self.error(format!("import cycle: {}", cycle.join(" -> ")), span, &path);
+            return None;
+        }
+
+        let source = match (self.read)(&path) {
+            Ok(source) => source,
+            Err(err) => {
+                self.error(format!("cannot read `{}`: {}", path.display(), err), span, &path);
+                self.loaded.insert(path, None);
+                return None;
+            }
+        };
+        let mut parser = Parser::new(new_file_lexer(&path.display().to_string(), source));
+        let program = match parser.parse() {
+            Ok(mut programs) => programs.remove(0),
+            Err(diagnostics) => {
+                self.diagnostics.extend(diagnostics);
+                self.loaded.insert(path, None);
+                return None;
+            }
+        };
+
+        self.stack.push(path.clone());
+        let directory = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        let mut imports: Vec<(String, usize)> = vec![];
+        for statement in program.iter() {
+            if let StmtKind::Import { name, path: import } = &statement.kind {
+                if imports.iter().any(|(imported, _)| *imported == name.0) {
+                    self.error(format!("`{}` is already imported", name.0), Some(&statement.span), &path);
+                    continue;
+                }
+                if let Some(index) = self.load_module(normalize(&directory.join(import)), name.0.clone(), Some(&statement.span)) {
+                    imports.push((name.0.clone(), index));
+                }
+            }
+        }
+        self.stack.pop();
+
+        self.modules.push(Module { name, path: path.clone(), program, imports });
+        self.loaded.insert(path, Some(self.modules.len() - 1));
+        Some(self.modules.len() - 1)
+    }
+
+    // The main file has no import to point at, so its errors point at the
+    // start of the file.
+    fn error(&mut self, message: String, span: Option<&Span>, path: &Path) {
+        let span = match span {
+            Some(span) => span.clone(),
+            None => Span::new(Rc::from(path.display().to_string()), 0, 0, 1, 1),
+        };
+        self.diagnostics.push(Diagnostic::new(message, span));
+    }
+}
+
+fn module_name(path: &Path) -> String {
+    path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+// normalize removes `.` and resolves `..` without touching the file system,
+// so that a file reached through different relative paths is loaded once.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if matches!(normalized.components().next_back(), Some(Component::Normal(_))) => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+// load_files loads a program from in-memory files, with `main.flash` as
+// the main file.
+#[cfg(test)]
+pub fn load_files(files: &[(&str, &str)]) -> Result<Vec<Module>, Vec<Diagnostic>> {
+    let files: HashMap<PathBuf, String> = files
+        .iter()
+        .map(|(path, source)| (PathBuf::from(path), source.to_string()))
+        .collect();
+    load_with(Path::new("main.flash"), |path| {
+        files
+            .get(path)
+            .cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "not found"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_errors(files: &[(&str, &str)]) -> Vec<String> {
+        load_files(files).unwrap_err().iter().map(|diagnostic| diagnostic.to_string()).collect()
+    }
+
+    #[test]
+    fn test_load_order() {
+        let modules = load_files(&[
+            ("main.flash", "import \"lib/geo.flash\";\nimport math;\nreturn 0;"),
+            ("math.flash", "pub func sq(x: int): int { return x * x; }"),
+            ("lib/geo.flash", "import \"../math.flash\";\nimport \"./util.flash\";"),
+            ("lib/util.flash", ""),
+        ])
+        .unwrap();
+        let names: Vec<&str> = modules.iter().map(|module| module.name.as_str()).collect();
+        assert_eq!(names, vec!["math", "util", "geo", "main"]);
+        assert_eq!(modules[2].imports, vec![("math".to_owned(), 0), ("util".to_owned(), 1)]);
+        assert_eq!(modules[3].imports, vec![("geo".to_owned(), 2), ("math".to_owned(), 0)]);
+        assert_eq!(modules[2].path, PathBuf::from("lib/geo.flash"));
+    }
+
+    #[test]
+    fn test_load_errors() {
+        let errors = load_errors(&[
+            ("main.flash", "import a;\nimport missing;"),
+            ("a.flash", "import b;"),
+            ("b.flash", "\nimport a;"),
+        ]);
+        assert_eq!(errors, vec![
+            "b.flash:2:1: import cycle: a.flash -> b.flash -> a.flash",
+            "main.flash:2:1: cannot read `missing.flash`: not found",
+        ]);
+
+        let errors = load_errors(&[("main.flash", "import a;\nimport a;\nlet x: int = ;"), ("a.flash", "")]);
+        assert_eq!(errors, vec![
+            "main.flash:3:14: expected an expression, found `;`",
+        ]);
+        let errors = load_errors(&[("main.flash", "import a;\nimport \"a.flash\";"), ("a.flash", "")]);
+        assert_eq!(errors, vec!["main.flash:2:1: `a` is already imported"]);
+
+        // A file that fails is reported once, however many files import it.
+        let errors = load_errors(&[
+            ("main.flash", "import a;\nimport b;\nimport broken;"),
+            ("a.flash", "import broken;\nimport gone;"),
+            ("b.flash", "import broken;\nimport gone;"),
+            ("broken.flash", "let x = ;"),
+        ]);
+        assert_eq!(errors, vec![
+            "broken.flash:1:9: expected an expression, found `;`",
+            "a.flash:2:1: cannot read `gone.flash`: not found",
+        ]);
+    }
+}
@@ -2,6 +2,7 @@ pub mod lexer;
 pub mod ir;
 pub mod parser;
 pub mod codegen;
+pub mod loader;
 
 #[macro_use]
 mod macros;
@@ -1,25 +1,143 @@
+use std::fmt;
+
+use crate::lexer::{IntKind, Span};
+
+pub mod visit;
+pub use visit::*;
+
 pub type Program = Vec<Stmt>;
 pub type BlockStmt = Vec<Stmt>;
 
+// Nodes carry the span they were parsed from. Spans take no part in
+// equality, so two trees compare equal when they have the same shape.
+#[derive(Debug, Clone)]
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub span: Span,
+}
+
+impl Stmt {
+    pub fn new(kind: StmtKind, span: Span) -> Self {
+        Stmt { kind, span }
+    }
+}
+
+impl PartialEq for Stmt {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind
+    }
+}
+
+impl From<StmtKind> for Stmt {
+    fn from(kind: StmtKind) -> Self {
+        Stmt::new(kind, Span::default())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, span: Span) -> Self {
+        Expr { kind, span }
+    }
+}
+
+impl PartialEq for Expr {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind
+    }
+}
+
+impl From<ExprKind> for Expr {
+    fn from(kind: ExprKind) -> Self {
+        Expr::new(kind, Span::default())
+    }
+}
+
 #[derive(PartialEq, Debug, Clone)]
-pub enum Stmt {
-    Let(Ident, Expr, LLVMExpressionType),
+pub enum StmtKind {
+    // The type is None when it is left out and inferred from the value.
+    Let(Ident, Expr, Option<LLVMExpressionType>),
+    // `let (q, r) = divmod(a, b);` binds each element of a tuple. A name of
+    // `_` skips its element.
+    LetTuple(Vec<Ident>, Expr, Option<LLVMExpressionType>),
     Assignment(Ident, Expr),
+    // `op` is the operator of a compound assignment such as `a[i] += y`.
+    // The target is kept once so that it is evaluated once.
+    IndexAssignment {
+        array: Expr,
+        index: Expr,
+        op: Option<Infix>,
+        value: Expr,
+    },
+    FieldAssignment {
+        object: Expr,
+        field: Ident,
+        op: Option<Infix>,
+        value: Expr,
+    },
     Func {
         name: String,
+        doc: Option<String>,
         distributed: bool,
+        // Whether other files can call the function through an import.
+        public: bool,
+        // `func max<T>(a: T, b: T): T`. Inside the function a type
+        // parameter is written like a named type.
+        type_params: Vec<Ident>,
         params: Vec<Ident>,
         param_types: Vec<LLVMExpressionType>,
         return_type: LLVMExpressionType,
         body: Program,
     },
+    // Structs can only be declared at the top level.
+    Struct {
+        name: Ident,
+        fields: Vec<(Ident, LLVMExpressionType)>,
+    },
+    // Like structs, enums can only be declared at the top level. Each
+    // variant lists the types of its payload.
+    Enum {
+        name: Ident,
+        variants: Vec<(Ident, Vec<LLVMExpressionType>)>,
+    },
+    // `import "lib/math.flash";` or `import math;`, which imports
+    // `math.flash`. `name` is the file name without its extension; the
+    // importing file calls the public functions as `math.f()`.
+    Import {
+        name: Ident,
+        path: String,
+    },
     Return(Expr),
     Expr(Expr),
+    // An expression ending a block without a semicolon. Its value is the
+    // value of the block.
+    Tail(Expr),
+    // Loops may be labelled so that `break` and `continue` can name an outer
+    // loop; without a label they apply to the innermost one.
+    While {
+        label: Option<Ident>,
+        cond: Expr,
+        body: BlockStmt,
+    },
+    For {
+        label: Option<Ident>,
+        init: Option<Box<Stmt>>,
+        cond: Option<Expr>,
+        step: Option<Box<Stmt>>,
+        body: BlockStmt,
+    },
+    Break(Option<Ident>),
+    Continue(Option<Ident>),
     Blank,
 }
 
 #[derive(PartialEq, Debug, Clone)]
-pub enum Expr {
+pub enum ExprKind {
     Ident(Ident),
     Literal(Literal),
     Prefix(Prefix, Box<Expr>),
@@ -33,30 +151,120 @@ pub enum Expr {
         func: Box<Expr>,
         args: Vec<Expr>,
     },
-   // Array(Vec<Expr>),
-   // Hash(Vec<(Literal, Expr)>),
-   /* Index {
+    Cast(Box<Expr>, LLVMExpressionType),
+    Array(Vec<Expr>),
+    // `(a, b)`. A tuple has at least two elements; its elements are read
+    // as fields named by their index, `t.0`.
+    Tuple(Vec<Expr>),
+    Hash(LLVMExpressionType, Vec<(Expr, Expr)>),
+    Index {
         array: Box<Expr>,
         index: Box<Expr>,
-    },*/
+    },
+    Struct {
+        name: Ident,
+        fields: Vec<(Ident, Expr)>,
+    },
+    Field {
+        object: Box<Expr>,
+        field: Ident,
+    },
+    // `Shape::Rect(1, 2)`, or `Shape::Empty` for a variant without payload.
+    Variant {
+        name: Ident,
+        variant: Ident,
+        args: Vec<Expr>,
+    },
+    Match {
+        value: Box<Expr>,
+        arms: Vec<MatchArm>,
+    },
+    // An anonymous function, `func(x: int): int { ... }`. It captures the
+    // variables of the enclosing scope it uses.
+    Closure {
+        params: Vec<Ident>,
+        param_types: Vec<LLVMExpressionType>,
+        return_type: LLVMExpressionType,
+        body: BlockStmt,
+    },
+}
+
+// An arm body written as a bare expression is a block holding just its
+// tail.
+#[derive(PartialEq, Debug, Clone)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub guard: Option<Expr>,
+    pub body: BlockStmt,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum Pattern {
+    Wildcard,
+    Binding(Ident),
+    // A literal, or a negated number.
+    Literal(Expr),
+    Variant {
+        name: Ident,
+        variant: Ident,
+        fields: Vec<Pattern>,
+    },
 }
 
 
 #[derive(PartialEq, Clone, Debug)]
 pub enum LLVMExpressionType {
-    Integer,
-//    String(u32),
+    Integer(IntKind),
+    Float,
+    Char,
+    String,
     Boolean,
     Null,
- //   Array(Box<LLVMExpressionType>, u32),
+    Array(Box<LLVMExpressionType>, u32),
+    Map(Box<LLVMExpressionType>, Box<LLVMExpressionType>),
+    // A struct or an enum, by name. The parser cannot tell them apart.
+    Named(String),
+    // `func(int, int): int`, the type of functions and closures taken as
+    // values.
+    Function(Vec<LLVMExpressionType>, Box<LLVMExpressionType>),
+    // `(int, bool)`, held inline like a struct.
+    Tuple(Vec<LLVMExpressionType>),
     Call,
 }
 
+impl fmt::Display for LLVMExpressionType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LLVMExpressionType::Integer(IntKind::I32) => write!(f, "int"),
+            LLVMExpressionType::Integer(kind) => write!(f, "{}", kind.name()),
+            LLVMExpressionType::Float => write!(f, "float"),
+            LLVMExpressionType::Char => write!(f, "char"),
+            LLVMExpressionType::String => write!(f, "string"),
+            LLVMExpressionType::Boolean => write!(f, "bool"),
+            LLVMExpressionType::Null => write!(f, "void"),
+            LLVMExpressionType::Array(element, length) => write!(f, "[{}; {}]", element, length),
+            LLVMExpressionType::Map(key, value) => write!(f, "map[{}]{}", key, value),
+            LLVMExpressionType::Named(name) => write!(f, "{}", name),
+            LLVMExpressionType::Function(params, return_type) => {
+                let params: Vec<String> = params.iter().map(|param| param.to_string()).collect();
+                write!(f, "func({}): {}", params.join(", "), return_type)
+            }
+            LLVMExpressionType::Tuple(elements) => {
+                let elements: Vec<String> = elements.iter().map(|element| element.to_string()).collect();
+                write!(f, "({})", elements.join(", "))
+            }
+            LLVMExpressionType::Call => write!(f, "call"),
+        }
+    }
+}
+
 #[derive(PartialEq, Debug, Clone)]
 pub enum Literal {
-    Int(i64),
+    Int(u64, Option<IntKind>),
+    Float(f64),
     Bool(bool),
-   // String(String),
+    Char(char),
+    String(String),
 }
 
 #[derive(PartialEq, Debug, Eq, Clone)]
@@ -73,17 +281,38 @@ pub enum Prefix {
 pub enum Infix {
     Plus,
     Minus,
+    Multiply,
+    Divide,
+    Modulo,
     Equal,
     NotEqual,
+    LessThan,
+    GreaterThan,
+    LessEqual,
+    GreaterEqual,
+    And,
+    Or,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
 }
 
 #[derive(PartialEq, PartialOrd, Debug, Clone)]
 pub enum Precedence {
     Lowest,
+    LogicalOr,
+    LogicalAnd,
     Equals,
     LessGreater,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Shift,
     Sum,
     Product,
+    Cast,
     Prefix,
     Call,
     Index,
@@ -0,0 +1,380 @@
+use crate::parser::ast::*;
+
+// Visitor walks a tree without changing it. Every method defaults to
+// visiting the children of its node, so a pass overrides the nodes it cares
+// about and calls the matching walk function to keep descending.
+pub trait Visitor {
+    fn visit_program(&mut self, program: &Program) {
+        walk_program(self, program)
+    }
+
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        walk_stmt(self, stmt)
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        walk_expr(self, expr)
+    }
+
+    fn visit_pattern(&mut self, pattern: &Pattern) {
+        walk_pattern(self, pattern)
+    }
+
+    // Types written in the source: annotations, casts, signatures and
+    // declarations. Types are not walked any further.
+    fn visit_type(&mut self, _value_type: &LLVMExpressionType) {}
+}
+
+pub fn walk_program<V: Visitor + ?Sized>(visitor: &mut V, program: &Program) {
+    for stmt in program.iter() {
+        visitor.visit_stmt(stmt);
+    }
+}
+
+pub fn walk_stmt<V: Visitor + ?Sized>(visitor: &mut V, stmt: &Stmt) {
+    match &stmt.kind {
+        StmtKind::Let(_, expr, value_type) | StmtKind::LetTuple(_, expr, value_type) => {
+            if let Some(value_type) = value_type {
+                visitor.visit_type(value_type);
+            }
+            visitor.visit_expr(expr);
+        }
+        StmtKind::Assignment(_, expr) | StmtKind::Return(expr) | StmtKind::Expr(expr) | StmtKind::Tail(expr) => {
+            visitor.visit_expr(expr)
+        }
+        StmtKind::IndexAssignment { array, index, value, .. } => {
+            visitor.visit_expr(array);
+            visitor.visit_expr(index);
+            visitor.visit_expr(value);
+        }
+        StmtKind::FieldAssignment { object, value, .. } => {
+            visitor.visit_expr(object);
+            visitor.visit_expr(value);
+        }
+        StmtKind::Func { param_types, return_type, body, .. } => {
+            param_types.iter().for_each(|param_type| visitor.visit_type(param_type));
+            visitor.visit_type(return_type);
+            visitor.visit_program(body);
+        }
+        StmtKind::Struct { fields, .. } => {
+            fields.iter().for_each(|(_, field_type)| visitor.visit_type(field_type));
+        }
+        StmtKind::Enum { variants, .. } => {
+            for (_, fields) in variants.iter() {
+                fields.iter().for_each(|field_type| visitor.visit_type(field_type));
+            }
+        }
+        StmtKind::While { cond, body, .. } => {
+            visitor.visit_expr(cond);
+            visitor.visit_program(body);
+        }
+        StmtKind::For { init, cond, step, body, .. } => {
+            if let Some(init) = init {
+                visitor.visit_stmt(init);
+            }
+            if let Some(cond) = cond {
+                visitor.visit_expr(cond);
+            }
+            if let Some(step) = step {
+                visitor.visit_stmt(step);
+            }
+            visitor.visit_program(body);
+        }
+        StmtKind::Import { .. } | StmtKind::Break(_) | StmtKind::Continue(_) | StmtKind::Blank => {}
+    }
+}
+
+pub fn walk_expr<V: Visitor + ?Sized>(visitor: &mut V, expr: &Expr) {
+    match &expr.kind {
+        ExprKind::Ident(_) | ExprKind::Literal(_) => {}
+        ExprKind::Prefix(_, right) => visitor.visit_expr(right),
+        ExprKind::Cast(value, target) => {
+            visitor.visit_expr(value);
+            visitor.visit_type(target);
+        }
+        ExprKind::Infix(_, left, right) => {
+            visitor.visit_expr(left);
+            visitor.visit_expr(right);
+        }
+        ExprKind::If { cond, consequence, alternative } => {
+            visitor.visit_expr(cond);
+            visitor.visit_program(consequence);
+            if let Some(alternative) = alternative {
+                visitor.visit_program(alternative);
+            }
+        }
+        ExprKind::Call { func, args } => {
+            visitor.visit_expr(func);
+            args.iter().for_each(|arg| visitor.visit_expr(arg));
+        }
+        ExprKind::Array(elements) | ExprKind::Tuple(elements) | ExprKind::Variant { args: elements, .. } => {
+            elements.iter().for_each(|element| visitor.visit_expr(element));
+        }
+        ExprKind::Hash(map_type, entries) => {
+            visitor.visit_type(map_type);
+            for (key, value) in entries.iter() {
+                visitor.visit_expr(key);
+                visitor.visit_expr(value);
+            }
+        }
+        ExprKind::Index { array, index } => {
+            visitor.visit_expr(array);
+            visitor.visit_expr(index);
+        }
+        ExprKind::Struct { fields, .. } => {
+            fields.iter().for_each(|(_, value)| visitor.visit_expr(value));
+        }
+        ExprKind::Field { object, .. } => visitor.visit_expr(object),
+        ExprKind::Match { value, arms } => {
+            visitor.visit_expr(value);
+            for arm in arms.iter() {
+                visitor.visit_pattern(&arm.pattern);
+                if let Some(guard) = &arm.guard {
+                    visitor.visit_expr(guard);
+                }
+                visitor.visit_program(&arm.body);
+            }
+        }
+        ExprKind::Closure { param_types, return_type, body, .. } => {
+            param_types.iter().for_each(|param_type| visitor.visit_type(param_type));
+            visitor.visit_type(return_type);
+            visitor.visit_program(body);
+        }
+    }
+}
+
+pub fn walk_pattern<V: Visitor + ?Sized>(visitor: &mut V, pattern: &Pattern) {
+    match pattern {
+        Pattern::Wildcard | Pattern::Binding(_) => {}
+        Pattern::Literal(literal) => visitor.visit_expr(literal),
+        Pattern::Variant { fields, .. } => fields.iter().for_each(|field| visitor.visit_pattern(field)),
+    }
+}
+
+// MutVisitor is the rewriting counterpart of Visitor: it walks the same
+// nodes in the same order, handing out mutable references so a pass can
+// change nodes in place.
+pub trait MutVisitor {
+    fn visit_program_mut(&mut self, program: &mut Program) {
+        walk_program_mut(self, program)
+    }
+
+    fn visit_stmt_mut(&mut self, stmt: &mut Stmt) {
+        walk_stmt_mut(self, stmt)
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        walk_expr_mut(self, expr)
+    }
+
+    fn visit_pattern_mut(&mut self, pattern: &mut Pattern) {
+        walk_pattern_mut(self, pattern)
+    }
+
+    fn visit_type_mut(&mut self, _value_type: &mut LLVMExpressionType) {}
+}
+
+pub fn walk_program_mut<V: MutVisitor + ?Sized>(visitor: &mut V, program: &mut Program) {
+    for stmt in program.iter_mut() {
+        visitor.visit_stmt_mut(stmt);
+    }
+}
+
+pub fn walk_stmt_mut<V: MutVisitor + ?Sized>(visitor: &mut V, stmt: &mut Stmt) {
+    match &mut stmt.kind {
+        StmtKind::Let(_, expr, value_type) | StmtKind::LetTuple(_, expr, value_type) => {
+            if let Some(value_type) = value_type {
+                visitor.visit_type_mut(value_type);
+            }
+            visitor.visit_expr_mut(expr);
+        }
+        StmtKind::Assignment(_, expr) | StmtKind::Return(expr) | StmtKind::Expr(expr) | StmtKind::Tail(expr) => {
+            visitor.visit_expr_mut(expr)
+        }
+        StmtKind::IndexAssignment { array, index, value, .. } => {
+            visitor.visit_expr_mut(array);
+            visitor.visit_expr_mut(index);
+            visitor.visit_expr_mut(value);
+        }
+        StmtKind::FieldAssignment { object, value, .. } => {
+            visitor.visit_expr_mut(object);
+            visitor.visit_expr_mut(value);
+        }
+        StmtKind::Func { param_types, return_type, body, .. } => {
+            param_types.iter_mut().for_each(|param_type| visitor.visit_type_mut(param_type));
+            visitor.visit_type_mut(return_type);
+            visitor.visit_program_mut(body);
+        }
+        StmtKind::Struct { fields, .. } => {
+            fields.iter_mut().for_each(|(_, field_type)| visitor.visit_type_mut(field_type));
+        }
+        StmtKind::Enum { variants, .. } => {
+            for (_, fields) in variants.iter_mut() {
+                fields.iter_mut().for_each(|field_type| visitor.visit_type_mut(field_type));
+            }
+        }
+        StmtKind::While { cond, body, .. } => {
+            visitor.visit_expr_mut(cond);
+            visitor.visit_program_mut(body);
+        }
+        StmtKind::For { init, cond, step, body, .. } => {
+            if let Some(init) = init {
+                visitor.visit_stmt_mut(init);
+            }
+            if let Some(cond) = cond {
+                visitor.visit_expr_mut(cond);
+            }
+            if let Some(step) = step {
+                visitor.visit_stmt_mut(step);
+            }
+            visitor.visit_program_mut(body);
+        }
+        StmtKind::Import { .. } | StmtKind::Break(_) | StmtKind::Continue(_) | StmtKind::Blank => {}
+    }
+}
+
+pub fn walk_expr_mut<V: MutVisitor + ?Sized>(visitor: &mut V, expr: &mut Expr) {
+    match &mut expr.kind {
+        ExprKind::Ident(_) | ExprKind::Literal(_) => {}
+        ExprKind::Prefix(_, right) => visitor.visit_expr_mut(right),
+        ExprKind::Cast(value, target) => {
+            visitor.visit_expr_mut(value);
+            visitor.visit_type_mut(target);
+        }
+        ExprKind::Infix(_, left, right) => {
+            visitor.visit_expr_mut(left);
+            visitor.visit_expr_mut(right);
+        }
+        ExprKind::If { cond, consequence, alternative } => {
+            visitor.visit_expr_mut(cond);
+            visitor.visit_program_mut(consequence);
+            if let Some(alternative) = alternative {
+                visitor.visit_program_mut(alternative);
+            }
+        }
+        ExprKind::Call { func, args } => {
+            visitor.visit_expr_mut(func);
+            args.iter_mut().for_each(|arg| visitor.visit_expr_mut(arg));
+        }
+        ExprKind::Array(elements) | ExprKind::Tuple(elements) | ExprKind::Variant { args: elements, .. } => {
+            elements.iter_mut().for_each(|element| visitor.visit_expr_mut(element));
+        }
+        ExprKind::Hash(map_type, entries) => {
+            visitor.visit_type_mut(map_type);
+            for (key, value) in entries.iter_mut() {
+                visitor.visit_expr_mut(key);
+                visitor.visit_expr_mut(value);
+            }
+        }
+        ExprKind::Index { array, index } => {
+            visitor.visit_expr_mut(array);
+            visitor.visit_expr_mut(index);
+        }
+        ExprKind::Struct { fields, .. } => {
+            fields.iter_mut().for_each(|(_, value)| visitor.visit_expr_mut(value));
+        }
+        ExprKind::Field { object, .. } => visitor.visit_expr_mut(object),
+        ExprKind::Match { value, arms } => {
+            visitor.visit_expr_mut(value);
+            for arm in arms.iter_mut() {
+                visitor.visit_pattern_mut(&mut arm.pattern);
+                if let Some(guard) = &mut arm.guard {
+                    visitor.visit_expr_mut(guard);
+                }
+                visitor.visit_program_mut(&mut arm.body);
+            }
+        }
+        ExprKind::Closure { param_types, return_type, body, .. } => {
+            param_types.iter_mut().for_each(|param_type| visitor.visit_type_mut(param_type));
+            visitor.visit_type_mut(return_type);
+            visitor.visit_program_mut(body);
+        }
+    }
+}
+
+pub fn walk_pattern_mut<V: MutVisitor + ?Sized>(visitor: &mut V, pattern: &mut Pattern) {
+    match pattern {
+        Pattern::Wildcard | Pattern::Binding(_) => {}
+        Pattern::Literal(literal) => visitor.visit_expr_mut(literal),
+        Pattern::Variant { fields, .. } => fields.iter_mut().for_each(|field| visitor.visit_pattern_mut(field)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::new_lexer;
+    use crate::parser::Parser;
+
+    const SOURCE: &str = "struct P { x: int }
+func f(a: int): int {
+    if (a > 0) {
+        return g(a);
+    } else {
+        let b: [int; 2] = [a, 1];
+    }
+    match a { 1 => a, _ if h(a) => { 0 } }
+}
+let c = func(x: int): int { k(x) as u8 };";
+
+    struct Calls {
+        names: Vec<String>,
+        types: usize,
+    }
+
+    impl Visitor for Calls {
+        fn visit_expr(&mut self, expr: &Expr) {
+            if let ExprKind::Call { func, .. } = &expr.kind {
+                if let ExprKind::Ident(name) = &func.kind {
+                    self.names.push(name.0.clone());
+                }
+            }
+            walk_expr(self, expr);
+        }
+
+        fn visit_type(&mut self, _value_type: &LLVMExpressionType) {
+            self.types += 1;
+        }
+    }
+
+    struct Rename;
+
+    impl MutVisitor for Rename {
+        fn visit_expr_mut(&mut self, expr: &mut Expr) {
+            if let ExprKind::Ident(name) = &mut expr.kind {
+                if name.0 == "a" {
+                    name.0 = "z".to_owned();
+                }
+            }
+            walk_expr_mut(self, expr);
+        }
+    }
+
+    fn parse(input: &str) -> Program {
+        Parser::new(new_lexer(input)).parse().unwrap().remove(0)
+    }
+
+    #[test]
+    fn test_visitor() {
+        let mut calls = Calls { names: vec![], types: 0 };
+        calls.visit_program(&parse(SOURCE));
+        assert_eq!(calls.names, vec!["g", "h", "k"]);
+        // int in P, two in f's signature, the array annotation and the
+        // closure's signature and cast.
+        assert_eq!(calls.types, 7);
+    }
+
+    #[test]
+    fn test_mut_visitor() {
+        let mut program = parse(SOURCE);
+        Rename.visit_program_mut(&mut program);
+        let expected = SOURCE
+            .replace("(a >", "(z >")
+            .replace("g(a)", "g(z)")
+            .replace("[a,", "[z,")
+            .replace("match a", "match z")
+            .replace("=> a", "=> z")
+            .replace("h(a)", "h(z)");
+        assert_eq!(program, parse(&expected));
+    }
+}
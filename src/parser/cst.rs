@@ -0,0 +1,243 @@
+use crate::lexer::*;
+use crate::parser::*;
+
+// SyntaxKind names the nodes of the concrete syntax tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyntaxKind {
+    Program,
+    Block,
+    LetStmt,
+    AssignStmt,
+    ReturnStmt,
+    ExprStmt,
+    FuncStmt,
+    StructStmt,
+    EnumStmt,
+    ImportStmt,
+    WhileStmt,
+    ForStmt,
+    BreakStmt,
+    ContinueStmt,
+    ParamList,
+    TypeParamList,
+    TypeAnnotation,
+    IdentExpr,
+    LiteralExpr,
+    PrefixExpr,
+    InfixExpr,
+    GroupedExpr,
+    IfExpr,
+    CallExpr,
+    CastExpr,
+    ArrayExpr,
+    HashExpr,
+    IndexExpr,
+    StructExpr,
+    FieldExpr,
+    VariantExpr,
+    MatchExpr,
+    ClosureExpr,
+    MatchArm,
+    Pattern,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(LosslessToken),
+}
+
+// SyntaxNode is a node of the concrete syntax tree. Unlike the AST it keeps
+// every token of the source, along with the whitespace and comments in front
+// of it, so the tree can be written back out exactly as it was read.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxNode {
+    pub kind: SyntaxKind,
+    pub children: Vec<SyntaxElement>,
+}
+
+impl SyntaxNode {
+    pub fn new(kind: SyntaxKind) -> Self {
+        SyntaxNode { kind, children: vec![] }
+    }
+
+    // text returns the source the node was parsed from, including the trivia
+    // in front of its first token.
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for token in self.tokens() {
+            token.write_to(&mut text);
+        }
+        text
+    }
+
+    pub fn tokens(&self) -> Vec<&LosslessToken> {
+        let mut tokens = vec![];
+        self.collect_tokens(&mut tokens);
+        tokens
+    }
+
+    fn collect_tokens<'a>(&'a self, tokens: &mut Vec<&'a LosslessToken>) {
+        for child in &self.children {
+            match child {
+                SyntaxElement::Node(node) => node.collect_tokens(tokens),
+                SyntaxElement::Token(token) => tokens.push(token),
+            }
+        }
+    }
+
+    pub fn child_nodes(&self) -> impl Iterator<Item = &SyntaxNode> {
+        self.children.iter().filter_map(|child| match child {
+            SyntaxElement::Node(node) => Some(node),
+            SyntaxElement::Token(_) => None,
+        })
+    }
+
+    // lower converts the node into the AST the parser would have produced for
+    // the same tokens. Spans still point into the original source.
+    pub fn lower(&self) -> Result<Vec<Program>, Vec<Diagnostic>> {
+        let mut tokens: Vec<SpannedToken> = self
+            .tokens()
+            .into_iter()
+            .map(|token| SpannedToken { token: token.token.clone(), span: token.span.clone() })
+            .collect();
+        if tokens.last().map(|last| &last.token) != Some(&Token::Eof) {
+            let span = match tokens.last() {
+                Some(last) => Span::new(last.span.file.clone(), last.span.end, last.span.end, last.span.line, last.span.column),
+                None => Span::default(),
+            };
+            tokens.push(SpannedToken { token: Token::Eof, span });
+        }
+        Parser::from_tokens(tokens).parse()
+    }
+}
+
+// CstBuilder assembles the tree while the parser runs. Tokens are added as
+// they become the parser's current token, so when a parse function starts,
+// the last child of the open node is the first token of its construct.
+pub struct CstBuilder {
+    stack: Vec<SyntaxNode>,
+    seen_eof: bool,
+}
+
+impl CstBuilder {
+    pub fn new() -> Self {
+        CstBuilder {
+            stack: vec![SyntaxNode::new(SyntaxKind::Program)],
+            seen_eof: false,
+        }
+    }
+
+    pub fn token(&mut self, token: LosslessToken) {
+        // The lexer keeps returning empty Eof tokens once the input runs out.
+        if token.token == Token::Eof {
+            if self.seen_eof {
+                return;
+            }
+            self.seen_eof = true;
+        }
+        self.current().children.push(SyntaxElement::Token(token));
+    }
+
+    // checkpoint marks the last child of the open node, which start_node_at
+    // can later wrap, together with everything after it, in a new node.
+    pub fn checkpoint(&self) -> usize {
+        self.stack.last().map_or(0, |node| node.children.len().saturating_sub(1))
+    }
+
+    pub fn start_node_at(&mut self, checkpoint: usize, kind: SyntaxKind) {
+        let parent = self.current();
+        let checkpoint = checkpoint.min(parent.children.len());
+        let children = parent.children.split_off(checkpoint);
+        self.stack.push(SyntaxNode { kind, children });
+    }
+
+    pub fn retag(&mut self, kind: SyntaxKind) {
+        self.current().kind = kind;
+    }
+
+    pub fn finish_node(&mut self) {
+        if self.stack.len() > 1 {
+            let node = self.stack.pop().unwrap();
+            self.current().children.push(SyntaxElement::Node(node));
+        }
+    }
+
+    pub fn finish(mut self) -> SyntaxNode {
+        while self.stack.len() > 1 {
+            self.finish_node();
+        }
+        self.stack.pop().unwrap()
+    }
+
+    fn current(&mut self) -> &mut SyntaxNode {
+        self.stack.last_mut().unwrap()
+    }
+}
+
+impl Default for CstBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// parse parses the whole input into a concrete syntax tree.
+pub fn parse(lexer: Lexer) -> SyntaxNode {
+    let mut parser = Parser::new_lossless(lexer);
+    // Syntax errors are still covered by the tree, which keeps every token.
+    let _ = parser.parse();
+    parser.into_syntax_tree().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "// Adds things.\n/// Sums two ints.\nfunc add(x: int, y: int): int {\n    return x + y; /* done */\n}\n\nlet total: int = add(1,   2) * -3;  // trailing\nif (total < 0) { total = 0; } else { total += 1; }\nprintf(\"héllo %d\\n\", total);\n";
+
+    #[test]
+    fn test_round_trip() {
+        let tree = parse(new_lexer(SOURCE));
+        assert_eq!(tree.text(), SOURCE);
+
+        for input in ["", "  \n// only a comment", "let x: int = 1;", "return (1 + 2) * 3;\n\n\n", "func max< T ,U>(a: T): T { return a; }", "let x: int = 1 /// c\n + 2; /// end"] {
+            assert_eq!(parse(new_lexer(input)).text(), input);
+        }
+    }
+
+    #[test]
+    fn test_tree_shape() {
+        let tree = parse(new_lexer(SOURCE));
+        let kinds: Vec<SyntaxKind> = tree.child_nodes().map(|node| node.kind).collect();
+        assert_eq!(kinds, vec![
+            SyntaxKind::FuncStmt,
+            SyntaxKind::LetStmt,
+            SyntaxKind::ExprStmt,
+            SyntaxKind::ExprStmt,
+        ]);
+
+        let func = tree.child_nodes().next().unwrap();
+        assert_eq!(func.text(), "// Adds things.\n/// Sums two ints.\nfunc add(x: int, y: int): int {\n    return x + y; /* done */\n}");
+        let func_kinds: Vec<SyntaxKind> = func.child_nodes().map(|node| node.kind).collect();
+        assert_eq!(func_kinds, vec![SyntaxKind::ParamList, SyntaxKind::TypeAnnotation, SyntaxKind::Block]);
+
+        let let_stmt = tree.child_nodes().nth(1).unwrap();
+        let value = let_stmt.child_nodes().last().unwrap();
+        assert_eq!(value.kind, SyntaxKind::InfixExpr);
+        let operands: Vec<SyntaxKind> = value.child_nodes().map(|node| node.kind).collect();
+        assert_eq!(operands, vec![SyntaxKind::CallExpr, SyntaxKind::PrefixExpr]);
+        assert_eq!(value.text(), " add(1,   2) * -3");
+    }
+
+    #[test]
+    fn test_lower() {
+        let tree = parse(new_file_lexer("main.flash", SOURCE));
+        let expected = Parser::new(new_file_lexer("main.flash", SOURCE)).parse().unwrap();
+        let lowered = tree.lower().unwrap();
+        assert_eq!(lowered, expected);
+        assert_eq!(lowered[0][1].span, expected[0][1].span);
+
+        let let_stmt = tree.child_nodes().nth(1).unwrap();
+        assert_eq!(let_stmt.lower().unwrap()[0], vec![expected[0][1].clone()]);
+    }
+}
@@ -0,0 +1,32 @@
+use std::fmt;
+
+use crate::lexer::{LexError, Span};
+
+// Diagnostic is a syntax or lexical error found while parsing. The parser
+// records it and carries on, so one run reports every problem in a file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub message: String,
+    pub span: Span,
+}
+
+impl Diagnostic {
+    pub fn new(message: impl Into<String>, span: Span) -> Self {
+        Diagnostic {
+            message: message.into(),
+            span,
+        }
+    }
+}
+
+impl From<LexError> for Diagnostic {
+    fn from(error: LexError) -> Self {
+        Diagnostic::new(error.kind.to_string(), error.span)
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.span, self.message)
+    }
+}
@@ -1,20 +1,72 @@
+use std::path::Path;
+
 use crate::lexer::*;
 pub mod ast;
 pub use ast::*;
+pub mod cst;
+use cst::{CstBuilder, SyntaxKind, SyntaxNode};
+pub mod diagnostic;
+pub use diagnostic::*;
+pub mod printer;
+
+enum TokenSource {
+    Lexer(Lexer),
+    // A token list that ends in Eof, e.g. the tokens of a syntax tree.
+    Tokens(Vec<SpannedToken>, usize),
+}
 
 pub struct Parser {
-    lexer: Lexer,
+    source: TokenSource,
     current_token: Token,
+    current_span: Span,
     next_token: Token,
+    next_span: Span,
+    // Only set when building a concrete syntax tree.
+    cst: Option<CstBuilder>,
+    next_lossless: Option<LosslessToken>,
+    // Doc comments read in front of a token that has not reached the tree
+    // yet. They go in just before it.
+    pending_docs: Vec<LosslessToken>,
+    // The doc comments in front of the current and the next token. To the
+    // parser they are trivia; only a function declaration reads them.
+    current_docs: Vec<SpannedToken>,
+    next_docs: Vec<SpannedToken>,
+    diagnostics: Vec<Diagnostic>,
+    // Set in loop headers, where a `{` after a name starts the body rather
+    // than a struct literal.
+    no_struct_literal: bool,
 }
 
 impl Parser {
 
     pub fn new(lexer: Lexer) -> Self {
+        Self::with_source(TokenSource::Lexer(lexer), None)
+    }
+
+    // new_lossless returns a parser that also records a concrete syntax tree,
+    // available from into_syntax_tree once parsing is done.
+    pub fn new_lossless(lexer: Lexer) -> Self {
+        Self::with_source(TokenSource::Lexer(lexer), Some(CstBuilder::new()))
+    }
+
+    pub fn from_tokens(tokens: Vec<SpannedToken>) -> Self {
+        Self::with_source(TokenSource::Tokens(tokens, 0), None)
+    }
+
+    fn with_source(source: TokenSource, cst: Option<CstBuilder>) -> Self {
         let mut parser = Parser {
-            lexer,
+            source,
             current_token: Token::Eof,
+            current_span: Span::default(),
             next_token: Token::Eof,
+            next_span: Span::default(),
+            cst,
+            next_lossless: None,
+            pending_docs: vec![],
+            current_docs: vec![],
+            next_docs: vec![],
+            diagnostics: vec![],
+            no_struct_literal: false,
         };
         parser.bump();
         parser.bump();
@@ -22,18 +74,166 @@ impl Parser {
         parser
     }
 
+    pub fn into_syntax_tree(mut self) -> Option<SyntaxNode> {
+        let mut cst = self.cst.take()?;
+        for token in self.pending_docs.drain(..) {
+            cst.token(token);
+        }
+        if let Some(token) = self.next_lossless.take() {
+            cst.token(token);
+        }
+        if let TokenSource::Lexer(ref mut lexer) = self.source {
+            loop {
+                let token = lexer.next_lossless_token();
+                let eof = token.token == Token::Eof;
+                cst.token(token);
+                if eof {
+                    break;
+                }
+            }
+        }
+        Some(cst.finish())
+    }
+
     fn token_to_precedence(tok: &Token) -> Precedence {
         match tok {
+            Token::Or => Precedence::LogicalOr,
+            Token::And => Precedence::LogicalAnd,
             Token::Equal | Token::NotEqual => Precedence::Equals,
+            Token::LessThan | Token::GreaterThan | Token::LessEqual | Token::GreaterEqual => Precedence::LessGreater,
+            Token::Pipe => Precedence::BitOr,
+            Token::Caret => Precedence::BitXor,
+            Token::Ampersand => Precedence::BitAnd,
+            Token::ShiftLeft | Token::ShiftRight => Precedence::Shift,
             Token::Plus | Token::Minus => Precedence::Sum,
+            Token::Asterisk | Token::Slash | Token::Percent => Precedence::Product,
+            Token::As => Precedence::Cast,
             Token::LParen => Precedence::Call,
+            Token::LBracket | Token::Dot => Precedence::Index,
             _ => Precedence::Lowest,
         }
     }
 
+    fn token_to_infix(tok: &Token) -> Option<Infix> {
+        match tok {
+            Token::Plus => Some(Infix::Plus),
+            Token::Minus => Some(Infix::Minus),
+            Token::Asterisk => Some(Infix::Multiply),
+            Token::Slash => Some(Infix::Divide),
+            Token::Percent => Some(Infix::Modulo),
+            Token::Equal => Some(Infix::Equal),
+            Token::NotEqual => Some(Infix::NotEqual),
+            Token::LessThan => Some(Infix::LessThan),
+            Token::GreaterThan => Some(Infix::GreaterThan),
+            Token::LessEqual => Some(Infix::LessEqual),
+            Token::GreaterEqual => Some(Infix::GreaterEqual),
+            Token::And => Some(Infix::And),
+            Token::Or => Some(Infix::Or),
+            Token::Ampersand => Some(Infix::BitAnd),
+            Token::Pipe => Some(Infix::BitOr),
+            Token::Caret => Some(Infix::BitXor),
+            Token::ShiftLeft => Some(Infix::ShiftLeft),
+            Token::ShiftRight => Some(Infix::ShiftRight),
+            _ => None,
+        }
+    }
+
+    // compound_assign_to_infix maps `+=` and friends to the operator they apply.
+    fn compound_assign_to_infix(tok: &Token) -> Option<Infix> {
+        match tok {
+            Token::PlusAssign => Some(Infix::Plus),
+            Token::MinusAssign => Some(Infix::Minus),
+            Token::AsteriskAssign => Some(Infix::Multiply),
+            Token::SlashAssign => Some(Infix::Divide),
+            Token::PercentAssign => Some(Infix::Modulo),
+            Token::AmpersandAssign => Some(Infix::BitAnd),
+            Token::PipeAssign => Some(Infix::BitOr),
+            Token::CaretAssign => Some(Infix::BitXor),
+            Token::ShiftLeftAssign => Some(Infix::ShiftLeft),
+            Token::ShiftRightAssign => Some(Infix::ShiftRight),
+            _ => None,
+        }
+    }
+
     fn bump(&mut self) {
-        self.current_token = self.next_token.clone();
-        self.next_token = self.lexer.next_token();
+        let mut docs = vec![];
+        let mut next = self.read_token();
+        while let Token::DocComment(_) = next.token {
+            docs.push(next);
+            next = self.read_token();
+        }
+        // The parser skips over Illegal tokens; the lexer's reason is what
+        // gets reported.
+        if let Token::Illegal(ref error) = next.token {
+            self.diagnostics.push(Diagnostic::from(error.clone()));
+        }
+        self.current_token = std::mem::replace(&mut self.next_token, next.token);
+        self.current_span = std::mem::replace(&mut self.next_span, next.span);
+        self.current_docs = std::mem::replace(&mut self.next_docs, docs);
+    }
+
+    fn read_token(&mut self) -> SpannedToken {
+        match self.source {
+            TokenSource::Lexer(ref mut lexer) => match self.cst {
+                Some(ref mut cst) => {
+                    let token = lexer.next_lossless_token();
+                    let next = SpannedToken { token: token.token.clone(), span: token.span.clone() };
+                    // The old next token is about to become the current one.
+                    match self.next_lossless.replace(token) {
+                        Some(doc @ LosslessToken { token: Token::DocComment(_), .. }) => self.pending_docs.push(doc),
+                        Some(current) => {
+                            for doc in self.pending_docs.drain(..) {
+                                cst.token(doc);
+                            }
+                            cst.token(current);
+                        }
+                        None => {}
+                    }
+                    next
+                }
+                None => lexer.next_token(),
+            },
+            TokenSource::Tokens(ref tokens, ref mut index) => {
+                let token = tokens[(*index).min(tokens.len() - 1)].clone();
+                *index += 1;
+                token
+            }
+        }
+    }
+
+    // node runs `parse` inside a new syntax tree node that starts at the
+    // current token.
+    fn node<T>(&mut self, kind: SyntaxKind, parse: impl FnOnce(&mut Self) -> T) -> T {
+        let checkpoint = self.checkpoint();
+        self.node_at(checkpoint, kind, parse)
+    }
+
+    fn node_at<T>(&mut self, checkpoint: usize, kind: SyntaxKind, parse: impl FnOnce(&mut Self) -> T) -> T {
+        if let Some(ref mut cst) = self.cst {
+            cst.start_node_at(checkpoint, kind);
+        }
+        let result = parse(self);
+        if let Some(ref mut cst) = self.cst {
+            cst.finish_node();
+        }
+        result
+    }
+
+    // retag changes the kind of the node being parsed, for constructs that
+    // turn out to be something else partway through.
+    fn retag(&mut self, kind: SyntaxKind) {
+        if let Some(ref mut cst) = self.cst {
+            cst.retag(kind);
+        }
+    }
+
+    fn checkpoint(&self) -> usize {
+        self.cst.as_ref().map_or(0, |cst| cst.checkpoint())
+    }
+
+    // span_from returns the span running from `start` to the current token.
+    fn span_from(&self, start: &Span) -> Span {
+        start.to(&self.current_span)
     }
 
     fn current_token_is(&mut self, tok: Token) -> bool {
@@ -49,7 +249,68 @@ impl Parser {
             self.bump();
             return true;
         } else {
-            panic!("Failre, expected token {:?}", tok);
+            self.error_at_next(format!("expected `{}`", tok));
+            false
+        }
+    }
+
+    fn expect_next_ident(&mut self) -> Option<Ident> {
+        match &self.next_token {
+            Token::Ident(_) => {
+                self.bump();
+                self.parse_ident()
+            }
+            _ => {
+                self.error_at_next("expected an identifier");
+                None
+            }
+        }
+    }
+
+    // error_at_next reports that the next token is not what `expected` says
+    // should come there.
+    fn error_at_next(&mut self, expected: impl Into<String>) {
+        let message = format!("{}, found {}", expected.into(), Self::describe(&self.next_token));
+        self.diagnostics.push(Diagnostic::new(message, self.next_span.clone()));
+    }
+
+    fn error_at_current(&mut self, expected: impl Into<String>) {
+        let message = format!("{}, found {}", expected.into(), Self::describe(&self.current_token));
+        self.diagnostics.push(Diagnostic::new(message, self.current_span.clone()));
+    }
+
+    fn describe(tok: &Token) -> String {
+        match tok {
+            Token::Eof => tok.to_string(),
+            tok => format!("`{}`", tok),
+        }
+    }
+
+    // A function, struct or enum declaration or an import ends any block
+    // that is still open, since they can only appear at the top level.
+    fn next_token_starts_item(&self) -> bool {
+        matches!(self.next_token, Token::Dis | Token::Pub | Token::Func | Token::Struct | Token::Enum | Token::Import)
+    }
+
+    // synchronize skips the rest of a statement that failed to parse. It
+    // stops on the `;` or `}` that ends the statement, or before the `}` of
+    // the enclosing block or a declaration, so that the caller's
+    // next bump lands on the start of the following statement.
+    fn synchronize(&mut self) {
+        let mut depth = 0;
+        loop {
+            match self.current_token {
+                Token::Eof => return,
+                Token::Semicolon if depth == 0 => return,
+                Token::LBrace => depth += 1,
+                Token::RBrace if depth <= 1 && self.next_token != Token::Else => return,
+                Token::RBrace => depth -= 1,
+                _ => {}
+            }
+            if (depth == 0 && self.next_token_is(&Token::RBrace)) || self.next_token_starts_item() {
+                return;
+            }
+            self.bump();
         }
     }
 
@@ -61,14 +322,17 @@ impl Parser {
         Self::token_to_precedence(&self.next_token)
     }
 
-    pub fn parse(&mut self) -> Vec<Program> {
+    // parse returns the program and the distributed functions in it, or every
+    // syntax error in the input.
+    pub fn parse(&mut self) -> Result<Vec<Program>, Vec<Diagnostic>> {
         let mut program: Program = vec![];
         let mut program2: Program = vec![];
         while !self.current_token_is(Token::Eof) {
+            let errors = self.diagnostics.len();
             match self.parse_stmt() {
-                Some(mut stmt) => {
-                    if let Stmt::Func{distributed: d, params: _, param_types: _, return_type: _,
-                        body: _, name: _} = stmt {
+                Some(stmt) => {
+                    if let StmtKind::Func{distributed: d, params: _, param_types: _, return_type: _,
+                        body: _, name: _, doc: _, public: _, type_params: _} = stmt.kind {
                             if d {
                                 program.push(stmt.clone());
                                 program2.push(stmt)
@@ -98,50 +362,328 @@ impl Parser {
                         program.push(stmt)
                     }*/
                 },
+                None if self.diagnostics.len() > errors => self.synchronize(),
                 None => {}
             }
             self.bump()
         }
-        return vec![program, program2];
+
+        if !self.diagnostics.is_empty() {
+            let mut diagnostics = std::mem::take(&mut self.diagnostics);
+            diagnostics.sort_by_key(|diagnostic| diagnostic.span.start);
+            return Err(diagnostics);
+        }
+        Ok(vec![program, program2])
     }
 
     pub fn parse_block_stmt(&mut self) -> BlockStmt {
-        self.bump();
+        self.with_struct_literals(true, Self::parse_block_stmts)
+    }
 
+    fn parse_block_stmts(&mut self) -> BlockStmt {
         let mut block = vec![];
 
-        while !self.current_token_is(Token::RBrace) && !self.current_token_is(Token::Eof) {
+        loop {
+            if self.next_token_starts_item() {
+                self.error_at_next("expected `}`");
+                return block;
+            }
+            self.bump();
+            match self.current_token {
+                Token::RBrace => return block,
+                Token::Eof => {
+                    self.error_at_current("expected `}`");
+                    return block;
+                }
+                _ => {}
+            }
+
+            let errors = self.diagnostics.len();
             match self.parse_stmt() {
-                Some(stmt) => block.push(stmt),
+                Some(mut stmt) => {
+                    if !self.current_token_is(Token::Semicolon) && self.next_token_is(&Token::RBrace) {
+                        if let StmtKind::Expr(expr) = stmt.kind {
+                            stmt.kind = StmtKind::Tail(expr);
+                        }
+                    }
+                    block.push(stmt)
+                }
+                None if self.diagnostics.len() > errors => self.synchronize(),
                 None => {}
             }
-            self.bump();
         }
-
-        block
     }
 
 
     fn parse_stmt(&mut self) -> Option<Stmt> {
         match self.current_token {
-            Token::Let => self.parse_let_stmt(),
-            Token::Return => self.parse_return_stmt(),
+            Token::Let => self.node(SyntaxKind::LetStmt, Self::parse_let_stmt),
+            Token::Return => self.node(SyntaxKind::ReturnStmt, Self::parse_return_stmt),
             Token::Ident(_) => self.parse_ident_stmt(), // Make sure to move this if we decide to do precidence
-            Token::Dis => self.parse_dis_func_expr(),
-            Token::Func => self.parse_func_expr(),
-            _ => self.parse_expr_stmt(),
+            Token::Dis | Token::Func | Token::Pub => self.parse_func_stmt(),
+            Token::Import => self.node(SyntaxKind::ImportStmt, Self::parse_import_stmt),
+            Token::Struct => self.node(SyntaxKind::StructStmt, Self::parse_struct_stmt),
+            Token::Enum => self.node(SyntaxKind::EnumStmt, Self::parse_enum_stmt),
+            Token::While => {
+                let start = self.current_span.clone();
+                self.node(SyntaxKind::WhileStmt, |parser| parser.parse_while_stmt(None, start))
+            }
+            Token::For => {
+                let start = self.current_span.clone();
+                self.node(SyntaxKind::ForStmt, |parser| parser.parse_for_stmt(None, start))
+            }
+            Token::Break => self.node(SyntaxKind::BreakStmt, Self::parse_break_stmt),
+            Token::Continue => self.node(SyntaxKind::ContinueStmt, Self::parse_continue_stmt),
+            // An empty statement.
+            Token::Semicolon => None,
+            _ => self.node(SyntaxKind::ExprStmt, Self::parse_expr_stmt),
+        }
+    }
+
+    // The doc comments right in front of a function document it; anywhere
+    // else they are ignored like any other comment.
+    fn parse_func_stmt(&mut self) -> Option<Stmt> {
+        let docs = std::mem::take(&mut self.current_docs);
+        let checkpoint = self.checkpoint().saturating_sub(docs.len());
+        let mut stmt = self.node_at(checkpoint, SyntaxKind::FuncStmt, |parser| match parser.current_token {
+            Token::Dis => parser.parse_dis_func_expr(),
+            Token::Pub => parser.parse_pub_func_expr(),
+            _ => parser.parse_func_expr(),
+        })?;
+        if let Some(first) = docs.first() {
+            let lines: Vec<String> = docs
+                .iter()
+                .filter_map(|doc| match &doc.token {
+                    Token::DocComment(line) => Some(line.clone()),
+                    _ => None,
+                })
+                .collect();
+            if let StmtKind::Func { ref mut doc, .. } = stmt.kind {
+                *doc = Some(lines.join("\n"));
+            }
+            stmt.span = first.span.to(&stmt.span);
         }
+        Some(stmt)
     }
 
     fn parse_ident_stmt(&mut self) -> Option<Stmt> {
+        if self.next_token_is(&Token::Colon) {
+            return self.parse_labeled_stmt();
+        }
+        if self.next_token_is(&Token::LBracket) || self.next_token_is(&Token::Dot) {
+            return self.parse_place_stmt();
+        }
         if self.next_token_is(&Token::Assign) {
-            return self.parse_assign_stmt();
+            return self.node(SyntaxKind::AssignStmt, Self::parse_assign_stmt);
+        }
+        if Self::compound_assign_to_infix(&self.next_token).is_some() {
+            return self.node(SyntaxKind::AssignStmt, Self::parse_compound_assign_stmt);
+        }
+        return self.node(SyntaxKind::ExprStmt, Self::parse_expr_stmt);
+    }
+
+    // A statement starting with `a[` or `a.` is an assignment to an element
+    // or a field when it is followed by `=` or a compound assignment, and an
+    // expression statement otherwise.
+    fn parse_place_stmt(&mut self) -> Option<Stmt> {
+        let start = self.current_span.clone();
+        let checkpoint = self.checkpoint();
+        let target = self.parse_expr(Precedence::Lowest)?;
+
+        let is_assignment = self.next_token_is(&Token::Assign) || Self::compound_assign_to_infix(&self.next_token).is_some();
+        if is_assignment && matches!(target.kind, ExprKind::Index { .. } | ExprKind::Field { .. }) {
+            return self.node_at(checkpoint, SyntaxKind::AssignStmt, |parser| {
+                parser.parse_place_assign_stmt(target, start)
+            });
+        }
+
+        self.node_at(checkpoint, SyntaxKind::ExprStmt, |parser| {
+            if parser.next_token_is(&Token::Semicolon) {
+                parser.bump();
+            }
+            Some(Stmt::new(StmtKind::Expr(target), parser.span_from(&start)))
+        })
+    }
+
+    // `a[i] += y` keeps its operator, so that `a` and `i` are evaluated once
+    // when the element is read and written back; likewise for fields.
+    fn parse_place_assign_stmt(&mut self, target: Expr, start: Span) -> Option<Stmt> {
+        self.bump();
+        let op = Self::compound_assign_to_infix(&self.current_token);
+        self.bump();
+
+        let value = self.parse_expr(Precedence::Lowest)?;
+        if self.next_token_is(&Token::Semicolon) {
+            self.bump();
+        }
+
+        let span = self.span_from(&start);
+        let kind = match target.kind {
+            ExprKind::Index { array, index } => StmtKind::IndexAssignment { array: *array, index: *index, op, value },
+            ExprKind::Field { object, field } => StmtKind::FieldAssignment { object: *object, field, op, value },
+            _ => return None,
+        };
+        Some(Stmt::new(kind, span))
+    }
+
+    // `outer: while cond { ... }`
+    fn parse_labeled_stmt(&mut self) -> Option<Stmt> {
+        let start = self.current_span.clone();
+        let checkpoint = self.checkpoint();
+        let label = self.parse_ident()?;
+        self.bump();
+
+        match self.next_token {
+            Token::While => {
+                self.bump();
+                self.node_at(checkpoint, SyntaxKind::WhileStmt, |parser| parser.parse_while_stmt(Some(label), start))
+            }
+            Token::For => {
+                self.bump();
+                self.node_at(checkpoint, SyntaxKind::ForStmt, |parser| parser.parse_for_stmt(Some(label), start))
+            }
+            _ => {
+                self.error_at_next("expected a loop after a label");
+                None
+            }
+        }
+    }
+
+    fn parse_while_stmt(&mut self, label: Option<Ident>, start: Span) -> Option<Stmt> {
+        self.bump();
+        let cond = self.with_struct_literals(false, |parser| parser.parse_expr(Precedence::Lowest))?;
+
+        if !self.expect_next_token(Token::LBrace) {
+            return None;
         }
-        return self.parse_expr_stmt();
+        let body = self.node(SyntaxKind::Block, Self::parse_block_stmt);
+
+        Some(Stmt::new(StmtKind::While { label, cond, body }, self.span_from(&start)))
+    }
+
+    // `for init; cond; step { ... }`, where each of the three parts may be
+    // left out.
+    fn parse_for_stmt(&mut self, label: Option<Ident>, start: Span) -> Option<Stmt> {
+        self.bump();
+        let (init, cond, step) = self.with_struct_literals(false, Self::parse_for_header)?;
+        if !self.expect_next_token(Token::LBrace) {
+            return None;
+        }
+        let body = self.node(SyntaxKind::Block, Self::parse_block_stmt);
+
+        Some(Stmt::new(StmtKind::For { label, init, cond, step, body }, self.span_from(&start)))
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn parse_for_header(&mut self) -> Option<(Option<Box<Stmt>>, Option<Expr>, Option<Box<Stmt>>)> {
+        let init = match self.current_token {
+            Token::Semicolon => None,
+            _ => {
+                let init = self.parse_for_clause()?;
+                if !self.current_token_is(Token::Semicolon) && !self.expect_next_token(Token::Semicolon) {
+                    return None;
+                }
+                Some(Box::new(init))
+            }
+        };
+
+        let cond = match self.next_token {
+            Token::Semicolon => None,
+            _ => {
+                self.bump();
+                Some(self.parse_expr(Precedence::Lowest)?)
+            }
+        };
+        if !self.expect_next_token(Token::Semicolon) {
+            return None;
+        }
+
+        let step = match self.next_token {
+            Token::LBrace => None,
+            _ => {
+                self.bump();
+                Some(Box::new(self.parse_for_clause()?))
+            }
+        };
+        Some((init, cond, step))
+    }
+
+    fn with_struct_literals<T>(&mut self, allowed: bool, parse: impl FnOnce(&mut Self) -> T) -> T {
+        let saved = std::mem::replace(&mut self.no_struct_literal, !allowed);
+        let result = parse(self);
+        self.no_struct_literal = saved;
+        result
+    }
+
+    fn parse_for_clause(&mut self) -> Option<Stmt> {
+        match self.current_token {
+            Token::Let => self.node(SyntaxKind::LetStmt, Self::parse_let_stmt),
+            Token::Ident(_) if self.next_token != Token::Colon => self.parse_ident_stmt(),
+            _ => self.node(SyntaxKind::ExprStmt, Self::parse_expr_stmt),
+        }
+    }
+
+    fn parse_break_stmt(&mut self) -> Option<Stmt> {
+        let start = self.current_span.clone();
+        let label = self.parse_loop_label()?;
+        Some(Stmt::new(StmtKind::Break(label), self.span_from(&start)))
+    }
+
+    fn parse_continue_stmt(&mut self) -> Option<Stmt> {
+        let start = self.current_span.clone();
+        let label = self.parse_loop_label()?;
+        Some(Stmt::new(StmtKind::Continue(label), self.span_from(&start)))
+    }
+
+    fn parse_loop_label(&mut self) -> Option<Option<Ident>> {
+        let label = match self.next_token {
+            Token::Ident(_) => {
+                self.bump();
+                Some(self.parse_ident()?)
+            }
+            _ => None,
+        };
+
+        if self.next_token_is(&Token::Semicolon) {
+            self.bump();
+        }
+        Some(label)
+    }
+
+    // `x += y` is sugar for `x = x + y`.
+    fn parse_compound_assign_stmt(&mut self) -> Option<Stmt> {
+        let start = self.current_span.clone();
+        let name = match self.parse_ident() {
+            Some(name) => name,
+            None => return None,
+        };
+
+        self.bump();
+        let infix = match Self::compound_assign_to_infix(&self.current_token) {
+            Some(infix) => infix,
+            None => return None,
+        };
+
+        self.bump();
+
+        let expr = match self.parse_expr(Precedence::Lowest) {
+            Some(expr) => expr,
+            None => return None,
+        };
+
+        if self.next_token_is(&Token::Semicolon) {
+            self.bump();
+        }
+
+        let target = Expr::new(ExprKind::Ident(name.clone()), start.clone());
+        let span = self.span_from(&start);
+        let value = Expr::new(ExprKind::Infix(infix, Box::new(target), Box::new(expr)), span.clone());
+        Some(Stmt::new(StmtKind::Assignment(name, value), span))
     }
 
 
     fn parse_assign_stmt(&mut self) -> Option<Stmt> {
+        let start = self.current_span.clone();
         let name = match self.parse_ident() {
             Some(name) => name,
             None => return None,
@@ -162,22 +704,26 @@ impl Parser {
             self.bump();
         }
 
-        Some(Stmt::Assignment(name, expr))
+        Some(Stmt::new(StmtKind::Assignment(name, expr), self.span_from(&start)))
     }
 
     fn parse_let_stmt(&mut self) -> Option<Stmt> {
-        match &self.next_token {
-            Token::Ident(_) => self.bump(),
-            _ => return None,
-        };
+        let start = self.current_span.clone();
+        if self.next_token_is(&Token::LParen) {
+            self.bump();
+            return self.parse_let_tuple_stmt(start);
+        }
+        let name = self.expect_next_ident()?;
 
-        let name = match self.parse_ident() {
-            Some(name) => name,
-            None => return None,
+        let value_type = match self.next_token {
+            Token::Colon => Some(self.parse_type()?),
+            Token::Assign => None,
+            _ => {
+                self.error_at_next("expected `:` or `=`");
+                return None;
+            }
         };
 
-        let value_type = self.parse_type().unwrap();
-
         if !self.expect_next_token(Token::Assign) {
             return None;
         }
@@ -193,10 +739,42 @@ impl Parser {
             self.bump();
         }
 
-        Some(Stmt::Let(name, expr, value_type))
+        Some(Stmt::new(StmtKind::Let(name, expr, value_type), self.span_from(&start)))
+    }
+
+    // `let (q, r) = divmod(a, b);`, with the current token on `(`.
+    fn parse_let_tuple_stmt(&mut self, start: Span) -> Option<Stmt> {
+        let mut names = vec![self.expect_next_ident()?];
+        while self.next_token_is(&Token::Comma) {
+            self.bump();
+            names.push(self.expect_next_ident()?);
+        }
+        if !self.expect_next_token(Token::RParen) {
+            return None;
+        }
+
+        let value_type = match self.next_token {
+            Token::Colon => Some(self.parse_type()?),
+            Token::Assign => None,
+            _ => {
+                self.error_at_next("expected `:` or `=`");
+                return None;
+            }
+        };
+        if !self.expect_next_token(Token::Assign) {
+            return None;
+        }
+        self.bump();
+
+        let expr = self.parse_expr(Precedence::Lowest)?;
+        if self.next_token_is(&Token::Semicolon) {
+            self.bump();
+        }
+        Some(Stmt::new(StmtKind::LetTuple(names, expr, value_type), self.span_from(&start)))
     }
 
     fn parse_return_stmt(&mut self) -> Option<Stmt> {
+        let start = self.current_span.clone();
         self.bump();
 
         let expr = match self.parse_expr(Precedence::Lowest) {
@@ -208,46 +786,77 @@ impl Parser {
             self.bump();
         }
 
-        Some(Stmt::Return(expr))
+        Some(Stmt::new(StmtKind::Return(expr), self.span_from(&start)))
     }
 
     fn parse_expr_stmt(&mut self) -> Option<Stmt> {
+        let start = self.current_span.clone();
         match self.parse_expr(Precedence::Lowest) {
             Some(expr) => {
                 if self.next_token_is(&Token::Semicolon) {
                     self.bump();
                 }
-                Some(Stmt::Expr(expr))
+                Some(Stmt::new(StmtKind::Expr(expr), self.span_from(&start)))
             }
             None => None,
         }
     }
 
     fn parse_expr(&mut self, precedence: Precedence) -> Option<Expr> {
+        let checkpoint = self.checkpoint();
         let mut left = match self.current_token.clone() {
-            Token::Ident(_) => self.parse_ident_expr(),
-            Token::Int(_) => self.parse_int_expr(),
-            // ADD STRING LATER
-            Token::Bool(_) => self.parse_bool_expr(),
-            Token::Minus | Token::Plus => self.parse_prefix_expr(),
-            Token::LParen => self.parse_grouped_expr(),
-            Token::If => self.parse_if_expr(),
-       /*     Token::Dis => self.parse_dis_func_expr(),
-            Token::Func => self.parse_func_expr(),*/
-            d => {
-                panic!("Failure, token: {:?}", d);
+            Token::Ident(_) if self.next_token_is(&Token::LBrace) && !self.no_struct_literal => {
+                self.node(SyntaxKind::StructExpr, Self::parse_struct_expr)
+            }
+            Token::Ident(_) if self.next_token_is(&Token::DoubleColon) => {
+                self.node(SyntaxKind::VariantExpr, Self::parse_variant_expr)
+            }
+            Token::Ident(_) => self.node(SyntaxKind::IdentExpr, Self::parse_ident_expr),
+            Token::Int(_, _) => self.node(SyntaxKind::LiteralExpr, Self::parse_int_expr),
+            Token::Float(_) => self.node(SyntaxKind::LiteralExpr, Self::parse_float_expr),
+            Token::String(_) => self.node(SyntaxKind::LiteralExpr, Self::parse_string_expr),
+            Token::Bool(_) | Token::True | Token::False => self.node(SyntaxKind::LiteralExpr, Self::parse_bool_expr),
+            Token::Char(_) => self.node(SyntaxKind::LiteralExpr, Self::parse_char_expr),
+            Token::Minus | Token::Plus | Token::Bang => self.node(SyntaxKind::PrefixExpr, Self::parse_prefix_expr),
+            Token::LParen => self.node(SyntaxKind::GroupedExpr, Self::parse_grouped_expr),
+            Token::LBracket => self.node(SyntaxKind::ArrayExpr, Self::parse_array_expr),
+            Token::MapType => self.node(SyntaxKind::HashExpr, Self::parse_hash_expr),
+            Token::If => self.node(SyntaxKind::IfExpr, Self::parse_if_expr),
+            Token::Match => self.node(SyntaxKind::MatchExpr, Self::parse_match_expr),
+            Token::Func => self.node(SyntaxKind::ClosureExpr, Self::parse_closure_expr),
+            Token::Illegal(_) => None,
+            _ => {
+                self.error_at_current("expected an expression");
+                None
             }
         };
 
         while !self.next_token_is(&Token::Semicolon) && precedence < self.next_token_precedence() {
             match self.next_token {
-                Token::Plus
-                | Token::Minus
-                | Token::Equal
-                | Token::NotEqual
-                | Token::LParen => {
+                Token::LParen => {
+                    self.bump();
+                    let func = left?;
+                    left = self.node_at(checkpoint, SyntaxKind::CallExpr, |parser| parser.parse_call_expr(func));
+                }
+                Token::LBracket => {
+                    self.bump();
+                    let array = left?;
+                    left = self.node_at(checkpoint, SyntaxKind::IndexExpr, |parser| parser.parse_index_expr(array));
+                }
+                Token::Dot => {
+                    self.bump();
+                    let object = left?;
+                    left = self.node_at(checkpoint, SyntaxKind::FieldExpr, |parser| parser.parse_field_expr(object));
+                }
+                Token::As => {
+                    self.bump();
+                    let value = left?;
+                    left = self.node_at(checkpoint, SyntaxKind::CastExpr, |parser| parser.parse_cast_expr(value));
+                }
+                ref tok if Self::token_to_infix(tok).is_some() => {
                     self.bump();
-                    left = self.parse_call_expr(left.unwrap());
+                    let lhs = left?;
+                    left = self.node_at(checkpoint, SyntaxKind::InfixExpr, |parser| parser.parse_infix_expr(lhs));
                 }
                 _ => return left,
             }
@@ -255,6 +864,17 @@ impl Parser {
         left
     }
 
+    fn parse_infix_expr(&mut self, left: Expr) -> Option<Expr> {
+        let infix = Self::token_to_infix(&self.current_token)?;
+        let precedence = self.current_token_precedence();
+
+        self.bump();
+
+        let right = self.parse_expr(precedence)?;
+        let span = self.span_from(&left.span);
+        Some(Expr::new(ExprKind::Infix(infix, Box::new(left), Box::new(right)), span))
+    }
+
     fn parse_ident(&mut self) -> Option<Ident> {
         match self.current_token {
             Token::Ident(ref mut ident) => Some(Ident(ident.clone())),
@@ -264,26 +884,60 @@ impl Parser {
 
     fn parse_ident_expr(&mut self) -> Option<Expr> {
         match self.parse_ident() {
-            Some(ident) => Some(Expr::Ident(ident)),
+            Some(ident) => Some(Expr::new(ExprKind::Ident(ident), self.current_span.clone())),
             None => None,
         }
     }
 
     fn parse_int_expr(&mut self) -> Option<Expr> {
         match self.current_token {
-            Token::Int(ref mut int) => Some(Expr::Literal(Literal::Int(int.clone()))),
+            Token::Int(int, suffix) => Some(Expr::new(ExprKind::Literal(Literal::Int(int, suffix)), self.current_span.clone())),
+            _ => None,
+        }
+    }
+
+    fn parse_float_expr(&mut self) -> Option<Expr> {
+        match self.current_token {
+            Token::Float(value) => Some(Expr::new(ExprKind::Literal(Literal::Float(value)), self.current_span.clone())),
             _ => None,
         }
     }
 
     fn parse_bool_expr(&mut self) -> Option<Expr> {
         match self.current_token {
-            Token::Bool(value) => Some(Expr::Literal(Literal::Bool(value == true))),
+            Token::Bool(value) => Some(Expr::new(ExprKind::Literal(Literal::Bool(value)), self.current_span.clone())),
+            Token::True => Some(Expr::new(ExprKind::Literal(Literal::Bool(true)), self.current_span.clone())),
+            Token::False => Some(Expr::new(ExprKind::Literal(Literal::Bool(false)), self.current_span.clone())),
+            _ => None,
+        }
+    }
+
+    fn parse_char_expr(&mut self) -> Option<Expr> {
+        match self.current_token {
+            Token::Char(value) => Some(Expr::new(ExprKind::Literal(Literal::Char(value)), self.current_span.clone())),
+            _ => None,
+        }
+    }
+
+    // `value as type` converts between the numeric types, chars and bools.
+    fn parse_cast_expr(&mut self, value: Expr) -> Option<Expr> {
+        let target = self.parse_next_type()?;
+        let span = self.span_from(&value.span);
+        Some(Expr::new(ExprKind::Cast(Box::new(value), target), span))
+    }
+
+    fn parse_string_expr(&mut self) -> Option<Expr> {
+        match self.current_token {
+            Token::String(ref string) => Some(Expr::new(ExprKind::Literal(Literal::String(string.clone())), self.current_span.clone())),
             _ => None,
         }
     }
 
     fn parse_expr_list(&mut self, end: Token) -> Option<Vec<Expr>> {
+        self.with_struct_literals(true, |parser| parser.parse_expr_list_items(end))
+    }
+
+    fn parse_expr_list_items(&mut self, end: Token) -> Option<Vec<Expr>> {
         let mut list = vec![];
 
         if self.next_token_is(&end) {
@@ -316,6 +970,7 @@ impl Parser {
     }
 
     fn parse_prefix_expr(&mut self) -> Option<Expr> {
+        let start = self.current_span.clone();
         let prefix = match self.current_token {
             Token::Bang => Prefix::Not,
             Token::Minus => Prefix::Minus,
@@ -326,24 +981,39 @@ impl Parser {
         self.bump();
 
         match self.parse_expr(Precedence::Prefix) {
-            Some(expr) => Some(Expr::Prefix(prefix, Box::new(expr))),
+            Some(expr) => Some(Expr::new(ExprKind::Prefix(prefix, Box::new(expr)), self.span_from(&start))),
             None => None,
         }
     }
 
     fn parse_grouped_expr(&mut self) -> Option<Expr> {
+        let start = self.current_span.clone();
         self.bump();
 
-        let expr = self.parse_expr(Precedence::Lowest);
+        let expr = self.with_struct_literals(true, |parser| parser.parse_expr(Precedence::Lowest));
 
-        if !self.expect_next_token(Token::RParen) {
-            None
+        // A comma makes it a tuple.
+        let expr = if self.next_token_is(&Token::Comma) {
+            let mut elements = vec![expr?];
+            while self.next_token_is(&Token::Comma) {
+                self.bump();
+                self.bump();
+                elements.push(self.with_struct_literals(true, |parser| parser.parse_expr(Precedence::Lowest))?);
+            }
+            Some(Expr::from(ExprKind::Tuple(elements)))
         } else {
             expr
+        };
+
+        if !self.expect_next_token(Token::RParen) {
+            None
+        } else {
+            expr.map(|expr| Expr::new(expr.kind, self.span_from(&start)))
         }
     }
 
     fn parse_if_expr(&mut self) -> Option<Expr> {
+        let start = self.current_span.clone();
         if !self.expect_next_token(Token::LParen) {
             return None;
         } 
@@ -359,109 +1029,341 @@ impl Parser {
             return None;
         }
 
-        let consequence = self.parse_block_stmt();
+        let consequence = self.node(SyntaxKind::Block, Self::parse_block_stmt);
         let mut alternative = None;
 
         if self.next_token_is(&Token::Else) {
             self.bump();
 
-            if !self.expect_next_token(Token::LBrace) {
-                return None;
-            }
+            // `else if` is an `else` block holding just the nested `if`.
+            if self.next_token_is(&Token::If) {
+                self.bump();
+                let nested = self.node(SyntaxKind::IfExpr, Self::parse_if_expr)?;
+                let span = nested.span.clone();
+                alternative = Some(vec![Stmt::new(StmtKind::Tail(nested), span)]);
+            } else {
+                if !self.expect_next_token(Token::LBrace) {
+                    return None;
+                }
 
-            alternative = Some(self.parse_block_stmt());
+                alternative = Some(self.node(SyntaxKind::Block, Self::parse_block_stmt));
+            }
         }
 
-        Some(Expr::If {
+        Some(Expr::new(ExprKind::If {
             cond: Box::new(cond),
             consequence,
             alternative,
-        })
+        }, self.span_from(&start)))
     }
 
     fn parse_dis_func_expr(&mut self) -> Option<Stmt> {
+        let start = self.current_span.clone();
         if !self.expect_next_token(Token::Func) {
             return None;
         }
 
-        match &self.next_token {
-            Token::Ident(_) => self.bump(),
-            _ => return None,
-        };
-
-        let name = match self.parse_ident() {
-            Some(name) => name,
-            None => return None,
-        };
+        let name = self.expect_next_ident()?;
+        let type_params = self.parse_type_params()?;
 
         if !self.expect_next_token(Token::LParen) {
             return None;
         }
 
-        let (params, param_types) = match self.parse_func_params() {
+        let (params, param_types) = match self.node(SyntaxKind::ParamList, Self::parse_func_params) {
             Some((params, param_types)) => (params, param_types),
             None => return None,
         };
 
-        let return_type = self.parse_type().unwrap();
+        let return_type = self.parse_type()?;
 
         if !self.expect_next_token(Token::LBrace) {
             return None;
         }
 
-        Some(Stmt::Func {
+        let body = self.node(SyntaxKind::Block, Self::parse_block_stmt);
+
+        Some(Stmt::new(StmtKind::Func {
             name: name.0,
+            doc: None,
             distributed: true,
+            public: false,
+            type_params,
             params: params,
             param_types: param_types,
             return_type: return_type,
-            body: self.parse_block_stmt(),
-        })
+            body: body,
+        }, self.span_from(&start)))
 
     }
 
-    pub fn convert_token_to_expression_type(&mut self, token: Token) -> LLVMExpressionType {
-        match token {
-            Token::BoolType => LLVMExpressionType::Boolean,
-            Token::IntType => LLVMExpressionType::Integer,
-            _ => LLVMExpressionType::Null,
+    // `pub func` or `pub dis func`.
+    fn parse_pub_func_expr(&mut self) -> Option<Stmt> {
+        let start = self.current_span.clone();
+        let mut stmt = match self.next_token {
+            Token::Dis => {
+                self.bump();
+                self.parse_dis_func_expr()?
+            }
+            Token::Func => {
+                self.bump();
+                self.parse_func_expr()?
+            }
+            _ => {
+                self.error_at_next("expected `func`");
+                return None;
+            }
+        };
+        if let StmtKind::Func { ref mut public, .. } = stmt.kind {
+            *public = true;
         }
+        stmt.span = start.to(&stmt.span);
+        Some(stmt)
     }
 
-    fn parse_func_expr(&mut self) -> Option<Stmt> {
-        match &self.next_token {
-            Token::Ident(_) => self.bump(),
-            _ => return None,
+    // `import "lib/math.flash";` or `import math;`
+    fn parse_import_stmt(&mut self) -> Option<Stmt> {
+        let start = self.current_span.clone();
+        let (name, path) = match self.next_token.clone() {
+            Token::Ident(name) => {
+                let path = format!("{}.flash", name);
+                (name, path)
+            }
+            Token::String(path) => {
+                let name = Path::new(&path)
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                (name, path)
+            }
+            _ => {
+                self.error_at_next("expected a module name or path");
+                return None;
+            }
         };
+        self.bump();
 
-        let name = match self.parse_ident() {
-            Some(name) => name,
-            None => return None,
+        if self.next_token_is(&Token::Semicolon) {
+            self.bump();
+        }
+        Some(Stmt::new(StmtKind::Import { name: Ident(name), path }, self.span_from(&start)))
+    }
+
+    pub fn convert_token_to_expression_type(&mut self, token: Token) -> Option<LLVMExpressionType> {
+        match token {
+            Token::BoolType => Some(LLVMExpressionType::Boolean),
+            Token::IntType => Some(LLVMExpressionType::Integer(IntKind::I32)),
+            Token::SizedIntType(kind) => Some(LLVMExpressionType::Integer(kind)),
+            Token::FloatType => Some(LLVMExpressionType::Float),
+            Token::CharType => Some(LLVMExpressionType::Char),
+            Token::StringType => Some(LLVMExpressionType::String),
+            Token::VoidType => Some(LLVMExpressionType::Null),
+            Token::Ident(name) => Some(LLVMExpressionType::Named(name)),
+            _ => None,
+        }
+    }
+
+    // `[int; 4]`
+    fn parse_array_type(&mut self) -> Option<LLVMExpressionType> {
+        let element = self.parse_next_type()?;
+        if !self.expect_next_token(Token::Semicolon) {
+            return None;
+        }
+
+        let length = match self.next_token {
+            Token::Int(length, None) if length <= u32::MAX as u64 => length as u32,
+            _ => {
+                self.error_at_next("expected an array length");
+                // Step past the `;` so that recovery does not take it for the
+                // end of the statement.
+                self.bump();
+                return None;
+            }
         };
+        self.bump();
 
+        if !self.expect_next_token(Token::RBracket) {
+            return None;
+        }
+        Some(LLVMExpressionType::Array(Box::new(element), length))
+    }
+
+    // `map[string]int`, with the current token on `map`.
+    fn parse_map_type(&mut self) -> Option<LLVMExpressionType> {
+        if !self.expect_next_token(Token::LBracket) {
+            return None;
+        }
+        let key = self.parse_next_type()?;
+        if !self.expect_next_token(Token::RBracket) {
+            return None;
+        }
+        let value = self.parse_next_type()?;
+        Some(LLVMExpressionType::Map(Box::new(key), Box::new(value)))
+    }
+
+    // `func(int, int): int`, with the current token on `func`.
+    fn parse_function_type(&mut self) -> Option<LLVMExpressionType> {
         if !self.expect_next_token(Token::LParen) {
             return None;
         }
 
-        let (params, param_types) = match self.parse_func_params() {
+        let mut params = vec![];
+        if !self.next_token_is(&Token::RParen) {
+            params.push(self.parse_next_type()?);
+            while self.next_token_is(&Token::Comma) {
+                self.bump();
+                params.push(self.parse_next_type()?);
+            }
+        }
+        if !self.expect_next_token(Token::RParen) {
+            return None;
+        }
+
+        let return_type = self.parse_type()?;
+        Some(LLVMExpressionType::Function(params, Box::new(return_type)))
+    }
+
+    // `(int, bool)`, with the current token on `(`.
+    fn parse_tuple_type(&mut self) -> Option<LLVMExpressionType> {
+        let mut elements = vec![self.parse_next_type()?];
+        if !self.expect_next_token(Token::Comma) {
+            return None;
+        }
+        elements.push(self.parse_next_type()?);
+        while self.next_token_is(&Token::Comma) {
+            self.bump();
+            elements.push(self.parse_next_type()?);
+        }
+        if !self.expect_next_token(Token::RParen) {
+            return None;
+        }
+        Some(LLVMExpressionType::Tuple(elements))
+    }
+
+    // `struct Point { x: int, y: int }`
+    fn parse_struct_stmt(&mut self) -> Option<Stmt> {
+        let start = self.current_span.clone();
+        let name = self.expect_next_ident()?;
+        if !self.expect_next_token(Token::LBrace) {
+            return None;
+        }
+
+        let mut fields = vec![];
+        if !self.next_token_is(&Token::RBrace) {
+            fields.push((self.expect_next_ident()?, self.parse_type()?));
+            while self.next_token_is(&Token::Comma) {
+                self.bump();
+                fields.push((self.expect_next_ident()?, self.parse_type()?));
+            }
+        }
+        if !self.expect_next_token(Token::RBrace) {
+            return None;
+        }
+
+        Some(Stmt::new(StmtKind::Struct { name, fields }, self.span_from(&start)))
+    }
+
+    // `enum Shape { Circle(int), Rect(int, int), Empty }`
+    fn parse_enum_stmt(&mut self) -> Option<Stmt> {
+        let start = self.current_span.clone();
+        let name = self.expect_next_ident()?;
+        if !self.expect_next_token(Token::LBrace) {
+            return None;
+        }
+
+        // As with literals, a bad variant skips to the closing brace so that
+        // it is not taken for the end of a block.
+        let mut variants = vec![];
+        if !self.next_token_is(&Token::RBrace) {
+            loop {
+                match self.parse_enum_variant() {
+                    Some(variant) => variants.push(variant),
+                    None => {
+                        self.skip_literal_entries();
+                        return Some(Stmt::new(StmtKind::Enum { name, variants }, self.span_from(&start)));
+                    }
+                }
+                if !self.next_token_is(&Token::Comma) {
+                    break;
+                }
+                self.bump();
+            }
+        }
+        if !self.expect_next_token(Token::RBrace) {
+            self.skip_literal_entries();
+        }
+
+        Some(Stmt::new(StmtKind::Enum { name, variants }, self.span_from(&start)))
+    }
+
+    fn parse_enum_variant(&mut self) -> Option<(Ident, Vec<LLVMExpressionType>)> {
+        let variant = self.expect_next_ident()?;
+        let mut fields = vec![];
+        if self.next_token_is(&Token::LParen) {
+            self.bump();
+            fields.push(self.parse_next_type()?);
+            while self.next_token_is(&Token::Comma) {
+                self.bump();
+                fields.push(self.parse_next_type()?);
+            }
+            if !self.expect_next_token(Token::RParen) {
+                return None;
+            }
+        }
+        Some((variant, fields))
+    }
+
+    fn parse_func_expr(&mut self) -> Option<Stmt> {
+        let start = self.current_span.clone();
+        let name = self.expect_next_ident()?;
+        let type_params = self.parse_type_params()?;
+
+        if !self.expect_next_token(Token::LParen) {
+            return None;
+        }
+
+        let (params, param_types) = match self.node(SyntaxKind::ParamList, Self::parse_func_params) {
             Some((params, param_types)) => (params, param_types),
             None => return None,
         };
 
-        let return_type = self.parse_type().unwrap();
+        let return_type = self.parse_type()?;
 
         if !self.expect_next_token(Token::LBrace) {
             return None;
         }
 
-        Some(Stmt::Func {
+        let body = self.node(SyntaxKind::Block, Self::parse_block_stmt);
+
+        Some(Stmt::new(StmtKind::Func {
             name: name.0,
+            doc: None,
             distributed: false,
+            public: false,
+            type_params,
             params: params,
             param_types: param_types,
             return_type: return_type,
-            body: self.parse_block_stmt(),
-        })
+            body: body,
+        }, self.span_from(&start)))
+    }
+
+    // `func(x: int): int { return x + 1; }`
+    fn parse_closure_expr(&mut self) -> Option<Expr> {
+        let start = self.current_span.clone();
+        if !self.expect_next_token(Token::LParen) {
+            return None;
+        }
+
+        let (params, param_types) = self.node(SyntaxKind::ParamList, Self::parse_func_params)?;
+        let return_type = self.parse_type()?;
+
+        if !self.expect_next_token(Token::LBrace) {
+            return None;
+        }
+
+        let body = self.node(SyntaxKind::Block, Self::parse_block_stmt);
+        Some(Expr::new(ExprKind::Closure { params, param_types, return_type, body }, self.span_from(&start)))
     }
 
     pub fn parse_type(&mut self) -> Option<LLVMExpressionType> {
@@ -469,8 +1371,65 @@ impl Parser {
             return None;
         }
 
+        self.node(SyntaxKind::TypeAnnotation, Self::parse_next_type)
+    }
+
+    fn parse_next_type(&mut self) -> Option<LLVMExpressionType> {
+        if self.next_token_is(&Token::LBracket) {
+            self.bump();
+            return self.parse_array_type();
+        }
+        if self.next_token_is(&Token::MapType) {
+            self.bump();
+            return self.parse_map_type();
+        }
+        if self.next_token_is(&Token::Func) {
+            self.bump();
+            return self.parse_function_type();
+        }
+        if self.next_token_is(&Token::LParen) {
+            self.bump();
+            return self.parse_tuple_type();
+        }
+
+        match self.convert_token_to_expression_type(self.next_token.clone()) {
+            Some(expression_type) => {
+                self.bump();
+                match expression_type {
+                    // `shapes.Rect`, a type of an imported file.
+                    LLVMExpressionType::Named(module) if self.next_token_is(&Token::Dot) => {
+                        self.bump();
+                        let name = self.expect_next_ident()?;
+                        Some(LLVMExpressionType::Named(format!("{}.{}", module, name.0)))
+                    }
+                    expression_type => Some(expression_type),
+                }
+            }
+            None => {
+                self.error_at_next("expected a type");
+                None
+            }
+        }
+    }
+
+    // `<T, U>` after the name of a generic function. Other functions have
+    // no type parameters.
+    fn parse_type_params(&mut self) -> Option<Vec<Ident>> {
+        if !self.next_token_is(&Token::LessThan) {
+            return Some(vec![]);
+        }
         self.bump();
-        Some(self.convert_token_to_expression_type(self.current_token.clone()))
+        self.node(SyntaxKind::TypeParamList, |parser| {
+            let mut type_params = vec![parser.expect_next_ident()?];
+            while parser.next_token_is(&Token::Comma) {
+                parser.bump();
+                type_params.push(parser.expect_next_ident()?);
+            }
+            if !parser.expect_next_token(Token::GreaterThan) {
+                return None;
+            }
+            Some(type_params)
+        })
     }
 
     fn parse_func_params(&mut self) -> Option<(Vec<Ident>, Vec<LLVMExpressionType>)> {
@@ -482,33 +1441,322 @@ impl Parser {
             return Some((params, param_types));
         }
 
+        params.push(self.expect_next_ident()?);
+        param_types.push(self.parse_type()?);
+
+        while self.next_token_is(&Token::Comma) {
+            self.bump();
+            params.push(self.expect_next_ident()?);
+            param_types.push(self.parse_type()?);
+        }
+        if !self.expect_next_token(Token::RParen) {
+            return None;
+        }
+
+        Some((params, param_types))
+    }
+
+    fn parse_array_expr(&mut self) -> Option<Expr> {
+        let start = self.current_span.clone();
+        let elements = self.parse_expr_list(Token::RBracket)?;
+        Some(Expr::new(ExprKind::Array(elements), self.span_from(&start)))
+    }
+
+    // `map[string]int{"a": 1, "b": 2}`
+    fn parse_hash_expr(&mut self) -> Option<Expr> {
+        let start = self.current_span.clone();
+        let map_type = self.parse_map_type()?;
+        if !self.expect_next_token(Token::LBrace) {
+            return None;
+        }
+
+        let mut entries = vec![];
+        if self.next_token_is(&Token::RBrace) {
+            self.bump();
+            return Some(Expr::new(ExprKind::Hash(map_type, entries), self.span_from(&start)));
+        }
+
+        loop {
+            self.bump();
+            match self.parse_hash_entry() {
+                Some(entry) => entries.push(entry),
+                // The error is reported; keep the entries parsed so far so
+                // that an enclosing expression carries on after the `}`.
+                None => {
+                    self.skip_literal_entries();
+                    return Some(Expr::new(ExprKind::Hash(map_type, entries), self.span_from(&start)));
+                }
+            }
+
+            if !self.next_token_is(&Token::Comma) {
+                break;
+            }
+            self.bump();
+        }
+
+        if !self.expect_next_token(Token::RBrace) {
+            return None;
+        }
+        Some(Expr::new(ExprKind::Hash(map_type, entries), self.span_from(&start)))
+    }
+
+    fn parse_hash_entry(&mut self) -> Option<(Expr, Expr)> {
+        let key = self.parse_expr(Precedence::Lowest)?;
+        if !self.expect_next_token(Token::Colon) {
+            return None;
+        }
         self.bump();
+        let value = self.parse_expr(Precedence::Lowest)?;
+        Some((key, value))
+    }
 
-        match self.parse_ident() {
-            Some(ident) => {
-                params.push(ident);
-                param_types.push(self.parse_type().unwrap())
-            },
-            None => return None,
+    // skip_literal_entries moves to the `}` closing a broken map or struct
+    // literal.
+    fn skip_literal_entries(&mut self) {
+        let mut depth = 0;
+        loop {
+            match self.current_token {
+                Token::RBrace if depth == 0 => return,
+                Token::RBrace => depth -= 1,
+                Token::LBrace => depth += 1,
+                Token::Eof => return,
+                _ => {}
+            }
+            self.bump();
+        }
+    }
+
+    // `Point { x: 1, y: 2 }`
+    fn parse_struct_expr(&mut self) -> Option<Expr> {
+        let start = self.current_span.clone();
+        let name = self.parse_ident()?;
+        self.bump();
+        self.parse_struct_fields(name, start)
+    }
+
+    // parse_struct_fields parses the fields of a struct literal, starting at
+    // its `{`.
+    fn parse_struct_fields(&mut self, name: Ident, start: Span) -> Option<Expr> {
+        let mut fields = vec![];
+        if self.next_token_is(&Token::RBrace) {
+            self.bump();
+            return Some(Expr::new(ExprKind::Struct { name, fields }, self.span_from(&start)));
+        }
+
+        loop {
+            match self.parse_struct_field() {
+                Some(field) => fields.push(field),
+                // As with maps, the entries parsed so far are kept.
+                None => {
+                    self.skip_literal_entries();
+                    return Some(Expr::new(ExprKind::Struct { name, fields }, self.span_from(&start)));
+                }
+            }
+
+            if !self.next_token_is(&Token::Comma) {
+                break;
+            }
+            self.bump();
+        }
+
+        if !self.expect_next_token(Token::RBrace) {
+            self.skip_literal_entries();
+        }
+        Some(Expr::new(ExprKind::Struct { name, fields }, self.span_from(&start)))
+    }
+
+    fn parse_struct_field(&mut self) -> Option<(Ident, Expr)> {
+        let field = self.expect_next_ident()?;
+        if !self.expect_next_token(Token::Colon) {
+            return None;
+        }
+        self.bump();
+        let value = self.parse_expr(Precedence::Lowest)?;
+        Some((field, value))
+    }
+
+    // `Shape::Rect(1, 2)`. A variant without payload takes no parentheses.
+    fn parse_variant_expr(&mut self) -> Option<Expr> {
+        let start = self.current_span.clone();
+        let name = self.parse_ident()?;
+        self.parse_variant_args(name, start)
+    }
+
+    // parse_variant_args parses `::Variant(args)` after the name of an enum.
+    fn parse_variant_args(&mut self, name: Ident, start: Span) -> Option<Expr> {
+        self.bump();
+        let variant = self.expect_next_ident()?;
+
+        let mut args = vec![];
+        if self.next_token_is(&Token::LParen) {
+            self.bump();
+            args = self.parse_expr_list(Token::RParen)?;
+        }
+        Some(Expr::new(ExprKind::Variant { name, variant, args }, self.span_from(&start)))
+    }
+
+    // `match value { pattern if guard => body, ... }`. As in a `while`
+    // condition, the value cannot be a struct literal.
+    fn parse_match_expr(&mut self) -> Option<Expr> {
+        let start = self.current_span.clone();
+        self.bump();
+        let value = self.with_struct_literals(false, |parser| parser.parse_expr(Precedence::Lowest))?;
+        if !self.expect_next_token(Token::LBrace) {
+            return None;
+        }
+
+        let mut arms = vec![];
+        while !self.next_token_is(&Token::RBrace) {
+            self.bump();
+            let arm = self.node(SyntaxKind::MatchArm, |parser| {
+                parser.with_struct_literals(true, Self::parse_match_arm)
+            });
+            match arm {
+                Some(arm) => arms.push(arm),
+                None => {
+                    self.skip_literal_entries();
+                    return Some(Expr::new(ExprKind::Match { value: Box::new(value), arms }, self.span_from(&start)));
+                }
+            }
+            if !self.next_token_is(&Token::Comma) {
+                break;
+            }
+            self.bump();
+        }
+        if !self.expect_next_token(Token::RBrace) {
+            self.skip_literal_entries();
+        }
+
+        Some(Expr::new(ExprKind::Match { value: Box::new(value), arms }, self.span_from(&start)))
+    }
+
+    fn parse_match_arm(&mut self) -> Option<MatchArm> {
+        let pattern = self.node(SyntaxKind::Pattern, Self::parse_pattern)?;
+        let mut guard = None;
+        if self.next_token_is(&Token::If) {
+            self.bump();
+            self.bump();
+            guard = Some(self.parse_expr(Precedence::Lowest)?);
+        }
+        if !self.expect_next_token(Token::FatArrow) {
+            return None;
+        }
+
+        let body = if self.next_token_is(&Token::LBrace) {
+            self.bump();
+            self.node(SyntaxKind::Block, Self::parse_block_stmt)
+        } else {
+            self.bump();
+            let expr = self.parse_expr(Precedence::Lowest)?;
+            let span = expr.span.clone();
+            vec![Stmt::new(StmtKind::Tail(expr), span)]
         };
+        Some(MatchArm { pattern, guard, body })
+    }
 
-        while self.next_token_is(&Token::Comma) {
+    // Patterns are `_`, a name to bind, a literal, or a variant whose
+    // payload is matched by nested patterns.
+    fn parse_pattern(&mut self) -> Option<Pattern> {
+        match self.current_token.clone() {
+            Token::Ident(name) if name == "_" => Some(Pattern::Wildcard),
+            Token::Ident(_) if self.next_token_is(&Token::DoubleColon) || self.next_token_is(&Token::Dot) => {
+                self.parse_variant_pattern()
+            }
+            Token::Ident(name) => Some(Pattern::Binding(Ident(name))),
+            Token::Minus | Token::Int(_, _) | Token::Float(_) | Token::Char(_) | Token::String(_) | Token::Bool(_) | Token::True | Token::False => {
+                let literal = self.parse_expr(Precedence::Prefix)?;
+                let is_literal = match &literal.kind {
+                    ExprKind::Literal(_) => true,
+                    ExprKind::Prefix(Prefix::Minus, value) => {
+                        matches!(value.kind, ExprKind::Literal(Literal::Int(_, _) | Literal::Float(_)))
+                    }
+                    _ => false,
+                };
+                if !is_literal {
+                    self.diagnostics.push(Diagnostic::new("expected a literal pattern".to_owned(), literal.span));
+                    return None;
+                }
+                Some(Pattern::Literal(literal))
+            }
+            _ => {
+                self.error_at_current("expected a pattern");
+                None
+            }
+        }
+    }
+
+    fn parse_variant_pattern(&mut self) -> Option<Pattern> {
+        let mut name = self.parse_ident()?;
+        if self.next_token_is(&Token::Dot) {
             self.bump();
+            let type_name = self.expect_next_ident()?;
+            name = Ident(format!("{}.{}", name.0, type_name.0));
+        }
+        if !self.expect_next_token(Token::DoubleColon) {
+            return None;
+        }
+        let variant = self.expect_next_ident()?;
+
+        let mut fields = vec![];
+        if self.next_token_is(&Token::LParen) {
             self.bump();
+            while !self.next_token_is(&Token::RParen) {
+                self.bump();
+                fields.push(self.node(SyntaxKind::Pattern, Self::parse_pattern)?);
+                if !self.next_token_is(&Token::Comma) {
+                    break;
+                }
+                self.bump();
+            }
+            if !self.expect_next_token(Token::RParen) {
+                return None;
+            }
+        }
+        Some(Pattern::Variant { name, variant, fields })
+    }
 
-            match self.parse_ident() {
-                Some(ident) => {
-                    params.push(ident);
-                    param_types.push(self.parse_type().unwrap())
-                },
-                None => return None,
+    // `p.x`, or `t.0` for an element of a tuple. After the name of an
+    // imported file, `shapes.Rect { .. }` and `shapes.Shape::Circle(r)`
+    // build one of its types.
+    fn parse_field_expr(&mut self, object: Expr) -> Option<Expr> {
+        let field = match self.next_token {
+            Token::Int(index, None) => {
+                self.bump();
+                Ident(index.to_string())
+            }
+            _ => self.expect_next_ident()?,
+        };
+        if let ExprKind::Ident(module) = &object.kind {
+            let name = Ident(format!("{}.{}", module.0, field.0));
+            if self.next_token_is(&Token::DoubleColon) {
+                self.retag(SyntaxKind::VariantExpr);
+                return self.parse_variant_args(name, object.span);
+            }
+            if self.next_token_is(&Token::LBrace) && !self.no_struct_literal {
+                self.retag(SyntaxKind::StructExpr);
+                self.bump();
+                return self.parse_struct_fields(name, object.span);
             }
         }
-        if !self.expect_next_token(Token::RParen) {
+        let span = self.span_from(&object.span);
+        Some(Expr::new(ExprKind::Field {
+            object: Box::new(object),
+            field,
+        }, span))
+    }
+
+    fn parse_index_expr(&mut self, array: Expr) -> Option<Expr> {
+        self.bump();
+        let index = self.with_struct_literals(true, |parser| parser.parse_expr(Precedence::Lowest))?;
+        if !self.expect_next_token(Token::RBracket) {
             return None;
         }
 
-        Some((params, param_types))
+        let span = self.span_from(&array.span);
+        Some(Expr::new(ExprKind::Index {
+            array: Box::new(array),
+            index: Box::new(index),
+        }, span))
     }
 
     fn parse_call_expr(&mut self, func: Expr) -> Option<Expr> {
@@ -517,10 +1765,11 @@ impl Parser {
             None => return None,
         };
 
-        Some(Expr::Call {
+        let span = self.span_from(&func.span);
+        Some(Expr::new(ExprKind::Call {
             func: Box::new(func),
             args,
-        })
+        }, span))
     }
 }
 
@@ -540,22 +1789,22 @@ mod tests {
         }");
 
         let mut parser = Parser::new(new_lexer(input));
-        let programs = parser.parse();
+        let programs = parser.parse().unwrap();
         let program = programs.get(0).unwrap().clone();
         assert!(true);
         return;
         assert_eq!(
             vec![
-                Stmt::Expr(Expr::Literal(Literal::Int(1000))),
-                Stmt::Expr(Expr::Literal(Literal::Int(1000))),
-                Stmt::Expr(Expr::Literal(Literal::Int(1000))),
-                Stmt::Expr(Expr::If {
-                    cond: Box::new(Expr::Ident(Ident(String::from("x")))),
+                Stmt::from(StmtKind::Expr(ExprKind::Literal(Literal::Int(1000, None)).into())),
+                StmtKind::Expr(ExprKind::Literal(Literal::Int(1000, None)).into()).into(),
+                StmtKind::Expr(ExprKind::Literal(Literal::Int(1000, None)).into()).into(),
+                StmtKind::Expr(ExprKind::If {
+                    cond: Box::new(ExprKind::Ident(Ident(String::from("x"))).into()),
                     consequence: vec![
-                        Stmt::Assignment(Ident(String::from("x")), Expr::Literal(Literal::Int(1000)))
+                        StmtKind::Assignment(Ident(String::from("x")), ExprKind::Literal(Literal::Int(1000, None)).into()).into()
                     ],
                     alternative: None,
-                }),
+                }.into()).into(),
             ],
             program,
         );
@@ -565,19 +1814,22 @@ mod tests {
     fn test_func_ast_1() {
         let input = "func takevalues(x: int, y: int): int {takevalues()}";
         let mut parser = Parser::new(new_lexer(input));
-        let programs = parser.parse();
+        let programs = parser.parse().unwrap();
         let program = programs.get(0).unwrap().clone();
         let program2 = programs.get(1).unwrap().clone();
         println!("{:?}", program);
         assert_eq!(
-            vec![Stmt::Func{
+            vec![Stmt::from(StmtKind::Func{
                 distributed: false,
+                public: false,
+                type_params: vec![],
                 name: String::from("takevalues"),
+                doc: None,
                 params: vec![Ident(String::from("x")), Ident(String::from("y"))],
-                param_types: vec![LLVMExpressionType::Integer, LLVMExpressionType::Integer],
-                return_type: LLVMExpressionType::Integer,
-                body: vec![Stmt::Expr(Expr::Call{func: Box::new(Expr::Ident(Ident(String::from("takevalues")))), args: vec![]})],
-            }],
+                param_types: vec![LLVMExpressionType::Integer(IntKind::I32), LLVMExpressionType::Integer(IntKind::I32)],
+                return_type: LLVMExpressionType::Integer(IntKind::I32),
+                body: vec![StmtKind::Tail(ExprKind::Call{func: Box::new(ExprKind::Ident(Ident(String::from("takevalues"))).into()), args: vec![]}.into()).into()],
+            })],
             program,
         );
     }
@@ -586,20 +1838,647 @@ mod tests {
     fn test_func_ast_2() {
         let input = "dis func takevalues(x: int, y: int): int {}";
         let mut parser = Parser::new(new_lexer(input));
-        let programs = parser.parse();
+        let programs = parser.parse().unwrap();
         let program = programs.get(0).unwrap().clone();
         let program2 = programs.get(1).unwrap().clone();
         assert_eq!(
-            vec![Stmt::Func{
+            vec![Stmt::from(StmtKind::Func{
                 distributed: true,
+                public: false,
+                type_params: vec![],
                 name: String::from("takevalues"),
+                doc: None,
                 params: vec![Ident(String::from("x")), Ident(String::from("y"))],
-                param_types: vec![LLVMExpressionType::Integer, LLVMExpressionType::Integer],
-                return_type: LLVMExpressionType::Integer,
+                param_types: vec![LLVMExpressionType::Integer(IntKind::I32), LLVMExpressionType::Integer(IntKind::I32)],
+                return_type: LLVMExpressionType::Integer(IntKind::I32),
                 body: vec![],
-            }],
+            })],
             program2,
         );
     }
-}
 
+    #[test]
+    fn test_spans() {
+        let input = "func main(): int {\n    let x: int = -1;\n    return x;\n}";
+        let mut parser = Parser::new(new_file_lexer("main.flash", input));
+        let programs = parser.parse().unwrap();
+        let program = programs.get(0).unwrap().clone();
+
+        let func = &program[0];
+        assert_eq!((func.span.start, func.span.end), (0, input.len()));
+        let body = match &func.kind {
+            StmtKind::Func { body, .. } => body,
+            _ => panic!("expected a function, got {:?}", func),
+        };
+        assert_eq!(format!("{}", body[0].span), "main.flash:2:5");
+        assert_eq!(&input[body[0].span.start..body[0].span.end], "let x: int = -1;");
+        assert_eq!(format!("{}", body[1].span), "main.flash:3:5");
+        match &body[1].kind {
+            StmtKind::Return(expr) => assert_eq!(format!("{}", expr.span), "main.flash:3:12"),
+            _ => panic!("expected a return, got {:?}", body[1]),
+        }
+    }
+
+    #[test]
+    fn test_doc_comments() {
+        let input = "
+        // not documentation
+        /// Returns its argument.
+        /// Used by the tests.
+        dis func id(x: int): int {
+            /// dangling
+            return x;
+        }";
+        let mut parser = Parser::new(new_lexer(input));
+        let programs = parser.parse().unwrap();
+        let program = programs.get(0).unwrap().clone();
+        assert_eq!(program.len(), 1);
+        match &program[0].kind {
+            StmtKind::Func { doc, body, distributed, .. } => {
+                assert_eq!(doc.as_deref(), Some("Returns its argument.\nUsed by the tests."));
+                assert!(*distributed);
+                assert_eq!(body.len(), 1);
+            }
+            _ => panic!("expected a function, got {:?}", program[0]),
+        }
+
+        // Anywhere else a doc comment is skipped like any other comment.
+        let program = Parser::new(new_lexer("let x: int = 1 /// c\n + 2;")).parse().unwrap().remove(0);
+        assert_eq!(program, vec![Stmt::from(StmtKind::Let(
+            Ident("x".to_owned()),
+            infix(Infix::Plus, ExprKind::Literal(Literal::Int(1, None)).into(), ExprKind::Literal(Literal::Int(2, None)).into()),
+            Some(LLVMExpressionType::Integer(IntKind::I32)),
+        ))]);
+
+        let program = Parser::new(new_lexer("let a = [1, /// one\n 2];")).parse().unwrap().remove(0);
+        assert_eq!(program, vec![Stmt::from(StmtKind::Let(
+            Ident("a".to_owned()),
+            ExprKind::Array(vec![ExprKind::Literal(Literal::Int(1, None)).into(), ExprKind::Literal(Literal::Int(2, None)).into()]).into(),
+            None,
+        ))]);
+    }
+
+    fn parse_single_expr(input: &str) -> Expr {
+        let mut parser = Parser::new(new_lexer(input));
+        let program = parser.parse().unwrap().remove(0);
+        match &program[0].kind {
+            StmtKind::Expr(expr) => expr.clone(),
+            _ => panic!("expected an expression, got {:?}", program[0]),
+        }
+    }
+
+    fn infix(infix: Infix, left: Expr, right: Expr) -> Expr {
+        ExprKind::Infix(infix, Box::new(left), Box::new(right)).into()
+    }
+
+    fn ident(name: &str) -> Expr {
+        ExprKind::Ident(Ident(name.to_owned())).into()
+    }
+
+    #[test]
+    fn test_operator_precedence() {
+        let tests = vec![
+            ("a + b * c", infix(Infix::Plus, ident("a"), infix(Infix::Multiply, ident("b"), ident("c")))),
+            ("a - b - c", infix(Infix::Minus, infix(Infix::Minus, ident("a"), ident("b")), ident("c"))),
+            ("a / b % c", infix(Infix::Modulo, infix(Infix::Divide, ident("a"), ident("b")), ident("c"))),
+            ("a <= b == c > d", infix(Infix::Equal,
+                infix(Infix::LessEqual, ident("a"), ident("b")),
+                infix(Infix::GreaterThan, ident("c"), ident("d")))),
+            ("a | b ^ c & d", infix(Infix::BitOr, ident("a"),
+                infix(Infix::BitXor, ident("b"), infix(Infix::BitAnd, ident("c"), ident("d"))))),
+            ("a << b + c", infix(Infix::ShiftLeft, ident("a"), infix(Infix::Plus, ident("b"), ident("c")))),
+            ("-a as u8 * b", infix(Infix::Multiply,
+                ExprKind::Cast(
+                    Box::new(ExprKind::Prefix(Prefix::Minus, Box::new(ident("a"))).into()),
+                    LLVMExpressionType::Integer(IntKind::U8),
+                ).into(),
+                ident("b"))),
+            ("a == b && c || d", infix(Infix::Or,
+                infix(Infix::And, infix(Infix::Equal, ident("a"), ident("b")), ident("c")),
+                ident("d"))),
+            ("!a == -b", infix(Infix::Equal,
+                ExprKind::Prefix(Prefix::Not, Box::new(ident("a"))).into(),
+                ExprKind::Prefix(Prefix::Minus, Box::new(ident("b"))).into())),
+            ("(a + b) * f(c)", infix(Infix::Multiply,
+                infix(Infix::Plus, ident("a"), ident("b")),
+                ExprKind::Call { func: Box::new(ident("f")), args: vec![ident("c")] }.into())),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(parse_single_expr(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_compound_assignment() {
+        let mut parser = Parser::new(new_lexer("x <<= y + 1;"));
+        let program = parser.parse().unwrap().remove(0);
+        let one: Expr = ExprKind::Literal(Literal::Int(1, None)).into();
+        assert_eq!(
+            program,
+            vec![Stmt::from(StmtKind::Assignment(
+                Ident("x".to_owned()),
+                infix(Infix::ShiftLeft, ident("x"), infix(Infix::Plus, ident("y"), one)),
+            ))],
+        );
+    }
+
+    #[test]
+    fn test_let_without_type() {
+        let input = "let a = 1;\nlet b: u8 = 2;\nfor let i = 0; i < 3; i += 1 {}";
+        let mut parser = Parser::new(new_lexer(input));
+        let program = parser.parse().unwrap().remove(0);
+        let int = |n: u64| -> Expr { ExprKind::Literal(Literal::Int(n, None)).into() };
+        assert_eq!(program[0], StmtKind::Let(Ident("a".to_owned()), int(1), None).into());
+        assert_eq!(program[1], StmtKind::Let(Ident("b".to_owned()), int(2), Some(LLVMExpressionType::Integer(IntKind::U8))).into());
+        match &program[2].kind {
+            StmtKind::For { init: Some(init), .. } => assert_eq!(**init, StmtKind::Let(Ident("i".to_owned()), int(0), None).into()),
+            kind => panic!("expected a for loop, got {:?}", kind),
+        }
+
+        assert_eq!(parse_errors("let a 1;\nlet b = ;"), vec![
+            "<input>:1:7: expected `:` or `=`, found `1`",
+            "<input>:2:9: expected an expression, found `;`",
+        ]);
+    }
+
+    #[test]
+    fn test_loops() {
+        let mut parser = Parser::new(new_lexer("outer: for let i: int = 0; i < n; i += 1 { while ok { break outer; } continue; }\nfor ;; { break; }"));
+        let program = parser.parse().unwrap().remove(0);
+        let zero: Expr = ExprKind::Literal(Literal::Int(0, None)).into();
+        let one: Expr = ExprKind::Literal(Literal::Int(1, None)).into();
+        assert_eq!(program, vec![
+            Stmt::from(StmtKind::For {
+                label: Some(Ident("outer".to_owned())),
+                init: Some(Box::new(StmtKind::Let(Ident("i".to_owned()), zero, Some(LLVMExpressionType::Integer(IntKind::I32))).into())),
+                cond: Some(infix(Infix::LessThan, ident("i"), ident("n"))),
+                step: Some(Box::new(StmtKind::Assignment(Ident("i".to_owned()), infix(Infix::Plus, ident("i"), one)).into())),
+                body: vec![
+                    StmtKind::While {
+                        label: None,
+                        cond: ident("ok"),
+                        body: vec![StmtKind::Break(Some(Ident("outer".to_owned()))).into()],
+                    }.into(),
+                    StmtKind::Continue(None).into(),
+                ],
+            }),
+            Stmt::from(StmtKind::For {
+                label: None,
+                init: None,
+                cond: None,
+                step: None,
+                body: vec![StmtKind::Break(None).into()],
+            }),
+        ]);
+
+        assert_eq!(parse_errors("outer: let x: int = 1;"), vec![
+            "<input>:1:8: expected a loop after a label, found `let`",
+        ]);
+        assert_eq!(parse_errors("for let i: int = 0; i < 3 i += 1 {}\nbreak;"), vec![
+            "<input>:1:27: expected `;`, found `i`",
+        ]);
+    }
+
+    #[test]
+    fn test_if_expr() {
+        let value = |n: u64| -> Expr { ExprKind::Literal(Literal::Int(n, None)).into() };
+        let mut parser = Parser::new(new_lexer("if (a) { 1 } else if (b) { f(); 2 } else { 3; }"));
+        let program = parser.parse().unwrap().remove(0);
+        let call: Expr = ExprKind::Call { func: Box::new(ident("f")), args: vec![] }.into();
+        assert_eq!(program, vec![Stmt::from(StmtKind::Expr(ExprKind::If {
+            cond: Box::new(ident("a")),
+            consequence: vec![StmtKind::Tail(value(1)).into()],
+            alternative: Some(vec![StmtKind::Tail(ExprKind::If {
+                cond: Box::new(ident("b")),
+                consequence: vec![StmtKind::Expr(call).into(), StmtKind::Tail(value(2)).into()],
+                alternative: Some(vec![StmtKind::Expr(value(3)).into()]),
+            }.into()).into()]),
+        }.into()))]);
+    }
+
+    #[test]
+    fn test_arrays() {
+        let mut parser = Parser::new(new_lexer("let a: [[u8; 2]; 3] = [[1], f(x)[0]];\na[i][0] += 2;\na[0];"));
+        let program = parser.parse().unwrap().remove(0);
+        let int = |n: u64| -> Expr { ExprKind::Literal(Literal::Int(n, None)).into() };
+        let index = |array: Expr, index: Expr| -> Expr {
+            ExprKind::Index { array: Box::new(array), index: Box::new(index) }.into()
+        };
+        let call: Expr = ExprKind::Call { func: Box::new(ident("f")), args: vec![ident("x")] }.into();
+        let row = LLVMExpressionType::Array(Box::new(LLVMExpressionType::Integer(IntKind::U8)), 2);
+        assert_eq!(program, vec![
+            StmtKind::Let(
+                Ident("a".to_owned()),
+                ExprKind::Array(vec![ExprKind::Array(vec![int(1)]).into(), index(call, int(0))]).into(),
+                Some(LLVMExpressionType::Array(Box::new(row), 3)),
+            ).into(),
+            StmtKind::IndexAssignment {
+                array: index(ident("a"), ident("i")),
+                index: int(0),
+                op: Some(Infix::Plus),
+                value: int(2),
+            }.into(),
+            StmtKind::Expr(index(ident("a"), int(0))).into(),
+        ]);
+
+        assert_eq!(parse_errors("let a: [int; n] = [1];\nlet b: [int 2] = [1];"), vec![
+            "<input>:1:14: expected an array length, found `n`",
+            "<input>:2:13: expected `;`, found `2`",
+        ]);
+    }
+
+    #[test]
+    fn test_maps() {
+        let mut parser = Parser::new(new_lexer("let m: map[string][int; 2] = map[string][int; 2]{\"a\": [1, 2], k: v};\nm[\"b\"] = [3, 4];\nlet e: map[char]bool = map[char]bool{};"));
+        let program = parser.parse().unwrap().remove(0);
+        let string = |s: &str| -> Expr { ExprKind::Literal(Literal::String(s.to_owned())).into() };
+        let int = |n: u64| -> Expr { ExprKind::Literal(Literal::Int(n, None)).into() };
+        let pair: Expr = ExprKind::Array(vec![int(1), int(2)]).into();
+        let map_type = LLVMExpressionType::Map(
+            Box::new(LLVMExpressionType::String),
+            Box::new(LLVMExpressionType::Array(Box::new(LLVMExpressionType::Integer(IntKind::I32)), 2)),
+        );
+        let flags = LLVMExpressionType::Map(Box::new(LLVMExpressionType::Char), Box::new(LLVMExpressionType::Boolean));
+        assert_eq!(program, vec![
+            StmtKind::Let(
+                Ident("m".to_owned()),
+                ExprKind::Hash(map_type.clone(), vec![(string("a"), pair), (ident("k"), ident("v"))]).into(),
+                Some(map_type),
+            ).into(),
+            StmtKind::IndexAssignment {
+                array: ident("m"),
+                index: string("b"),
+                op: None,
+                value: ExprKind::Array(vec![int(3), int(4)]).into(),
+            }.into(),
+            StmtKind::Let(Ident("e".to_owned()), ExprKind::Hash(flags.clone(), vec![]).into(), Some(flags)).into(),
+        ]);
+
+        let input = "let m: map[int]int = map[int]int{1 2};\nlet n: map[int] = m;\nlet o: map[int]map[int]int = map[int]map[int]int{1: map[int]int{2: }, 3: m};";
+        assert_eq!(parse_errors(input), vec![
+            "<input>:1:36: expected `:`, found `2`",
+            "<input>:2:17: expected a type, found `=`",
+            "<input>:3:68: expected an expression, found `}`",
+        ]);
+    }
+
+    #[test]
+    fn test_structs() {
+        let input = "struct Line { from: Point, to: Point }\nlet l: Line = Line { from: p, to: Point { x: 1, y: 2 } };\nl.to.x += 1;\nwhile l.from.x < n { l.from = f(Point {}); }";
+        let mut parser = Parser::new(new_lexer(input));
+        let program = parser.parse().unwrap().remove(0);
+        let int = |n: u64| -> Expr { ExprKind::Literal(Literal::Int(n, None)).into() };
+        let name = |name: &str| Ident(name.to_owned());
+        let field = |object: Expr, field: &str| -> Expr {
+            ExprKind::Field { object: Box::new(object), field: name(field) }.into()
+        };
+        let point = LLVMExpressionType::Named("Point".to_owned());
+        assert_eq!(program, vec![
+            StmtKind::Struct { name: name("Line"), fields: vec![(name("from"), point.clone()), (name("to"), point)] }.into(),
+            StmtKind::Let(
+                name("l"),
+                ExprKind::Struct {
+                    name: name("Line"),
+                    fields: vec![
+                        (name("from"), ident("p")),
+                        (name("to"), ExprKind::Struct { name: name("Point"), fields: vec![(name("x"), int(1)), (name("y"), int(2))] }.into()),
+                    ],
+                }.into(),
+                Some(LLVMExpressionType::Named("Line".to_owned())),
+            ).into(),
+            StmtKind::FieldAssignment {
+                object: field(ident("l"), "to"),
+                field: name("x"),
+                op: Some(Infix::Plus),
+                value: int(1),
+            }.into(),
+            StmtKind::While {
+                label: None,
+                cond: infix(Infix::LessThan, field(field(ident("l"), "from"), "x"), ident("n")),
+                body: vec![StmtKind::FieldAssignment {
+                    object: ident("l"),
+                    field: name("from"),
+                    op: None,
+                    value: ExprKind::Call {
+                        func: Box::new(ident("f")),
+                        args: vec![ExprKind::Struct { name: name("Point"), fields: vec![] }.into()],
+                    }.into(),
+                }.into()],
+            }.into(),
+        ]);
+
+        let input = "let p: Point = Point { x: 1 y: 2 };\nlet q: int = p.;\nfunc f(): int {\n    return 1;\nstruct S { a: int }";
+        assert_eq!(parse_errors(input), vec![
+            "<input>:1:29: expected `}`, found `y`",
+            "<input>:2:16: expected an identifier, found `;`",
+            "<input>:5:1: expected `}`, found `struct`",
+        ]);
+    }
+
+    #[test]
+    fn test_enums() {
+        let input = "enum Shape { Circle(int), Rect(int, [u8; 2]), Empty }\nlet s: Shape = Shape::Rect(1, [2, 3]);\nlet n: int = match s { Shape::Circle(r) if r > 0 => r, Shape::Rect(w, _) => { w }, _ => -1, };\nmatch n { 0 => f(Shape::Empty), x => x };";
+        let mut parser = Parser::new(new_lexer(input));
+        let program = parser.parse().unwrap().remove(0);
+        let int = |n: u64| -> Expr { ExprKind::Literal(Literal::Int(n, None)).into() };
+        let name = |name: &str| Ident(name.to_owned());
+        let variant = |variant: &str, fields: Vec<Pattern>| Pattern::Variant { name: name("Shape"), variant: name(variant), fields };
+        let tail = |expr: Expr| vec![Stmt::from(StmtKind::Tail(expr))];
+        assert_eq!(program, vec![
+            StmtKind::Enum {
+                name: name("Shape"),
+                variants: vec![
+                    (name("Circle"), vec![LLVMExpressionType::Integer(IntKind::I32)]),
+                    (name("Rect"), vec![
+                        LLVMExpressionType::Integer(IntKind::I32),
+                        LLVMExpressionType::Array(Box::new(LLVMExpressionType::Integer(IntKind::U8)), 2),
+                    ]),
+                    (name("Empty"), vec![]),
+                ],
+            }.into(),
+            StmtKind::Let(
+                name("s"),
+                ExprKind::Variant {
+                    name: name("Shape"),
+                    variant: name("Rect"),
+                    args: vec![int(1), ExprKind::Array(vec![int(2), int(3)]).into()],
+                }.into(),
+                Some(LLVMExpressionType::Named("Shape".to_owned())),
+            ).into(),
+            StmtKind::Let(
+                name("n"),
+                ExprKind::Match {
+                    value: Box::new(ident("s")),
+                    arms: vec![
+                        MatchArm {
+                            pattern: variant("Circle", vec![Pattern::Binding(name("r"))]),
+                            guard: Some(infix(Infix::GreaterThan, ident("r"), int(0))),
+                            body: tail(ident("r")),
+                        },
+                        MatchArm {
+                            pattern: variant("Rect", vec![Pattern::Binding(name("w")), Pattern::Wildcard]),
+                            guard: None,
+                            body: tail(ident("w")),
+                        },
+                        MatchArm {
+                            pattern: Pattern::Wildcard,
+                            guard: None,
+                            body: tail(ExprKind::Prefix(Prefix::Minus, Box::new(int(1))).into()),
+                        },
+                    ],
+                }.into(),
+                Some(LLVMExpressionType::Integer(IntKind::I32)),
+            ).into(),
+            StmtKind::Expr(ExprKind::Match {
+                value: Box::new(ident("n")),
+                arms: vec![
+                    MatchArm {
+                        pattern: Pattern::Literal(int(0)),
+                        guard: None,
+                        body: tail(ExprKind::Call {
+                            func: Box::new(ident("f")),
+                            args: vec![ExprKind::Variant { name: name("Shape"), variant: name("Empty"), args: vec![] }.into()],
+                        }.into()),
+                    },
+                    MatchArm { pattern: Pattern::Binding(name("x")), guard: None, body: tail(ident("x")) },
+                ],
+            }.into()).into(),
+        ]);
+
+        let input = "enum E { A(int) B }\nlet x: int = match e { E::A(a) a };\nlet y: int = match e { -x => 1 };\nlet z: int = 1;";
+        assert_eq!(parse_errors(input), vec![
+            "<input>:1:17: expected `}`, found `B`",
+            "<input>:2:32: expected `=>`, found `a`",
+            "<input>:3:24: expected a literal pattern",
+        ]);
+    }
+
+    #[test]
+    fn test_closures() {
+        let input = "func apply(f: func(int, int): int, g: func(): void): int { return f(1, 2); }\nlet k: int = 3;\nlet add: func(int, int): int = func(a: int, b: int): int { return a + b + k; };\nmake()(4);";
+        let mut parser = Parser::new(new_lexer(input));
+        let program = parser.parse().unwrap().remove(0);
+        let int = LLVMExpressionType::Integer(IntKind::I32);
+        let name = |name: &str| Ident(name.to_owned());
+        let binary = LLVMExpressionType::Function(vec![int.clone(), int.clone()], Box::new(int.clone()));
+        let call = |func: Expr, args: Vec<Expr>| -> Expr { ExprKind::Call { func: Box::new(func), args }.into() };
+        let literal = |n: u64| -> Expr { ExprKind::Literal(Literal::Int(n, None)).into() };
+        assert_eq!(program, vec![
+            StmtKind::Func {
+                name: "apply".to_owned(),
+                doc: None,
+                distributed: false,
+                public: false,
+                type_params: vec![],
+                params: vec![name("f"), name("g")],
+                param_types: vec![
+                    binary.clone(),
+                    LLVMExpressionType::Function(vec![], Box::new(LLVMExpressionType::Null)),
+                ],
+                return_type: int.clone(),
+                body: vec![StmtKind::Return(call(ident("f"), vec![literal(1), literal(2)])).into()],
+            }.into(),
+            StmtKind::Let(name("k"), literal(3), Some(int.clone())).into(),
+            StmtKind::Let(
+                name("add"),
+                ExprKind::Closure {
+                    params: vec![name("a"), name("b")],
+                    param_types: vec![int.clone(), int.clone()],
+                    return_type: int.clone(),
+                    body: vec![StmtKind::Return(infix(
+                        Infix::Plus,
+                        infix(Infix::Plus, ident("a"), ident("b")),
+                        ident("k"),
+                    )).into()],
+                }.into(),
+                Some(binary.clone()),
+            ).into(),
+            StmtKind::Expr(call(call(ident("make"), vec![]), vec![literal(4)])).into(),
+        ]);
+        assert_eq!(binary.to_string(), "func(int, int): int");
+
+        assert_eq!(parse_errors("let f: func(int) = g;\nlet h: int = func(x: int) { return x; };"), vec![
+            "<input>:1:18: expected `:`, found `=`",
+            "<input>:2:27: expected `:`, found `{`",
+        ]);
+    }
+
+    #[test]
+    fn test_imports() {
+        let input = "import math;\nimport \"lib/geo.flash\"\n/// Squares.\npub func sq(x: int): int { return math.mul(x, x); }\npub dis func one(): int { return 1; }";
+        let mut parser = Parser::new(new_lexer(input));
+        let program = parser.parse().unwrap().remove(0);
+        let name = |name: &str| Ident(name.to_owned());
+        assert_eq!(program[0], StmtKind::Import { name: name("math"), path: "math.flash".to_owned() }.into());
+        assert_eq!(program[1], StmtKind::Import { name: name("geo"), path: "lib/geo.flash".to_owned() }.into());
+        match &program[2].kind {
+            StmtKind::Func { name: func, doc, public, body, .. } => {
+                assert_eq!(func, "sq");
+                assert_eq!(doc.as_deref(), Some("Squares."));
+                assert!(*public);
+                assert_eq!(body[0], StmtKind::Return(ExprKind::Call {
+                    func: Box::new(ExprKind::Field { object: Box::new(ident("math")), field: name("mul") }.into()),
+                    args: vec![ident("x"), ident("x")],
+                }.into()).into());
+            }
+            kind => panic!("expected a function, got {:?}", kind),
+        }
+        assert!(matches!(program[3].kind, StmtKind::Func { public: true, distributed: true, .. }));
+        assert_eq!(&input[program[3].span.start..program[3].span.start + 3], "pub");
+
+        // The types of an imported file are qualified with its name.
+        let input = "let r: [geo.Rect; 1] = [geo.Rect { w: 1 }];\nmatch s { geo.Shape::Dot(_) => 1, _ => 0 };\nwhile geo.Shape::Empty == s {}";
+        let program = Parser::new(new_lexer(input)).parse().unwrap().remove(0);
+        let rect = ExprKind::Struct { name: name("geo.Rect"), fields: vec![(name("w"), ExprKind::Literal(Literal::Int(1, None)).into())] };
+        assert_eq!(program[0], StmtKind::Let(
+            name("r"),
+            ExprKind::Array(vec![rect.into()]).into(),
+            Some(LLVMExpressionType::Array(Box::new(LLVMExpressionType::Named("geo.Rect".to_owned())), 1)),
+        ).into());
+        match &program[1].kind {
+            StmtKind::Expr(Expr { kind: ExprKind::Match { arms, .. }, .. }) => assert_eq!(arms[0].pattern, Pattern::Variant {
+                name: name("geo.Shape"),
+                variant: name("Dot"),
+                fields: vec![Pattern::Wildcard],
+            }),
+            kind => panic!("expected a match, got {:?}", kind),
+        }
+        match &program[2].kind {
+            StmtKind::While { cond, .. } => assert_eq!(*cond, infix(
+                Infix::Equal,
+                ExprKind::Variant { name: name("geo.Shape"), variant: name("Empty"), args: vec![] }.into(),
+                ident("s"),
+            )),
+            kind => panic!("expected a loop, got {:?}", kind),
+        }
+
+        assert_eq!(parse_errors("import 1;\npub let x: int = 1;\nfunc f(): int {\n    return 1;\nimport math;"), vec![
+            "<input>:1:8: expected a module name or path, found `1`",
+            "<input>:2:5: expected `func`, found `let`",
+            "<input>:5:1: expected `}`, found `import`",
+        ]);
+    }
+
+    #[test]
+    fn test_generics() {
+        let input = "func max<T>(a: T, b: T): T { return if (a > b) { a } else { b }; }\npub func pair<K, V>(k: K, v: V): map[K]V { return map[K]V{k: v}; }";
+        let mut parser = Parser::new(new_lexer(input));
+        let program = parser.parse().unwrap().remove(0);
+        let named = |name: &str| LLVMExpressionType::Named(name.to_owned());
+        match &program[0].kind {
+            StmtKind::Func { name, type_params, param_types, return_type, .. } => {
+                assert_eq!(name, "max");
+                assert_eq!(*type_params, vec![Ident("T".to_owned())]);
+                assert_eq!(*param_types, vec![named("T"), named("T")]);
+                assert_eq!(*return_type, named("T"));
+            }
+            kind => panic!("expected a function, got {:?}", kind),
+        }
+        match &program[1].kind {
+            StmtKind::Func { type_params, return_type, public, .. } => {
+                assert_eq!(*type_params, vec![Ident("K".to_owned()), Ident("V".to_owned())]);
+                assert_eq!(*return_type, LLVMExpressionType::Map(Box::new(named("K")), Box::new(named("V"))));
+                assert!(*public);
+            }
+            kind => panic!("expected a function, got {:?}", kind),
+        }
+
+        assert_eq!(parse_errors("func f<>(): int { return 1; }\nfunc g<T U>(a: T): T { return a; }"), vec![
+            "<input>:1:8: expected an identifier, found `>`",
+            "<input>:2:10: expected `>`, found `U`",
+        ]);
+    }
+
+    #[test]
+    fn test_tuples() {
+        let input = "func divmod(a: int, b: int): (int, int) { return (a / b, a % b); }\nlet (q, _): (int, int) = divmod(7, 2);\nlet t = ((1, true), 2.5);\nt.0.1 = false;\nlet x = (1);";
+        let mut parser = Parser::new(new_lexer(input));
+        let program = parser.parse().unwrap().remove(0);
+        let int = LLVMExpressionType::Integer(IntKind::I32);
+        let name = |name: &str| Ident(name.to_owned());
+        let literal = |n: u64| -> Expr { ExprKind::Literal(Literal::Int(n, None)).into() };
+        let field = |object: Expr, field: &str| -> Expr { ExprKind::Field { object: Box::new(object), field: name(field) }.into() };
+        match &program[0].kind {
+            StmtKind::Func { return_type, body, .. } => {
+                assert_eq!(*return_type, LLVMExpressionType::Tuple(vec![int.clone(), int.clone()]));
+                assert_eq!(body[0], StmtKind::Return(ExprKind::Tuple(vec![
+                    ExprKind::Infix(Infix::Divide, Box::new(ident("a")), Box::new(ident("b"))).into(),
+                    ExprKind::Infix(Infix::Modulo, Box::new(ident("a")), Box::new(ident("b"))).into(),
+                ]).into()).into());
+            }
+            kind => panic!("expected a function, got {:?}", kind),
+        }
+        assert_eq!(program[1], StmtKind::LetTuple(
+            vec![name("q"), name("_")],
+            ExprKind::Call { func: Box::new(ident("divmod")), args: vec![literal(7), literal(2)] }.into(),
+            Some(LLVMExpressionType::Tuple(vec![int.clone(), int.clone()])),
+        ).into());
+        assert_eq!(program[2], StmtKind::Let(
+            name("t"),
+            ExprKind::Tuple(vec![
+                ExprKind::Tuple(vec![literal(1), ExprKind::Literal(Literal::Bool(true)).into()]).into(),
+                ExprKind::Literal(Literal::Float(2.5)).into(),
+            ]).into(),
+            None,
+        ).into());
+        assert_eq!(program[3], StmtKind::FieldAssignment {
+            object: field(ident("t"), "0"),
+            field: name("1"),
+            op: None,
+            value: ExprKind::Literal(Literal::Bool(false)).into(),
+        }.into());
+        // Parentheses around one expression only group it.
+        assert_eq!(program[4], StmtKind::Let(name("x"), literal(1), None).into());
+
+        assert_eq!(parse_errors("let a: (int) = 1;\nlet (b c) = d;\nlet (e) int = f;"), vec![
+            "<input>:1:12: expected `,`, found `)`",
+            "<input>:2:8: expected `)`, found `c`",
+            "<input>:3:9: expected `:` or `=`, found `int`",
+        ]);
+    }
+
+    #[test]
+    fn test_lex_errors() {
+        let mut parser = Parser::new(new_lexer("let a: int = 1;\n$ \"\\q\" 3;\nlet b: int = 2;"));
+        let messages: Vec<String> = parser.parse().unwrap_err().iter().map(|error| error.to_string()).collect();
+        assert_eq!(messages, vec![
+            "<input>:2:1: unexpected character `$`",
+            "<input>:2:3: unknown escape sequence `\\q`",
+        ]);
+    }
+
+    fn parse_errors(input: &str) -> Vec<String> {
+        let mut parser = Parser::new(new_lexer(input));
+        parser.parse().unwrap_err().iter().map(|error| error.to_string()).collect()
+    }
+
+    #[test]
+    fn test_error_recovery() {
+        let input = "let a int = 1;\nlet b: int = ;\nfunc f(x: int): { return x; }\nfunc g(): int {\n    let c: int = 1 +;\n    c = );\n    return c;\n}\nlet d: int = (1 + 2;\n";
+        assert_eq!(parse_errors(input), vec![
+            "<input>:1:7: expected `:` or `=`, found `int`",
+            "<input>:2:14: expected an expression, found `;`",
+            "<input>:3:17: expected a type, found `{`",
+            "<input>:5:21: expected an expression, found `;`",
+            "<input>:6:9: expected an expression, found `)`",
+            "<input>:9:20: expected `)`, found `;`",
+        ]);
+
+        // An unclosed body ends at the next function.
+        assert_eq!(parse_errors("func f(): int {\n    return 1;\nfunc g(): int { return @; }"), vec![
+            "<input>:3:1: expected `}`, found `func`",
+            "<input>:3:24: unexpected character `@`",
+        ]);
+        assert_eq!(parse_errors("func f(): int {\n    return 1;\n"), vec![
+            "<input>:3:1: expected `}`, found end of file",
+        ]);
+        assert_eq!(parse_errors("let 1: int = 2; let x: int = 3 as;"), vec![
+            "<input>:1:5: expected an identifier, found `1`",
+            "<input>:1:34: expected a type, found `;`",
+        ]);
+
+        let mut parser = Parser::new(new_lexer("let a: int = 1;; void_fn();"));
+        assert_eq!(parser.parse().unwrap()[0].len(), 2);
+    }
+}
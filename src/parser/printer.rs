@@ -0,0 +1,668 @@
+use crate::parser::ast::*;
+
+// print_program writes a program back out as flash source. The output is
+// canonical: parsing it gives the same tree, and printing that tree again
+// gives the same text. Comments are lost, except for doc comments.
+pub fn print_program(program: &Program) -> String {
+    let mut printer = Printer::new();
+    for (index, statement) in program.iter().enumerate() {
+        if matches!(statement.kind, StmtKind::Blank) {
+            continue;
+        }
+        // Declarations are set apart by a blank line.
+        if index > 0 && (is_declaration(statement) || is_declaration(&program[index - 1])) {
+            printer.out.push('\n');
+        }
+        printer.statement(statement);
+        printer.out.push('\n');
+    }
+    printer.out
+}
+
+// print_statement writes one statement, as it would appear at the top level.
+pub fn print_statement(statement: &Stmt) -> String {
+    let mut printer = Printer::new();
+    printer.statement(statement);
+    printer.out
+}
+
+pub fn print_expression(expr: &Expr) -> String {
+    let mut printer = Printer::new();
+    printer.expression(expr);
+    printer.out
+}
+
+struct Printer {
+    out: String,
+    indent: usize,
+    // Set where the parser takes `Name {` for the start of a block, as in
+    // loop conditions. Struct literals are put in parentheses there.
+    no_struct_literal: bool,
+}
+
+impl Printer {
+    fn new() -> Self {
+        Printer { out: String::new(), indent: 0, no_struct_literal: false }
+    }
+
+    fn newline(&mut self) {
+        self.out.push('\n');
+        self.out.push_str(&"    ".repeat(self.indent));
+    }
+
+    fn block(&mut self, block: &BlockStmt) {
+        let statements: Vec<&Stmt> = block.iter().filter(|statement| !matches!(statement.kind, StmtKind::Blank)).collect();
+        if statements.is_empty() {
+            self.out.push_str("{}");
+            return;
+        }
+        let saved = std::mem::replace(&mut self.no_struct_literal, false);
+        self.out.push('{');
+        self.indent += 1;
+        for statement in statements {
+            self.newline();
+            self.statement(statement);
+        }
+        self.indent -= 1;
+        self.newline();
+        self.out.push('}');
+        self.no_struct_literal = saved;
+    }
+
+    fn statement(&mut self, statement: &Stmt) {
+        match &statement.kind {
+            StmtKind::Func { name, doc, distributed, public, type_params, params, param_types, return_type, body } => {
+                if let Some(doc) = doc {
+                    for line in doc.split('\n') {
+                        self.out.push_str("///");
+                        if !line.is_empty() {
+                            self.out.push(' ');
+                            self.out.push_str(line);
+                        }
+                        self.newline();
+                    }
+                }
+                if *public {
+                    self.out.push_str("pub ");
+                }
+                if *distributed {
+                    self.out.push_str("dis ");
+                }
+                self.out.push_str("func ");
+                self.out.push_str(name);
+                if !type_params.is_empty() {
+                    let type_params: Vec<&str> = type_params.iter().map(|param| param.0.as_str()).collect();
+                    self.out.push_str(&format!("<{}>", type_params.join(", ")));
+                }
+                self.signature(params, param_types, return_type);
+                self.out.push(' ');
+                self.block(body);
+            }
+            StmtKind::Struct { name, fields } => {
+                let fields: Vec<String> = fields.iter().map(|(field, field_type)| format!("{}: {}", field.0, field_type)).collect();
+                self.out.push_str(&format!("struct {} {}", name.0, braced(&fields)));
+            }
+            StmtKind::Enum { name, variants } => {
+                let variants: Vec<String> = variants
+                    .iter()
+                    .map(|(variant, fields)| match fields.is_empty() {
+                        true => variant.0.clone(),
+                        false => format!("{}({})", variant.0, join(fields)),
+                    })
+                    .collect();
+                self.out.push_str(&format!("enum {} {}", name.0, braced(&variants)));
+            }
+            StmtKind::While { label, cond, body } => {
+                self.label(label);
+                self.out.push_str("while ");
+                self.without_struct_literals(|printer| printer.expression(cond));
+                self.out.push(' ');
+                self.block(body);
+            }
+            StmtKind::For { label, init, cond, step, body } => {
+                self.label(label);
+                self.out.push_str("for ");
+                self.without_struct_literals(|printer| {
+                    if let Some(init) = init {
+                        printer.clause(init);
+                    }
+                    printer.out.push(';');
+                    if let Some(cond) = cond {
+                        printer.out.push(' ');
+                        printer.expression(cond);
+                    }
+                    printer.out.push(';');
+                    if let Some(step) = step {
+                        printer.out.push(' ');
+                        printer.clause(step);
+                    }
+                });
+                self.out.push(' ');
+                self.block(body);
+            }
+            // A tail ends its block, where it must not have a semicolon.
+            StmtKind::Tail(expr) => self.expression(expr),
+            StmtKind::Blank => {}
+            _ => {
+                self.clause(statement);
+                self.out.push(';');
+            }
+        }
+    }
+
+    // clause writes a statement that is ended by a semicolon, without the
+    // semicolon. The parts of a `for` header are written this way.
+    fn clause(&mut self, statement: &Stmt) {
+        match &statement.kind {
+            StmtKind::Let(name, value, value_type) => {
+                self.out.push_str("let ");
+                self.out.push_str(&name.0);
+                self.initializer(value, value_type);
+            }
+            StmtKind::LetTuple(names, value, value_type) => {
+                let names: Vec<&str> = names.iter().map(|name| name.0.as_str()).collect();
+                self.out.push_str(&format!("let ({})", names.join(", ")));
+                self.initializer(value, value_type);
+            }
+            StmtKind::Assignment(name, value) => {
+                self.out.push_str(&name.0);
+                self.out.push_str(" = ");
+                self.expression(value);
+            }
+            StmtKind::IndexAssignment { array, index, op, value } => {
+                self.operand(array, precedence(array) < Precedence::Call);
+                self.out.push('[');
+                self.expression(index);
+                self.out.push(']');
+                self.assign_operator(op);
+                self.expression(value);
+            }
+            StmtKind::FieldAssignment { object, field, op, value } => {
+                self.operand(object, precedence(object) < Precedence::Call);
+                self.out.push_str(&format!(".{}", field.0));
+                self.assign_operator(op);
+                self.expression(value);
+            }
+            StmtKind::Import { name, path } => {
+                if *path == format!("{}.flash", name.0) {
+                    self.out.push_str(&format!("import {}", name.0));
+                } else {
+                    self.out.push_str(&format!("import {}", quote(path, '"')));
+                }
+            }
+            StmtKind::Return(expr) => {
+                self.out.push_str("return ");
+                self.expression(expr);
+            }
+            StmtKind::Expr(expr) | StmtKind::Tail(expr) => self.expression(expr),
+            StmtKind::Break(label) => self.jump("break", label),
+            StmtKind::Continue(label) => self.jump("continue", label),
+            _ => self.statement(statement),
+        }
+    }
+
+    fn initializer(&mut self, value: &Expr, value_type: &Option<LLVMExpressionType>) {
+        if let Some(value_type) = value_type {
+            self.out.push_str(&format!(": {}", value_type));
+        }
+        self.out.push_str(" = ");
+        self.expression(value);
+    }
+
+    fn assign_operator(&mut self, op: &Option<Infix>) {
+        match op {
+            Some(op) => self.out.push_str(&format!(" {}= ", op)),
+            None => self.out.push_str(" = "),
+        }
+    }
+
+    fn label(&mut self, label: &Option<Ident>) {
+        if let Some(label) = label {
+            self.out.push_str(&format!("{}: ", label.0));
+        }
+    }
+
+    fn jump(&mut self, keyword: &str, label: &Option<Ident>) {
+        self.out.push_str(keyword);
+        if let Some(label) = label {
+            self.out.push(' ');
+            self.out.push_str(&label.0);
+        }
+    }
+
+    fn signature(&mut self, params: &[Ident], param_types: &[LLVMExpressionType], return_type: &LLVMExpressionType) {
+        let params: Vec<String> = params
+            .iter()
+            .zip(param_types.iter())
+            .map(|(param, param_type)| format!("{}: {}", param.0, param_type))
+            .collect();
+        self.out.push_str(&format!("({}): {}", params.join(", "), return_type));
+    }
+
+    fn without_struct_literals(&mut self, print: impl FnOnce(&mut Self)) {
+        let saved = std::mem::replace(&mut self.no_struct_literal, true);
+        print(self);
+        self.no_struct_literal = saved;
+    }
+
+    // operand writes an expression that is part of a larger one, in
+    // parentheses when it binds more loosely than its place requires.
+    fn operand(&mut self, expr: &Expr, parenthesize: bool) {
+        if !parenthesize {
+            self.expression(expr);
+            return;
+        }
+        let saved = std::mem::replace(&mut self.no_struct_literal, false);
+        self.out.push('(');
+        self.expression(expr);
+        self.out.push(')');
+        self.no_struct_literal = saved;
+    }
+
+    fn expressions(&mut self, exprs: &[Expr]) {
+        for (index, expr) in exprs.iter().enumerate() {
+            if index > 0 {
+                self.out.push_str(", ");
+            }
+            self.expression(expr);
+        }
+    }
+
+    fn expression(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Ident(ident) => self.out.push_str(&ident.0),
+            ExprKind::Literal(literal) => self.out.push_str(&print_literal(literal)),
+            ExprKind::Prefix(prefix, right) => {
+                self.out.push_str(match prefix {
+                    Prefix::Plus => "+",
+                    Prefix::Minus => "-",
+                    Prefix::Not => "!",
+                });
+                // `--x` would not read back as two minus signs.
+                let parenthesize = precedence(right) < Precedence::Call || matches!(right.kind, ExprKind::Prefix(_, _));
+                self.operand(right, parenthesize);
+            }
+            ExprKind::Infix(infix, left, right) => {
+                let infix_precedence = infix_precedence(infix);
+                self.operand(left, precedence(left) < infix_precedence);
+                self.out.push_str(&format!(" {} ", infix_operator(infix)));
+                self.operand(right, precedence(right) <= infix_precedence);
+            }
+            ExprKind::Cast(value, target) => {
+                self.operand(value, precedence(value) < Precedence::Cast);
+                self.out.push_str(&format!(" as {}", target));
+            }
+            ExprKind::If { cond, consequence, alternative } => {
+                self.out.push_str("if (");
+                self.expression(cond);
+                self.out.push_str(") ");
+                self.block(consequence);
+                match alternative.as_deref() {
+                    Some([Stmt { kind: StmtKind::Tail(nested @ Expr { kind: ExprKind::If { .. }, .. }), .. }]) => {
+                        self.out.push_str(" else ");
+                        self.expression(nested);
+                    }
+                    Some(alternative) => {
+                        self.out.push_str(" else ");
+                        self.block(&alternative.to_vec());
+                    }
+                    None => {}
+                }
+            }
+            ExprKind::Call { func, args } => {
+                self.operand(func, precedence(func) < Precedence::Call);
+                self.out.push('(');
+                self.expressions(args);
+                self.out.push(')');
+            }
+            ExprKind::Array(elements) => {
+                self.out.push('[');
+                self.expressions(elements);
+                self.out.push(']');
+            }
+            ExprKind::Tuple(elements) => {
+                self.out.push('(');
+                self.expressions(elements);
+                self.out.push(')');
+            }
+            ExprKind::Hash(map_type, entries) => {
+                self.out.push_str(&format!("{}{{", map_type));
+                for (index, (key, value)) in entries.iter().enumerate() {
+                    if index > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.expression(key);
+                    self.out.push_str(": ");
+                    self.expression(value);
+                }
+                self.out.push('}');
+            }
+            ExprKind::Index { array, index } => {
+                self.operand(array, precedence(array) < Precedence::Call);
+                self.out.push('[');
+                self.expression(index);
+                self.out.push(']');
+            }
+            ExprKind::Struct { name, fields } => {
+                let parenthesize = self.no_struct_literal;
+                if parenthesize {
+                    self.out.push('(');
+                }
+                let saved = std::mem::replace(&mut self.no_struct_literal, false);
+                self.out.push_str(&name.0);
+                self.out.push_str(" {");
+                for (index, (field, value)) in fields.iter().enumerate() {
+                    self.out.push_str(if index > 0 { ", " } else { " " });
+                    self.out.push_str(&format!("{}: ", field.0));
+                    self.expression(value);
+                }
+                self.out.push_str(if fields.is_empty() { "}" } else { " }" });
+                self.no_struct_literal = saved;
+                if parenthesize {
+                    self.out.push(')');
+                }
+            }
+            ExprKind::Field { object, field } => {
+                self.operand(object, precedence(object) < Precedence::Call);
+                self.out.push('.');
+                self.out.push_str(&field.0);
+            }
+            ExprKind::Variant { name, variant, args } => {
+                self.out.push_str(&format!("{}::{}", name.0, variant.0));
+                if !args.is_empty() {
+                    self.out.push('(');
+                    self.expressions(args);
+                    self.out.push(')');
+                }
+            }
+            ExprKind::Match { value, arms } => {
+                self.out.push_str("match ");
+                self.without_struct_literals(|printer| printer.expression(value));
+                self.out.push_str(" {");
+                self.indent += 1;
+                let saved = std::mem::replace(&mut self.no_struct_literal, false);
+                for arm in arms.iter() {
+                    self.newline();
+                    self.out.push_str(&print_pattern(&arm.pattern));
+                    if let Some(guard) = &arm.guard {
+                        self.out.push_str(" if ");
+                        self.expression(guard);
+                    }
+                    self.out.push_str(" => ");
+                    match arm.body.as_slice() {
+                        [Stmt { kind: StmtKind::Tail(expr), .. }] => self.expression(expr),
+                        body => self.block(&body.to_vec()),
+                    }
+                    self.out.push(',');
+                }
+                self.no_struct_literal = saved;
+                self.indent -= 1;
+                if !arms.is_empty() {
+                    self.newline();
+                }
+                self.out.push('}');
+            }
+            ExprKind::Closure { params, param_types, return_type, body } => {
+                self.out.push_str("func");
+                self.signature(params, param_types, return_type);
+                self.out.push(' ');
+                self.block(body);
+            }
+        }
+    }
+}
+
+// precedence returns how tightly an expression binds. Calls, indexing and
+// everything that cannot be split bind tightest; an `if`, a `match` or a
+// closure is put in parentheses whenever it is an operand.
+fn precedence(expr: &Expr) -> Precedence {
+    match &expr.kind {
+        ExprKind::Infix(infix, _, _) => infix_precedence(infix),
+        ExprKind::Cast(_, _) => Precedence::Cast,
+        ExprKind::Prefix(_, _) => Precedence::Prefix,
+        ExprKind::If { .. } | ExprKind::Match { .. } | ExprKind::Closure { .. } => Precedence::Lowest,
+        _ => Precedence::Index,
+    }
+}
+
+fn infix_precedence(infix: &Infix) -> Precedence {
+    match infix {
+        Infix::Or => Precedence::LogicalOr,
+        Infix::And => Precedence::LogicalAnd,
+        Infix::Equal | Infix::NotEqual => Precedence::Equals,
+        Infix::LessThan | Infix::GreaterThan | Infix::LessEqual | Infix::GreaterEqual => Precedence::LessGreater,
+        Infix::BitOr => Precedence::BitOr,
+        Infix::BitXor => Precedence::BitXor,
+        Infix::BitAnd => Precedence::BitAnd,
+        Infix::ShiftLeft | Infix::ShiftRight => Precedence::Shift,
+        Infix::Plus | Infix::Minus => Precedence::Sum,
+        Infix::Multiply | Infix::Divide | Infix::Modulo => Precedence::Product,
+    }
+}
+
+fn infix_operator(infix: &Infix) -> &'static str {
+    match infix {
+        Infix::Plus => "+",
+        Infix::Minus => "-",
+        Infix::Multiply => "*",
+        Infix::Divide => "/",
+        Infix::Modulo => "%",
+        Infix::Equal => "==",
+        Infix::NotEqual => "!=",
+        Infix::LessThan => "<",
+        Infix::GreaterThan => ">",
+        Infix::LessEqual => "<=",
+        Infix::GreaterEqual => ">=",
+        Infix::And => "&&",
+        Infix::Or => "||",
+        Infix::BitAnd => "&",
+        Infix::BitOr => "|",
+        Infix::BitXor => "^",
+        Infix::ShiftLeft => "<<",
+        Infix::ShiftRight => ">>",
+    }
+}
+
+// Operators display as they are written, so that errors can name them.
+impl std::fmt::Display for Infix {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(infix_operator(self))
+    }
+}
+
+fn print_literal(literal: &Literal) -> String {
+    match literal {
+        Literal::Int(value, None) => value.to_string(),
+        Literal::Int(value, Some(kind)) => format!("{}{}", value, kind.name()),
+        // Debug keeps the fraction of whole numbers, `2.0`, so the literal
+        // reads back as a float.
+        Literal::Float(value) => format!("{:?}", value),
+        Literal::Bool(value) => value.to_string(),
+        Literal::Char(value) => quote(&value.to_string(), '\''),
+        Literal::String(value) => quote(value, '"'),
+    }
+}
+
+// quote writes a string or char literal with the escapes the lexer reads.
+fn quote(value: &str, delimiter: char) -> String {
+    let mut quoted = String::from(delimiter);
+    for chr in value.chars() {
+        match chr {
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            '\0' => quoted.push_str("\\0"),
+            '\\' => quoted.push_str("\\\\"),
+            chr if chr == delimiter => {
+                quoted.push('\\');
+                quoted.push(chr);
+            }
+            chr if chr.is_control() => quoted.push_str(&format!("\\u{{{:x}}}", chr as u32)),
+            chr => quoted.push(chr),
+        }
+    }
+    quoted.push(delimiter);
+    quoted
+}
+
+fn print_pattern(pattern: &Pattern) -> String {
+    match pattern {
+        Pattern::Wildcard => "_".to_owned(),
+        Pattern::Binding(name) => name.0.clone(),
+        Pattern::Literal(literal) => print_expression(literal),
+        Pattern::Variant { name, variant, fields } if fields.is_empty() => format!("{}::{}", name.0, variant.0),
+        Pattern::Variant { name, variant, fields } => {
+            let fields: Vec<String> = fields.iter().map(print_pattern).collect();
+            format!("{}::{}({})", name.0, variant.0, fields.join(", "))
+        }
+    }
+}
+
+fn is_declaration(statement: &Stmt) -> bool {
+    matches!(statement.kind, StmtKind::Func { .. } | StmtKind::Struct { .. } | StmtKind::Enum { .. })
+}
+
+fn join(types: &[LLVMExpressionType]) -> String {
+    let types: Vec<String> = types.iter().map(|part_type| part_type.to_string()).collect();
+    types.join(", ")
+}
+
+// braced writes the members of a struct or an enum declaration on one line.
+fn braced(members: &[String]) -> String {
+    match members.is_empty() {
+        true => "{}".to_owned(),
+        false => format!("{{ {} }}", members.join(", ")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::new_lexer;
+    use crate::parser::Parser;
+
+    fn parse(input: &str) -> Program {
+        Parser::new(new_lexer(input)).parse().unwrap().remove(0)
+    }
+
+    // round_trip checks that the printed program parses back to the same
+    // tree and that printing is stable.
+    fn round_trip(input: &str) -> String {
+        let program = parse(input);
+        let printed = print_program(&program);
+        assert_eq!(parse(&printed), program, "{}", printed);
+        assert_eq!(print_program(&parse(&printed)), printed);
+        printed
+    }
+
+    const SOURCE: &str = r#"import math;
+import "lib/geo shapes.flash";
+struct Point { x: int, y: int }
+struct Unit {}
+enum Shape { Circle(float), Rect(Point, Point), Empty }
+/// Sums two ints.
+///
+/// Both are positive.
+pub func add(x: int, y: int): int { return x + y; }
+dis func remote(): void {}
+pub func max<T, U>(a: T, b: U): T { return a; }
+func main(): int {
+    let p: Point = Point { x: 1, y: -2 };
+    let q = Point {};
+    let r: geo.Rect = geo.Rect { w: 1 };
+    match geo.Shape::Dot(r) { geo.Shape::Dot(_) => 1, _ => 0 };
+    let (a, _): (int, bool) = (1, true);
+    let (b, c) = (a, (2, 3u8));
+    let xs: [int; 3] = [1, 2, 3];
+    let m = map[string][i64; 2]{"a\n\"b\"": [1i64, 2i64], "\\": [0i64, 0i64]};
+    let chars = ['a', '\'', '\t', '\0', '"'];
+    let f = 2.5 + 1e20 + 3.0;
+    let g: func(int): int = func(v: int): int { v * 2 };
+    p.x = 3;
+    xs[0] += 1;
+    foo.bar[1].baz = f(2).x;
+    a = -(-a) + !!true as int;
+    outer: while a < 10 && !(b == 2) {
+        for let i = 0; i < 3; i += 1 {
+            if (i == 1) { continue outer; } else if (i == 2) { break; } else { a = a + 1; }
+        }
+        for ;; { break outer; }
+        for ; a > 0; {}
+        while (Point { x: 1, y: 2 }).x > a { a += 1; }
+    }
+    let s = match Shape::Rect(p, Point { x: 0, y: 0 }) {
+        Shape::Circle(r) if r > 1.0 => 1,
+        Shape::Rect(Point, _) => { a; 2 },
+        Shape::Empty => {},
+        _ => 3,
+    };
+    match a { -1 => 0, 'x' => 1, "s" => 2, v => v, }
+    match b {}
+    let t = (if (a > 1) { 1 } else { 2 }) + (match a { _ => 1 }) * 3;
+    (func(): int { 1 })();
+    t.0.1;
+    return (a - (b - c)) * (a + b) % 2 << 1 & 7 | 8 ^ (a as u8 as int);
+}
+let top = add(1, 2);
+printf("%d\n", top);
+"#;
+
+    #[test]
+    fn test_round_trip() {
+        round_trip(SOURCE);
+        for input in ["", "1;", "return 1 - 2 - 3;", "return 1 - (2 - 3);", "x = (1 + 2) * 3;", "a[1][2] = f(g)(h);"] {
+            round_trip(input);
+        }
+    }
+
+    #[test]
+    fn test_print() {
+        let printed = round_trip("struct P {x:int}\nlet  a:int=1 ;;let b=a*(2+3)-(4-5);\nfunc f(): void {   }\nfunc g(a: int): int { if (a) { return 1; } else if (a > 2) { 2 } else { 3 } }\nf();");
+        assert_eq!(printed, "struct P { x: int }
+
+let a: int = 1;
+let b = a * (2 + 3) - (4 - 5);
+
+func f(): void {}
+
+func g(a: int): int {
+    if (a) {
+        return 1;
+    } else if (a > 2) {
+        2
+    } else {
+        3
+    }
+}
+
+f();
+");
+
+        let printed = round_trip("/// Doc.\n///\nfunc f(): int { outer: for let i: int = 0; i < 2; i = i + 1 { while (P{x:1}).x { break outer; } } match p { P::A(x) if x > 0 => x, _ => { 0 } } }");
+        assert_eq!(printed, "/// Doc.
+///
+func f(): int {
+    outer: for let i: int = 0; i < 2; i = i + 1 {
+        while (P { x: 1 }).x {
+            break outer;
+        }
+    }
+    match p {
+        P::A(x) if x > 0 => x,
+        _ => 0,
+    }
+}
+");
+    }
+
+    #[test]
+    fn test_print_expression() {
+        let program = parse("-(1 + 2) * -x.y[0] as u8; (a || b) && !c; \"tab\\there\"; 'q';");
+        let printed: Vec<String> = program.iter().map(print_statement).collect();
+        assert_eq!(printed, vec!["-(1 + 2) * -x.y[0] as u8;", "(a || b) && !c;", "\"tab\\there\";", "'q';"]);
+
+        if let StmtKind::Expr(expr) = &program[0].kind {
+            assert_eq!(print_expression(expr), "-(1 + 2) * -x.y[0] as u8");
+        }
+    }
+}